@@ -1,95 +1,766 @@
-use std::{collections::HashMap, default, path::PathBuf};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, VecDeque}, default, net::Ipv4Addr, path::{Path, PathBuf}, sync::{atomic::AtomicBool, Arc}, thread::JoinHandle, time::{Duration, Instant}};
 
 use eframe::egui;
 use egui_file_dialog::FileDialog;
 use egui::{Color32, RichText, Widget};
 use network_interface::{NetworkInterface, NetworkInterfaceConfig};
 
-use crate::network::{self, NetworkProfile};
+use crate::audit;
+use crate::diagnostics;
+use crate::elevation::{self, ElevationMethod, ElevationStatus};
+use crate::file_operations::{self, ProfileFormat};
+use crate::import_preview::{ImportAction, ImportPreview};
+use crate::link_watcher::LinkWatcher;
+use crate::loader::{LoaderAction, ProfileLoader};
+use crate::network::{self, AdapterConfig, ApplyOutcome, Backend, MaskFormat, NetworkProfile};
+use crate::notification::{self, NotificationKind};
+use crate::palette::{CommandPalette, PaletteAction};
+use crate::settings::{Settings, SettingsAction, Theme};
 
 #[derive(serde::Deserialize, serde::Serialize)]
 #[derive(Default)]
 #[serde(default)]
 pub struct NetProfiler {
     pub profiles: HashMap<String, network::NetworkProfile>,
+    /// Names in the order the profile list shows them, which the user can rearrange
+    pub profile_order: Vec<String>,
+    /// Name of the profile applied by the panic button to recover connectivity
+    pub safe_profile: Option<String>,
+    /// Profile and interface remembered by the quick apply bar
+    pub quick_profile: Option<String>,
+    pub quick_adapter: String,
+    pub elevation_method: ElevationMethod,
+    /// Shared key for signing exports and verifying imports, empty for checksum only
+    pub signing_key: String,
+    /// Subnet mask format imported profiles are normalized to, or None to keep them as written
+    pub import_mask_format: Option<MaskFormat>,
+    pub settings: Settings,
+    /// Preferences saved before they moved into `settings`, read once and never written back
+    #[serde(rename = "os_notifications", skip_serializing)]
+    legacy_os_notifications: bool,
+    #[serde(rename = "compact_export", skip_serializing)]
+    legacy_compact_export: bool,
+    #[serde(rename = "provider_dns", skip_serializing)]
+    legacy_provider_dns: HashMap<network::DNSProvider, [String; 2]>,
+    /// List profiles under a header for their first tag
+    pub group_by_tag: bool,
     #[serde(skip)]
     pub adapters: Vec<String>,
+    #[serde(skip)]
+    adapter_error: Option<String>,
+    /// Readout of the interface with the default route, refreshed in the background
+    #[serde(skip)]
+    primary_status: Option<String>,
+    #[serde(skip)]
+    status_poll: Option<JoinHandle<Option<String>>>,
+    #[serde(skip)]
+    status_polled_at: Option<Instant>,
+    /// Profiles as last written to the library file, to tell when they need writing again
+    #[serde(skip)]
+    library_saved: Option<HashMap<String, NetworkProfile>>,
 
     // Private fields:
     #[serde(skip)]
     file_dialog: FileDialog,
     #[serde(skip)]
-    import_export: bool, // 0 = import, 1 = export
+    file_action: FileAction,
     #[serde(skip)]
     builder: Option<network::NetworkProfile>,
+    #[serde(skip)]
+    builder_warning: Option<String>,
+    #[serde(skip)]
+    builder_history: EditHistory,
+    #[serde(skip)]
+    loader: Option<ProfileLoader>,
+    /// Applies started outside the loader, running off the UI thread
+    #[serde(skip)]
+    applies: Vec<BackgroundApply>,
+    /// How the last of those applies finished
+    #[serde(skip)]
+    apply_status: Option<String>,
+    #[serde(skip)]
+    json_editor: Option<JsonEditor>,
+    #[serde(skip)]
+    rename: Option<ProfileRename>,
+    #[serde(skip)]
+    import_preview: Option<ImportPreview>,
+    #[serde(skip)]
+    find_replace: Option<FindReplace>,
+    /// Profiles ticked for bulk edits
+    #[serde(skip)]
+    checked: BTreeSet<String>,
+    /// Tag groups folded away while grouping by tag
+    #[serde(skip)]
+    collapsed_groups: BTreeSet<String>,
+    /// Where "Set DNS for selected" copies DNS from: a provider preset or another profile
+    #[serde(skip)]
+    bulk_dns: DnsSource,
+    #[serde(skip)]
+    bulk_status: Option<String>,
+    /// "Delete Selected" was clicked and is waiting on the user to confirm it
+    #[serde(skip)]
+    confirm_bulk_delete: bool,
+    /// Profile file last imported or exported, which "Watch File" follows
+    #[serde(skip)]
+    open_file: Option<PathBuf>,
+    #[serde(skip)]
+    watcher: Option<file_operations::FileWatcher>,
+    /// Runs while any profile auto applies, to notice its adapter coming up
+    #[serde(skip)]
+    link_watcher: Option<LinkWatcher>,
+    /// Profiles from the watched file waiting on the user to accept or ignore them
+    #[serde(skip)]
+    pending_reload: Option<HashMap<String, NetworkProfile>>,
+    #[serde(skip)]
+    palette: Option<CommandPalette>,
+    #[serde(skip)]
+    diagnostics: Option<String>,
+    /// Interface shown in the Adapter Configuration window and what was read from it, None while closed
+    #[serde(skip)]
+    adapter_config: Option<(String, Option<Result<AdapterConfig, String>>)>,
+    /// Reads the configuration off the UI thread, since it runs external tools
+    #[serde(skip)]
+    adapter_config_read: Option<JoinHandle<Result<AdapterConfig, String>>>,
+    #[serde(skip)]
+    settings_open: bool,
+    /// How many restart reasons the user has already answered "Later" to
+    #[serde(skip)]
+    restart_dismissed: usize,
+    /// Search text narrowing the profile list by name or address
+    #[serde(skip)]
+    profile_filter: String,
+    /// Profile highlighted for keyboard navigation, as an index into the filtered list
+    #[serde(skip)]
+    selected: Option<usize>,
+    #[serde(skip)]
+    elevated: bool,
+    #[serde(skip)]
+    elevation_error: Option<String>,
+}
+
+/// Raw JSON being edited for a profile, kept as text until it parses and validates
+struct JsonEditor {
+    /// Name the profile is stored under, which the edit may rename
+    original: String,
+    text: String,
+    error: Option<String>,
+}
+
+/// A profile's name being edited in place in the list, started by double-clicking it
+struct ProfileRename {
+    original: String,
+    name: String,
+    error: Option<String>,
+    /// Whether the name field still needs focusing, on the frame it appears or after an error
+    focus: bool,
+}
+
+/// How many earlier states of the profile being built can be undone
+const UNDO_LIMIT: usize = 20;
+
+/// Undo and redo for the profile builder, holding the profile as it was before each edit
+#[derive(Default)]
+struct EditHistory {
+    undo: VecDeque<NetworkProfile>,
+    redo: Vec<NetworkProfile>,
+    /// Text field the last edit was typed into, whose keystrokes undo together
+    typing: Option<egui::Id>,
+}
+
+impl EditHistory {
+    /// Records `before` if the profile has changed since, unless it's more typing in the same field.
+    fn record(&mut self, before: NetworkProfile, after: &NetworkProfile, typing: Option<egui::Id>) {
+        if before == *after {
+            return;
+        }
+        if typing.is_none() || typing != self.typing {
+            if self.undo.len() == UNDO_LIMIT {
+                self.undo.pop_front();
+            }
+            self.undo.push_back(before);
+        }
+        self.redo.clear();
+        self.typing = typing;
+    }
+
+    fn undo(&mut self, profile: &mut NetworkProfile) {
+        if let Some(previous) = self.undo.pop_back() {
+            self.redo.push(std::mem::replace(profile, previous));
+            self.typing = None;
+        }
+    }
+
+    fn redo(&mut self, profile: &mut NetworkProfile) {
+        if let Some(next) = self.redo.pop() {
+            self.undo.push_back(std::mem::replace(profile, next));
+            self.typing = None;
+        }
+    }
+}
+
+/// A profile being applied on a worker thread, so the window stays responsive while tools run
+struct BackgroundApply {
+    name: String,
+    adapter: String,
+    handle: JoinHandle<ApplyOutcome>,
+}
+
+impl BackgroundApply {
+    fn start(profile: NetworkProfile) -> Self {
+        Self {
+            name: profile.name.clone(),
+            adapter: profile.target_adapter().to_string(),
+            handle: std::thread::spawn(move || profile.load_cancellable(&AtomicBool::new(false))),
+        }
+    }
+}
+
+/// How often the status bar re-reads the primary interface
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long after an apply to an adapter its link coming up is put down to that apply, rather
+/// than triggering an auto apply that would undo it
+const AUTO_APPLY_GRACE: Duration = Duration::from_secs(30);
+
+#[derive(Clone, PartialEq)]
+enum DnsSource {
+    Provider(network::DNSProvider),
+    Profile(String),
+}
+
+impl Default for DnsSource {
+    fn default() -> Self {
+        DnsSource::Provider(network::DNSProvider::Quad9)
+    }
+}
+
+/// Address find and replace across every profile
+#[derive(Default)]
+struct FindReplace {
+    find: String,
+    replace: String,
+    scope: network::ReplaceScope,
+}
+
+/// Actions reachable from the command palette
+#[derive(Clone)]
+enum Command {
+    AddProfile,
+    Import,
+    Export,
+    ExportSigned,
+    ExportAuditLog,
+    RunDiagnostics,
+    ShowAdapterConfig,
+    RefreshInterfaces,
+    FindReplace,
+    OpenSettings,
+    ApplySafeProfile,
+    Apply(String),
+    Preview(String),
+    ToggleNotifications,
+    ToggleCompactExport,
+}
+
+/// What the file dialog's selected path is used for
+#[derive(Default)]
+enum FileAction {
+    #[default]
+    Import,
+    Export,
+    /// Export of the one profile with this name
+    ExportProfile(String),
+    ExportSigned,
+    SaveReport,
+    ExportAuditLog,
+    /// New file for the profile library
+    ChooseLibrary,
 }
 
 impl NetProfiler {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+        let mut app: Self = Default::default();
+        if let Some(blob) = cc.storage.and_then(|storage| storage.get_string(eframe::APP_KEY)) {
+            match ron::from_str(&blob) {
+                Ok(saved) => app = saved,
+                Err(e) => backup_corrupt_state(&blob, &e),
+            }
         }
 
-        Default::default()
+        // The library file wins over the saved state, so hand edits to it take effect
+        let library = app.settings.library_path();
+        if library.exists() {
+            match file_operations::load_library(&library) {
+                Ok(profiles) => {
+                    app.profiles = profiles;
+                    app.library_saved = Some(app.profiles.clone());
+                }
+                Err(e) => backup_corrupt_library(&library, &e),
+            }
+        }
+
+        app.settings.os_notifications |= std::mem::take(&mut app.legacy_os_notifications);
+        app.settings.compact_export |= std::mem::take(&mut app.legacy_compact_export);
+        app.settings.provider_dns.extend(std::mem::take(&mut app.legacy_provider_dns));
+        // Older versions saved whatever was typed, valid or not
+        app.settings.provider_dns.retain(|_, servers| servers.iter().all(|server| server.parse::<Ipv4Addr>().is_ok()));
+        network::set_provider_overrides(&app.settings.provider_dns);
+        app.elevated = elevation::is_elevated();
+        app.file_dialog = FileDialog::new()
+            .add_file_filter("Profiles", Arc::new(|path| ProfileFormat::from_path(path).is_some()))
+            .add_file_filter("JSON (.nprf, .json)", Arc::new(|path| ProfileFormat::from_path(path) == Some(ProfileFormat::Json)))
+            .add_file_filter("TOML (.toml)", Arc::new(|path| ProfileFormat::from_path(path) == Some(ProfileFormat::Toml)))
+            .add_file_filter("YAML (.yaml)", Arc::new(|path| ProfileFormat::from_path(path) == Some(ProfileFormat::Yaml)));
+        app
+    }
+
+    /// Brings the list order in line with the stored profiles: removed ones are dropped and new
+    /// ones go at the end, sorted so a first run doesn't list them in hash order.
+    fn sync_profile_order(&mut self) {
+        self.profile_order.retain(|name| self.profiles.contains_key(name));
+        let mut added: Vec<String> = self.profiles.keys()
+            .filter(|name| !self.profile_order.contains(name))
+            .cloned()
+            .collect();
+        added.sort();
+        self.profile_order.extend(added);
+    }
+
+    /// Whether a profile is already stored under the name.
+    pub fn name_exists(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+
+    /// Stores `profile` in place of the one named `original`, keeping its place in the list and
+    /// anything referring to it by name.
+    fn replace_profile(&mut self, original: &str, profile: NetworkProfile) {
+        self.profiles.remove(original);
+        for reference in [&mut self.safe_profile, &mut self.quick_profile] {
+            if reference.as_deref() == Some(original) {
+                *reference = Some(profile.name.clone());
+            }
+        }
+        if self.checked.remove(original) {
+            self.checked.insert(profile.name.clone());
+        }
+        if let Some(entry) = self.profile_order.iter_mut().find(|entry| *entry == original) {
+            *entry = profile.name.clone();
+        }
+        self.profiles.insert(profile.name.clone(), profile);
+    }
+
+    /// Deletes the profile, along with anything referring to it by name.
+    fn remove_profile(&mut self, name: &str) {
+        self.profiles.remove(name);
+        self.checked.remove(name);
+        for reference in [&mut self.safe_profile, &mut self.quick_profile] {
+            if reference.as_deref() == Some(name) {
+                *reference = None;
+            }
+        }
+    }
+
+    /// Renames a profile to the name typed in place, refusing names that are empty or taken.
+    fn finish_rename(&mut self) {
+        let Some((original, name)) = self.rename.as_ref().map(|rename| (rename.original.clone(), rename.name.trim().to_string())) else {
+            return;
+        };
+        let error = match name.as_str() {
+            "" => Some("Profile name is empty".to_string()),
+            _ if name != original && self.name_exists(&name) => Some(format!("A profile named {} already exists", name)),
+            _ => None,
+        };
+        if let Some(rename) = self.rename.as_mut().filter(|_| error.is_some()) {
+            rename.error = error;
+            rename.focus = true;
+            return;
+        }
+        self.rename = None;
+        if let Some(profile) = self.profiles.get(&original).filter(|_| name != original) {
+            let profile = NetworkProfile { name, ..profile.clone() };
+            self.replace_profile(&original, profile);
+        }
+    }
+
+    /// Opens the save dialog for the action, suggesting the file name when it isn't empty.
+    fn save_file(&mut self, action: FileAction, file_name: &str) {
+        self.file_action = action;
+        self.file_dialog.config_mut().default_file_name = file_name.to_string();
+        self.file_dialog.save_file();
+    }
+
+    /// Writes the profiles to the library file when they've changed since it was last written.
+    fn autosave_library(&mut self) {
+        if self.library_saved.as_ref() == Some(&self.profiles) {
+            return;
+        }
+        if let Err(e) = file_operations::save_library(&self.settings.library_path(), &self.profiles) {
+            log::error!("Error saving profile library: {}", e);
+        }
+        // Recorded even on failure, so a read-only directory isn't retried every frame
+        self.library_saved = Some(self.profiles.clone());
+    }
+
+    /// Whether a profile is being applied to the adapter, by this window or anything else.
+    fn is_applying(&self, adapter: &str) -> bool {
+        network::is_applying(adapter) || self.applies.iter().any(|apply| apply.adapter == adapter)
+    }
+
+    /// Applies the auto apply profile for an adapter that just came up, unless a profile is being
+    /// or was just applied to it.
+    fn auto_apply(&mut self, adapter: &str) {
+        let mut candidates = self.profile_order.iter()
+            .filter_map(|name| self.profiles.get(name))
+            .filter(|profile| profile.auto_apply && profile.bound_adapter().unwrap_or_else(|| profile.adapter.clone()) == adapter);
+        let Some(profile) = candidates.next() else {
+            return;
+        };
+        if let Some(other) = candidates.next() {
+            log::warn!("{} and {} both auto apply on {}, applying {}", profile.name, other.name, adapter, profile.name);
+        }
+        let profile = NetworkProfile { adapter: adapter.to_string(), ..profile.clone() };
+        let target = profile.target_adapter();
+        if network::applied_within(target, AUTO_APPLY_GRACE) || self.is_applying(target) {
+            log::info!("Not auto applying {} to {}, a profile was just applied there", profile.name, target);
+            return;
+        }
+        log::info!("{} came up, auto applying {}", adapter, profile.name);
+        self.applies.push(BackgroundApply::start(profile));
+    }
+
+    /// Applies `profile` in the background, or through the loader's confirmation, with its
+    /// disconnect warning and revert option, when the settings ask to confirm applies.
+    fn apply_profile(&mut self, profile: NetworkProfile) {
+        match self.settings.confirm_apply {
+            true => {
+                let mut loader = ProfileLoader::new(profile, true);
+                loader.apply();
+                self.loader = Some(loader);
+            }
+            false => self.applies.push(BackgroundApply::start(profile)),
+        }
+    }
+
+    /// Opens the Adapter Configuration window on `adapter`, reading its current configuration on a
+    /// worker thread. A read still running for another interface is left to finish and discarded.
+    fn show_adapter_config(&mut self, adapter: String) {
+        if adapter.is_empty() {
+            self.adapter_config_read = None;
+            self.adapter_config = Some((adapter, Some(Err("No network interfaces found".to_string()))));
+            return;
+        }
+        let read = adapter.clone();
+        self.adapter_config_read = Some(std::thread::spawn(move || network::show_adapter_config(&read)));
+        self.adapter_config = Some((adapter, None));
+    }
+
+    /// Re-enumerates interfaces and elevation, which may have changed since startup
+    /// if permissions were granted some other way mid-session.
+    pub fn refresh_adapters(&mut self) {
+        match network::list_adapters() {
+            Ok(adapters) => {
+                self.adapters = adapters;
+                self.adapter_error = None;
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                self.adapter_error = Some(e);
+            }
+        }
+        self.elevated = elevation::is_elevated();
+    }
+
+    /// Everything the command palette can run, labelled as it's searched.
+    fn commands(&self) -> Vec<(String, Command)> {
+        let mut commands = vec![
+            ("Add Profile".to_string(), Command::AddProfile),
+            ("Import".to_string(), Command::Import),
+            ("Export".to_string(), Command::Export),
+            ("Export Signed".to_string(), Command::ExportSigned),
+            ("Export Audit Log".to_string(), Command::ExportAuditLog),
+            ("Run Diagnostics".to_string(), Command::RunDiagnostics),
+            ("Show Adapter Configuration".to_string(), Command::ShowAdapterConfig),
+            ("Refresh Interfaces".to_string(), Command::RefreshInterfaces),
+            ("Find & Replace".to_string(), Command::FindReplace),
+            ("Settings".to_string(), Command::OpenSettings),
+            ("Settings: Toggle OS Notifications".to_string(), Command::ToggleNotifications),
+            ("Settings: Toggle Compact Export".to_string(), Command::ToggleCompactExport),
+        ];
+        if let Some(name) = &self.safe_profile {
+            commands.push((format!("Panic: Apply Safe Profile ({})", name), Command::ApplySafeProfile));
+        }
+        for name in self.profiles.keys() {
+            commands.push((format!("Apply {}", name), Command::Apply(name.clone())));
+            commands.push((format!("Preview {}", name), Command::Preview(name.clone())));
+        }
+        commands
+    }
+
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::AddProfile => {
+                self.builder = Some(network::NetworkProfile {
+                    name: "New Profile".to_string(),
+                    subnet: self.settings.new_profile_subnet(),
+                    dns_provider: self.settings.default_dns.clone(),
+                    ..Default::default()
+                });
+            }
+            Command::Import => {
+                self.file_action = FileAction::Import;
+                self.file_dialog.select_file();
+            }
+            Command::Export => self.save_file(FileAction::Export, ""),
+            Command::ExportSigned => self.save_file(FileAction::ExportSigned, ""),
+            Command::ExportAuditLog => self.save_file(FileAction::ExportAuditLog, ""),
+            Command::RunDiagnostics => {
+                self.diagnostics = Some(diagnostics::format_report(&diagnostics::run_diagnostics()));
+            }
+            Command::ShowAdapterConfig => {
+                let adapter = self.adapters.first().cloned().unwrap_or_default();
+                self.show_adapter_config(adapter);
+            }
+            Command::RefreshInterfaces => self.refresh_adapters(),
+            Command::FindReplace => self.find_replace = Some(FindReplace::default()),
+            Command::OpenSettings => self.settings_open = true,
+            Command::ApplySafeProfile => {
+                if let Some(profile) = self.safe_profile.as_ref().and_then(|name| self.profiles.get(name)) {
+                    self.applies.push(BackgroundApply::start(profile.clone()));
+                }
+            }
+            Command::Apply(name) => {
+                if let Some(profile) = self.profiles.get(&name).cloned() {
+                    self.apply_profile(profile);
+                }
+            }
+            Command::Preview(name) => {
+                if let Some(profile) = self.profiles.get(&name) {
+                    self.loader = Some(ProfileLoader::new(profile.clone(), self.settings.confirm_apply));
+                }
+            }
+            Command::ToggleNotifications => self.settings.os_notifications = !self.settings.os_notifications,
+            Command::ToggleCompactExport => self.settings.compact_export = !self.settings.compact_export,
+        }
+    }
+}
+
+/// Keeps a copy of saved state that failed to deserialize, so starting fresh doesn't lose the user's profiles.
+fn backup_corrupt_state(blob: &str, error: &ron::error::SpannedError) {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let file_path = eframe::storage_dir("Net Profiler")
+        .unwrap_or_default()
+        .join(format!("app.ron.{}.bak", timestamp));
+
+    log::error!("Error loading saved state: {}", error);
+    match std::fs::write(&file_path, blob) {
+        Ok(_) => log::warn!("Saved state backed up to {}", file_path.display()),
+        Err(e) => log::error!("Error backing up saved state: {}", e),
+    }
+}
+
+/// Moves a library file that doesn't parse out of the way, so autosaving doesn't write over hand edits.
+fn backup_corrupt_library(path: &Path, error: &str) {
+    let backup = path.with_extension("nprf.bak");
+    log::error!("{}", error);
+    match std::fs::rename(path, &backup) {
+        Ok(_) => log::warn!("Profile library backed up to {}", backup.display()),
+        Err(e) => log::error!("Error backing up profile library: {}", e),
     }
 }
 
 impl eframe::App for NetProfiler {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.autosave_library();
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.settings.apply_theme(ctx, frame);
+        self.autosave_library();
+
+        // Command palette
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::P)) {
+            self.palette = match self.palette {
+                Some(_) => None,
+                None => Some(CommandPalette::default()),
+            };
+        }
+        let commands = if self.palette.is_some() { self.commands() } else { Vec::new() };
+        if let Some(palette) = self.palette.as_mut() {
+            match palette.show(ctx, &commands) {
+                PaletteAction::Run(command) => {
+                    self.palette = None;
+                    self.run_command(command);
+                }
+                PaletteAction::Close => self.palette = None,
+                PaletteAction::None => {}
+            }
+        }
+
         // Check for file dialog events
         self.file_dialog.update(ctx);
         if let Some(file_path) = self.file_dialog.take_selected() {
-            if self.import_export {
+            if let FileAction::ChooseLibrary = self.file_action {
+                self.settings.library_path = Some(file_path);
+                // Written out on the next autosave
+                self.library_saved = None;
+            } else if let FileAction::ExportAuditLog = self.file_action {
+                let file_path = file_path.with_extension("csv");
+                match std::fs::copy(audit::audit_log_path(), &file_path) {
+                    Ok(_) => log::info!("Audit log exported to {}", file_path.display()),
+                    Err(e) => log::error!("Error exporting audit log: {}", e),
+                }
+            } else if let FileAction::SaveReport = self.file_action {
+                // Save the loader's change report, defaulting to markdown
+                let file_path = match file_path.extension() {
+                    Some(_) => file_path,
+                    None => file_path.with_extension("md"),
+                };
+                let report = self.loader.as_ref().map(|loader| loader.report()).unwrap_or_default();
+                match std::fs::write(&file_path, report) {
+                    Ok(_) => log::info!("Report saved to {}", file_path.display()),
+                    Err(e) => log::error!("Error saving report: {}", e),
+                }
+            } else if let FileAction::Import = self.file_action {
                 // Import the file
-                if let Ok(profiles) = serde_json::from_str::<HashMap<String, network::NetworkProfile>>(&std::fs::read_to_string(&file_path).unwrap()) {
-                    for (name, profile) in profiles {
-                        self.profiles.insert(name, NetworkProfile {
-                            adapter: String::new(),
-                            ..profile
-                        });
+                match file_operations::import_verified(&file_path, Some(&self.signing_key)) {
+                    Ok(mut profiles) => {
+                        if let Some(format) = self.import_mask_format {
+                            let converted = file_operations::normalize_masks(&mut profiles, format);
+                            log::info!("Normalized {} subnet masks to {:?}", converted, format);
+                        }
+                        self.import_preview = Some(ImportPreview::new(file_path.display().to_string(), profiles, &self.profiles));
+                        self.open_file = Some(file_path.clone());
                     }
+                    Err(e) => log::error!("{}", e),
                 }
-            } else {
-                // Remove adapter field from profiles
-                let mut export_profiles: HashMap<String, NetworkProfile> = HashMap::new();
-                for (name, profile) in self.profiles.iter() {
-                    export_profiles.insert(name.clone(), NetworkProfile {
-                        adapter: String::new(),
-                        ..profile.clone()
-                    });
+            } else if let FileAction::ExportProfile(name) = &self.file_action {
+                let file_path = match ProfileFormat::from_path(&file_path) {
+                    Some(_) => file_path,
+                    None => file_path.with_extension("nprf"),
+                };
+                // Same format as a full export, holding the one profile
+                let profiles: HashMap<String, NetworkProfile> = self.profiles.get_key_value(name)
+                    .map(|(name, profile)| (name.clone(), profile.clone()))
+                    .into_iter()
+                    .collect();
+                match file_operations::export_profiles_to_file(&file_path, &profiles, !self.settings.compact_export) {
+                    Ok(_) => log::info!("Profile {} saved successfully", name),
+                    Err(e) => log::error!("Error saving file: {}", e),
                 }
-
-                // Export the file
-                let file_path = PathBuf::from(file_path).with_extension("nprf");
-                let profiles = serde_json::to_string(&export_profiles).unwrap();
-                match std::fs::write(&file_path, profiles) {
-                    Ok(_) => println!("File saved successfully"),
-                    Err(e) => println!("Error saving file: {}", e),
+            } else if let FileAction::ExportSigned = self.file_action {
+                let file_path = file_path.with_extension("nprf");
+                match file_operations::export_signed(&file_path, &self.profiles, Some(&self.signing_key), !self.settings.compact_export) {
+                    Ok(_) => {
+                        log::info!("Signed profiles saved to {}", file_path.display());
+                        self.open_file = Some(file_path);
+                    }
+                    Err(e) => log::error!("Error saving file: {}", e),
+                }
+            } else {
+                // Export the file, in the format its extension names
+                let file_path = match ProfileFormat::from_path(&file_path) {
+                    Some(_) => file_path,
+                    None => file_path.with_extension("nprf"),
+                };
+                match file_operations::export_profiles_to_file(&file_path, &self.profiles, !self.settings.compact_export) {
+                    Ok(_) => {
+                        log::info!("Profiles saved to {}", file_path.display());
+                        self.open_file = Some(file_path);
+                    }
+                    Err(e) => log::error!("Error saving file: {}", e),
                 }
             }
         }
 
         // Profile Builder
         let mut finished = false;
+        let name_taken = self.builder.as_ref().is_some_and(|builder| self.name_exists(&builder.name));
         if let Some(ref mut builder) = self.builder.as_mut() {
+            // Text fields have their own undo for what's typed into them
+            let typing = ctx.memory(|mem| mem.focused()).filter(|id| egui::text_edit::TextEditState::load(ctx, *id).is_some());
+            if typing.is_none() {
+                let (undo, redo) = ctx.input_mut(|i| (
+                    i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z),
+                    i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y),
+                ));
+                if undo {
+                    self.builder_history.undo(builder);
+                }
+                if redo {
+                    self.builder_history.redo(builder);
+                }
+            }
+            let before = builder.clone();
+            // Set when this frame's change is an undo or redo rather than an edit
+            let mut stepped = false;
+
             egui::Window::new("Profile Builder").show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    ui.label("Profile Name:");
-                    ui.text_edit_singleline(&mut builder.name);
+                    if ui.add_enabled(!self.builder_history.undo.is_empty(), egui::Button::new("⟲"))
+                        .on_hover_text("Undo (Ctrl+Z)")
+                        .clicked()
+                    {
+                        self.builder_history.undo(builder);
+                        stepped = true;
+                    }
+                    if ui.add_enabled(!self.builder_history.redo.is_empty(), egui::Button::new("⟳"))
+                        .on_hover_text("Redo (Ctrl+Y)")
+                        .clicked()
+                    {
+                        self.builder_history.redo(builder);
+                        stepped = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let label = ui.label("Profile Name:");
+                    let id = egui::Id::new("builder_name");
+                    let name = ui.add(egui::TextEdit::singleline(&mut builder.name).id(id)).labelled_by(label.id);
+                    // Start keyboard users at the top of the builder's tab order when it opens
+                    if ui.data(|d| d.get_temp::<bool>(id).is_none()) {
+                        name.request_focus();
+                        ui.data_mut(|d| d.insert_temp(id, true));
+                    }
                 });
 
+                if let Some(warning) = &self.builder_warning {
+                    ui.label(RichText::new(format!("⚠ {}", warning)).color(ui.visuals().warn_fg_color));
+                }
+
                 display_profile(builder, ui, &self.adapters);
+                if !stepped {
+                    self.builder_history.record(before, builder, typing);
+                }
 
+                let name_empty = builder.name.trim().is_empty();
+                show_validation(ui, !name_empty, "Profile name is required");
+                if name_taken {
+                    ui.label(RichText::new(format!("⚠ A profile named {} already exists", builder.name)).color(ui.visuals().warn_fg_color));
+                }
                 ui.horizontal(|ui| {
-                    if ui.button("Create").clicked() {
-                        self.profiles.insert(builder.name.clone(), builder.clone());
-                        finished = true;
+                    match name_taken {
+                        true => {
+                            if ui.button("Overwrite").clicked() {
+                                self.profiles.insert(builder.name.clone(), builder.clone());
+                                finished = true;
+                            }
+                            if ui.button("Keep Both").on_hover_text("Create it under a numbered name").clicked() {
+                                builder.name = network::unique_profile_name(&self.profiles, &builder.name);
+                                self.profiles.insert(builder.name.clone(), builder.clone());
+                                finished = true;
+                            }
+                        }
+                        false => {
+                            if ui.add_enabled(!name_empty, egui::Button::new("Create")).clicked() {
+                                self.profiles.insert(builder.name.clone(), builder.clone());
+                                finished = true;
+                            }
+                        }
                     }
                     if ui.button("Cancel").clicked() {
                         finished = true;
@@ -99,6 +770,343 @@ impl eframe::App for NetProfiler {
         }
         if finished {
             self.builder = None;
+            self.builder_warning = None;
+            self.builder_history = EditHistory::default();
+            ctx.data_mut(|d| d.remove::<bool>(egui::Id::new("builder_name")));
+        }
+
+        // Diagnostics report
+        let mut close_diagnostics = false;
+        if let Some(report) = &self.diagnostics {
+            egui::Window::new("Diagnostics").show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.label(RichText::new(report).monospace());
+                });
+                close_diagnostics = ui.button("Close").clicked();
+            });
+        }
+        if close_diagnostics {
+            self.diagnostics = None;
+        }
+
+        // Changes that only take effect after a restart, such as a new Windows computer name
+        let restart_reasons = network::restart_reasons();
+        if restart_reasons.len() > self.restart_dismissed {
+            egui::Window::new("Restart Required")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label("A reboot is needed for some changes to take effect. Reboot now?");
+                    for reason in restart_reasons.iter() {
+                        ui.label(format!("• {}", reason));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Reboot Now").clicked() {
+                            if let Err(e) = network::restart_computer() {
+                                log::error!("Error restarting: {}", e);
+                                self.apply_status = Some(format!("Error restarting: {}", e));
+                            }
+                        }
+                        if ui.button("Later").clicked() {
+                            self.restart_dismissed = restart_reasons.len();
+                        }
+                    });
+                });
+        }
+
+        // Bulk delete confirmation
+        if self.confirm_bulk_delete {
+            let mut names: Vec<String> = self.checked.iter().cloned().collect();
+            names.sort();
+            egui::Window::new("Delete Profiles")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(format!("Delete {} profiles? This can't be undone.", names.len()));
+                    egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                        for name in names.iter() {
+                            ui.label(RichText::new(name).small());
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button(RichText::new("Delete").color(ui.visuals().error_fg_color)).clicked() {
+                            for name in names.iter() {
+                                self.remove_profile(name);
+                            }
+                            log::info!("Deleted {} profiles", names.len());
+                            self.bulk_status = None;
+                            self.confirm_bulk_delete = false;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.confirm_bulk_delete = false;
+                        }
+                    });
+                });
+        }
+
+        // Settings
+        let mut settings_open = self.settings_open;
+        match self.settings.show(ctx, &mut settings_open) {
+            SettingsAction::BrowseLibrary => self.save_file(FileAction::ChooseLibrary, "profiles.nprf"),
+            SettingsAction::LibraryMoved => self.library_saved = None,
+            SettingsAction::None => {}
+        }
+        self.settings_open = settings_open;
+
+        // Live configuration of an interface, read only
+        let mut selected_adapter = None;
+        if self.adapter_config_read.as_ref().is_some_and(|read| read.is_finished()) {
+            let config = self.adapter_config_read.take().unwrap().join()
+                .unwrap_or_else(|_| Err("Reading the configuration panicked".to_string()));
+            if let Some((_, pending)) = self.adapter_config.as_mut() {
+                *pending = Some(config);
+            }
+        }
+        if self.adapter_config_read.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        let mut adapter_config_open = self.adapter_config.is_some();
+        if let Some((adapter, config)) = &self.adapter_config {
+            egui::Window::new("Adapter Configuration").open(&mut adapter_config_open).show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Interface")
+                        .selected_text(adapter.as_str())
+                        .show_ui(ui, |ui| {
+                            for other in self.adapters.iter() {
+                                if ui.selectable_label(other == adapter, other).clicked() {
+                                    selected_adapter = Some(other.clone());
+                                }
+                            }
+                        });
+                    if ui.button("⟳").on_hover_text("Read the configuration again").clicked() {
+                        selected_adapter = Some(adapter.clone());
+                    }
+                });
+                match config {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Reading configuration...");
+                        });
+                    }
+                    Some(Ok(config)) => {
+                        let or_none = |values: &[String]| match values.is_empty() {
+                            true => "none".to_string(),
+                            false => values.join("\n"),
+                        };
+                        egui::Grid::new("adapter_config").num_columns(2).striped(true).show(ui, |ui| {
+                            let rows = [
+                                ("Addresses", or_none(&config.addresses)),
+                                ("Assigned by", if config.dhcp { "DHCP" } else { "static" }.to_string()),
+                                ("Gateways", or_none(&config.gateways)),
+                                ("DNS servers", or_none(&config.dns_servers)),
+                                ("MAC", config.mac.clone().unwrap_or_else(|| "none".to_string())),
+                                ("MTU", config.mtu.map(|mtu| mtu.to_string()).unwrap_or_else(|| "unknown".to_string())),
+                            ];
+                            for (label, value) in rows {
+                                ui.label(RichText::new(label).strong());
+                                ui.label(RichText::new(value).monospace());
+                                ui.end_row();
+                            }
+                        });
+                    }
+                    Some(Err(e)) => {
+                        ui.label(RichText::new(e).color(Color32::RED));
+                    }
+                }
+            });
+        }
+        if !adapter_config_open {
+            self.adapter_config = None;
+            self.adapter_config_read = None;
+        } else if let Some(adapter) = selected_adapter {
+            self.show_adapter_config(adapter);
+        }
+
+        // JSON editor
+        let mut close_editor = false;
+        let mut edited = None;
+        if let Some(editor) = self.json_editor.as_mut() {
+            egui::Window::new(format!("Edit {} as JSON", editor.original)).show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.add(egui::TextEdit::multiline(&mut editor.text).code_editor().desired_width(f32::INFINITY));
+                });
+                if let Some(error) = &editor.error {
+                    ui.label(RichText::new(format!("❌ {}", error)).color(Color32::RED));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        let parsed = serde_json::from_str::<NetworkProfile>(&editor.text)
+                            .map_err(|e| format!("Malformed profile: {}", e))
+                            .and_then(|profile| profile.validate().map(|_| profile).map_err(|e| e.to_string()));
+                        match parsed {
+                            Ok(profile) if profile.name != editor.original && self.profiles.contains_key(&profile.name) => {
+                                editor.error = Some(format!("A profile named {} already exists", profile.name));
+                            }
+                            Ok(profile) => {
+                                edited = Some((editor.original.clone(), profile));
+                                close_editor = true;
+                            }
+                            Err(e) => editor.error = Some(e),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close_editor = true;
+                    }
+                });
+            });
+        }
+        if let Some((original, profile)) = edited {
+            self.replace_profile(&original, profile);
+        }
+        if close_editor {
+            self.json_editor = None;
+        }
+
+        // Reload the watched file when it changes, unless it already matches the app
+        if self.watcher.as_ref().is_some_and(|watcher| watcher.changed()) {
+            let path = self.watcher.as_ref().map(|watcher| watcher.path.clone()).unwrap_or_default();
+            match file_operations::import_verified(&path, Some(&self.signing_key)) {
+                Ok(profiles) => {
+                    let current: HashMap<String, NetworkProfile> = self.profiles.iter()
+                        .map(|(name, profile)| (name.clone(), NetworkProfile { adapter: String::new(), ..profile.clone() }))
+                        .collect();
+                    if profiles != current {
+                        self.pending_reload = Some(profiles);
+                    }
+                }
+                // Editors can leave the file briefly half written
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+        let mut reload = None;
+        if self.pending_reload.is_some() {
+            let path = self.watcher.as_ref().map(|watcher| watcher.path.display().to_string()).unwrap_or_default();
+            egui::Window::new("File Changed").collapsible(false).show(ctx, |ui| {
+                ui.label(format!("{} changed on disk.", path));
+                ui.label("Reloading replaces the profiles in the app, including any edits made here.");
+                ui.horizontal(|ui| {
+                    if ui.button("Reload").clicked() {
+                        reload = Some(true);
+                    }
+                    if ui.button("Keep Mine").clicked() {
+                        reload = Some(false);
+                    }
+                });
+            });
+        }
+        if let Some(accepted) = reload {
+            let profiles = self.pending_reload.take().unwrap_or_default();
+            if accepted {
+                // Files don't carry adapters, so keep the ones chosen on this machine
+                self.profiles = profiles.into_iter()
+                    .map(|(name, profile)| {
+                        let adapter = self.profiles.get(&name).map(|existing| existing.adapter.clone()).unwrap_or_default();
+                        (name, NetworkProfile { adapter, ..profile })
+                    })
+                    .collect();
+                self.selected = None;
+            }
+        }
+
+        // Find and replace
+        let mut close_find_replace = false;
+        if let Some(tool) = self.find_replace.as_mut() {
+            egui::Window::new("Find & Replace").show(ctx, |ui| {
+                egui::Grid::new("find_replace").show(ui, |ui| {
+                    let label = ui.label("Find:");
+                    ui.text_edit_singleline(&mut tool.find).labelled_by(label.id);
+                    ui.end_row();
+                    let label = ui.label("Replace with:");
+                    ui.text_edit_singleline(&mut tool.replace).labelled_by(label.id);
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut tool.scope.ip, "IP");
+                    ui.checkbox(&mut tool.scope.gateway, "Gateway");
+                    ui.checkbox(&mut tool.scope.dns, "DNS");
+                });
+                ui.separator();
+
+                // Preview against copies so nothing changes until Replace is pressed
+                let mut preview = self.profiles.clone();
+                let result = network::replace_in_profiles(preview.values_mut(), &tool.find, &tool.replace, tool.scope);
+                let mut affected: Vec<&String> = preview.iter()
+                    .filter(|(name, profile)| self.profiles.get(*name) != Some(profile))
+                    .map(|(name, _)| name)
+                    .collect();
+                affected.sort();
+                match &result {
+                    Err(e) if !tool.replace.is_empty() => show_validation(ui, false, e),
+                    _ => {}
+                }
+                ui.label(format!("{} profiles affected", affected.len()));
+                for name in affected.iter() {
+                    ui.label(RichText::new(*name).weak());
+                }
+
+                ui.horizontal(|ui| {
+                    let ready = result.is_ok() && !affected.is_empty();
+                    if ui.add_enabled(ready, egui::Button::new("Replace")).clicked() {
+                        if let Ok(changed) = network::replace_in_profiles(self.profiles.values_mut(), &tool.find, &tool.replace, tool.scope) {
+                            log::info!("Replaced {} with {} in {} profiles", tool.find, tool.replace, changed);
+                        }
+                        close_find_replace = true;
+                    }
+                    if ui.button("Close").clicked() {
+                        close_find_replace = true;
+                    }
+                });
+            });
+        }
+        if close_find_replace {
+            self.find_replace = None;
+        }
+
+        // Import preview
+        if let Some(preview) = self.import_preview.as_mut() {
+            match preview.show(ctx) {
+                ImportAction::Import { profiles, replace_all, summary } => {
+                    log::info!("Import: {}", summary);
+                    if replace_all {
+                        self.profiles.clear();
+                    }
+                    self.profiles.extend(profiles.into_iter().map(|profile| (profile.name.clone(), profile)));
+                    if replace_all {
+                        for reference in [&mut self.safe_profile, &mut self.quick_profile] {
+                            if reference.as_ref().is_some_and(|name| !self.profiles.contains_key(name)) {
+                                *reference = None;
+                            }
+                        }
+                        self.checked.retain(|name| self.profiles.contains_key(name));
+                    }
+                    self.apply_status = Some(format!("Import: {}", summary));
+                    self.import_preview = None;
+                }
+                ImportAction::Close => self.import_preview = None,
+                ImportAction::None => {}
+            }
+        }
+
+        // Profile Loader
+        if let Some(loader) = self.loader.as_mut() {
+            match loader.show(ctx, self.settings.os_notifications) {
+                LoaderAction::SaveReport => self.save_file(FileAction::SaveReport, ""),
+                LoaderAction::Close => self.loader = None,
+                LoaderAction::None => {}
+            }
+        }
+        if let Some(mut previous) = self.loader.as_mut().and_then(|loader| loader.previous.take()) {
+            // An older snapshot of the adapter is replaced, a profile of the user's own is kept
+            let user_profile = self.profiles.get(&previous.name)
+                .is_some_and(|existing| !existing.metadata.contains_key("captured_at"));
+            if user_profile {
+                previous.name = network::unique_profile_name(&self.profiles, &previous.name);
+            }
+            log::info!("Saved the current configuration as {}", previous.name);
+            self.profiles.insert(previous.name.clone(), previous);
         }
 
 
@@ -106,76 +1114,650 @@ impl eframe::App for NetProfiler {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
                     if ui.button("Import").clicked() {
-                        self.import_export = true;
-                        self.file_dialog.select_file();
+                        self.run_command(Command::Import);
                     }
                     if ui.button("Export").clicked() {
-                        self.import_export = false;
-                        self.file_dialog.save_file();
+                        self.run_command(Command::Export);
+                    }
+                    if ui.button("Export Signed")
+                        .on_hover_text("Include a checksum, and an HMAC when a signing key is set, so tampering is detected on import")
+                        .clicked()
+                    {
+                        self.run_command(Command::ExportSigned);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Signing Key");
+                        ui.add(egui::TextEdit::singleline(&mut self.signing_key).password(true).desired_width(120.0));
+                    });
+                    ui.horizontal(|ui| {
+                        let label = ui.label("Import Masks As");
+                        let selected = match self.import_mask_format {
+                            None => "Keep",
+                            Some(MaskFormat::Cidr) => "CIDR",
+                            Some(MaskFormat::Dotted) => "Dotted",
+                        };
+                        egui::ComboBox::from_id_source("import_mask_format")
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.import_mask_format, None, "Keep");
+                                ui.selectable_value(&mut self.import_mask_format, Some(MaskFormat::Cidr), "CIDR");
+                                ui.selectable_value(&mut self.import_mask_format, Some(MaskFormat::Dotted), "Dotted");
+                            })
+                            .response
+                            .labelled_by(label.id);
+                    });
+                    if ui.button("Export Audit Log (CSV)").clicked() {
+                        self.run_command(Command::ExportAuditLog);
+                    }
+                    let mut watching = self.watcher.is_some();
+                    let hover = match &self.open_file {
+                        Some(path) => format!("Offer to reload {} when it changes on disk", path.display()),
+                        None => "Import or export a file to watch it".to_string(),
+                    };
+                    if ui.add_enabled(self.open_file.is_some(), egui::Checkbox::new(&mut watching, "Watch File"))
+                        .on_hover_text(hover)
+                        .changed()
+                    {
+                        self.watcher = None;
+                        if let Some(path) = self.open_file.as_ref().filter(|_| watching) {
+                            let ctx = ctx.clone();
+                            match file_operations::FileWatcher::new(path, move || ctx.request_repaint()) {
+                                Ok(watcher) => self.watcher = Some(watcher),
+                                Err(e) => log::error!("{}", e),
+                            }
+                        }
+                    }
+                    ui.separator();
+                    if ui.button("Settings").clicked() {
+                        self.run_command(Command::OpenSettings);
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Tools", |ui| {
+                    if ui.button("Run Diagnostics").clicked() {
+                        self.run_command(Command::RunDiagnostics);
+                        ui.close_menu();
+                    }
+                    if ui.button("Adapter Configuration").on_hover_text("Show an interface's current addresses, gateways, DNS, MAC and MTU").clicked() {
+                        self.run_command(Command::ShowAdapterConfig);
+                        ui.close_menu();
+                    }
+                    if ui.button("Find & Replace").on_hover_text("Change an address across all profiles").clicked() {
+                        self.run_command(Command::FindReplace);
+                        ui.close_menu();
+                    }
+                    if ui.button("Refresh Interfaces").clicked() {
+                        self.run_command(Command::RefreshInterfaces);
+                        ui.close_menu();
+                    }
+                    if ui.button("Command Palette").on_hover_text("Ctrl+P").clicked() {
+                        self.palette = Some(CommandPalette::default());
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let supported = cfg!(target_os = "linux");
+                    if ui.add_enabled(supported, egui::Button::new("Create Test Interface"))
+                        .on_hover_text(format!("Create a dummy {} interface to try profiles on", network::TEST_INTERFACE))
+                        .clicked()
+                    {
+                        if let Err(e) = network::create_dummy_interface(&mut network::SystemBackend, network::TEST_INTERFACE) {
+                            log::error!("Error creating test interface: {}", e);
+                        }
+                        self.refresh_adapters();
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(supported, egui::Button::new("Remove Test Interface")).clicked() {
+                        if let Err(e) = network::delete_dummy_interface(&mut network::SystemBackend, network::TEST_INTERFACE) {
+                            log::error!("Error removing test interface: {}", e);
+                        }
+                        self.refresh_adapters();
+                        ui.close_menu();
                     }
                 });
 
                 if ui.button("Add Profile").clicked() {
-                    self.builder = Some(network::NetworkProfile {
-                        name: "New Profile".to_string(),
-                        subnet: "255.255.255.0".to_string(),
-                        ..Default::default()
-                    });
+                    self.run_command(Command::AddProfile);
+                }
+
+                let dark = ui.visuals().dark_mode;
+                if ui.button(if dark { "☀" } else { "🌙" }).on_hover_text("Switch between light and dark themes").clicked() {
+                    self.settings.theme = if dark { Theme::Light } else { Theme::Dark };
+                }
+
+                if let Some(profile) = self.safe_profile.as_ref().and_then(|name| self.profiles.get(name)) {
+                    if ui.add_enabled(!self.is_applying(profile.target_adapter()), egui::Button::new(RichText::new("Panic: Apply Safe Profile").color(Color32::RED)))
+                        .on_hover_text(format!("Apply \"{}\" to {}", profile.name, profile.adapter))
+                        .clicked()
+                    {
+                        self.applies.push(BackgroundApply::start(profile.clone()));
+                    }
+                }
+                if !self.applies.is_empty() {
+                    ui.spinner().on_hover_text(format!("Applying {}", self.applies.iter().map(|apply| apply.name.as_str()).collect::<Vec<_>>().join(", ")));
+                }
+            });
+
+            // Quick apply bar
+            ui.horizontal(|ui| {
+                let label = ui.label("Quick Apply:");
+                let mut names: Vec<&String> = self.profiles.keys().collect();
+                names.sort();
+                egui::ComboBox::from_id_source("quick_profile")
+                    .selected_text(self.quick_profile.as_deref().unwrap_or("Profile"))
+                    .show_ui(ui, |ui| {
+                        for name in names {
+                            ui.selectable_value(&mut self.quick_profile, Some(name.clone()), name);
+                        }
+                    })
+                    .response
+                    .labelled_by(label.id);
+                egui::ComboBox::from_id_source("quick_adapter")
+                    .selected_text(if self.quick_adapter.is_empty() { "Adapter" } else { &self.quick_adapter })
+                    .show_ui(ui, |ui| {
+                        for adapter in self.adapters.iter() {
+                            ui.selectable_value(&mut self.quick_adapter, adapter.clone(), adapter);
+                        }
+                    })
+                    .response
+                    .labelled_by(label.id);
+
+                let profile = self.quick_profile.as_ref().and_then(|name| self.profiles.get(name));
+                let bound = profile.is_some_and(|profile| profile.mac_binding.is_some());
+                let busy = self.is_applying(&self.quick_adapter);
+                let ready = profile.is_some() && (bound || !self.quick_adapter.is_empty()) && !busy;
+                if ui.add_enabled(ready, egui::Button::new("Apply"))
+                    .on_hover_text("Profiles bound to a MAC apply to that NIC when it's present")
+                    .clicked()
+                {
+                    if let Some(profile) = profile {
+                        let adapter = profile.bound_adapter().unwrap_or_else(|| self.quick_adapter.clone());
+                        self.apply_profile(NetworkProfile {
+                            adapter,
+                            ..profile.clone()
+                        });
+                    }
+                }
+            });
+
+            // Bulk edits for ticked profiles
+            if !self.checked.is_empty() {
+                ui.horizontal(|ui| {
+                    let label = ui.label(format!("Set DNS for {} selected:", self.checked.len()));
+                    let selected_text = match &self.bulk_dns {
+                        DnsSource::Provider(provider) => format!("{:?}", provider),
+                        DnsSource::Profile(name) => format!("From {}", name),
+                    };
+                    let mut names: Vec<&String> = self.profiles.keys().collect();
+                    names.sort();
+                    egui::ComboBox::from_id_source("bulk_dns")
+                        .selected_text(selected_text)
+                        .show_ui(ui, |ui| {
+                            for provider in [network::DNSProvider::Quad9, network::DNSProvider::Google, network::DNSProvider::Cloudflare, network::DNSProvider::OpenDNS] {
+                                let text = format!("{:?}", provider);
+                                ui.selectable_value(&mut self.bulk_dns, DnsSource::Provider(provider), text);
+                            }
+                            ui.separator();
+                            for name in names {
+                                ui.selectable_value(&mut self.bulk_dns, DnsSource::Profile(name.clone()), format!("From {}", name));
+                            }
+                        })
+                        .response
+                        .labelled_by(label.id);
+
+                    if ui.button("Apply to Selected").clicked() {
+                        let source = match &self.bulk_dns {
+                            DnsSource::Provider(provider) => Some(NetworkProfile { dns_provider: provider.clone(), ..Default::default() }),
+                            DnsSource::Profile(name) => self.profiles.get(name).cloned(),
+                        };
+                        let targets = self.profiles.iter_mut()
+                            .filter(|(name, _)| self.checked.contains(*name))
+                            .map(|(_, profile)| profile);
+                        let result = match source {
+                            Some(source) => network::copy_dns(targets, &source),
+                            None => Err("The source profile no longer exists".to_string()),
+                        };
+                        let status = match result {
+                            Ok(changed) => format!("DNS updated on {} profiles", changed),
+                            Err(e) => {
+                                let status = format!("Error setting DNS: {}", e);
+                                log::error!("{}", status);
+                                status
+                            }
+                        };
+                        self.bulk_status = Some(status);
+                    }
+                    if ui.button("Clear Selection").clicked() {
+                        self.checked.clear();
+                        self.bulk_status = None;
+                    }
+                    if ui.button(RichText::new("Delete Selected").color(ui.visuals().error_fg_color)).clicked() {
+                        self.confirm_bulk_delete = true;
+                    }
+                });
+                if let Some(status) = &self.bulk_status {
+                    ui.label(RichText::new(status).small());
+                }
+            }
+        });
+
+        // Watch links only while a profile wants applying when its adapter comes up
+        let auto_apply = self.profiles.values().any(|profile| profile.auto_apply);
+        match (auto_apply, &self.link_watcher) {
+            (true, None) => {
+                let ctx = ctx.clone();
+                self.link_watcher = Some(LinkWatcher::new(move || ctx.request_repaint()));
+            }
+            (false, Some(_)) => self.link_watcher = None,
+            _ => {}
+        }
+        for adapter in self.link_watcher.as_ref().map(LinkWatcher::links_up).unwrap_or_default() {
+            self.auto_apply(&adapter);
+        }
+
+        let (finished, running): (Vec<_>, Vec<_>) = std::mem::take(&mut self.applies).into_iter()
+            .partition(|apply| apply.handle.is_finished());
+        self.applies = running;
+        for apply in finished {
+            let (status, kind) = match apply.handle.join() {
+                Ok(ApplyOutcome::Applied) => ("applied".to_string(), NotificationKind::Success),
+                Ok(ApplyOutcome::Cancelled) => ("cancelled".to_string(), NotificationKind::Success),
+                Ok(ApplyOutcome::Failed(e) | ApplyOutcome::RolledBack(e)) => (format!("failed: {}", e), NotificationKind::Failure),
+                Err(_) => ("failed: worker thread panicked".to_string(), NotificationKind::Failure),
+            };
+            let status = format!("{} on {} {}", apply.name, apply.adapter, status);
+            match kind {
+                NotificationKind::Success => log::info!("{}", status),
+                NotificationKind::Failure => log::error!("{}", status),
+            }
+            if self.settings.os_notifications {
+                notification::notify(&apply.name, &status, kind);
+            }
+            self.apply_status = Some(status);
+        }
+        if !self.applies.is_empty() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        // Poll the primary interface off the UI thread, since reading DNS runs external tools
+        if self.status_poll.as_ref().is_some_and(|poll| poll.is_finished()) {
+            self.primary_status = self.status_poll.take().and_then(|poll| poll.join().ok()).flatten();
+        }
+        if self.status_poll.is_none() && self.status_polled_at.is_none_or(|at| at.elapsed() >= STATUS_POLL_INTERVAL) {
+            self.status_poll = Some(std::thread::spawn(network::primary_interface_summary));
+            self.status_polled_at = Some(Instant::now());
+        }
+        ctx.request_repaint_after(STATUS_POLL_INTERVAL);
+
+        egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
+            if let Some(error) = &self.adapter_error {
+                ui.label(RichText::new(format!("⚠ {}", error)).color(ui.visuals().warn_fg_color));
+            }
+            if !self.elevated {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("⚠ Not running as administrator, profiles can't be applied").color(ui.visuals().warn_fg_color));
+                });
+                ui.horizontal(|ui| {
+                    if cfg!(not(target_os = "windows")) {
+                        egui::ComboBox::from_id_source("elevation_method")
+                            .selected_text(self.elevation_method.program())
+                            .show_ui(ui, |ui| {
+                                for method in ElevationMethod::ALL {
+                                    ui.selectable_value(&mut self.elevation_method, method, method.program());
+                                }
+                            });
+                    }
+                    if ui.button("Relaunch Elevated").clicked() {
+                        match elevation::check_and_relaunch_elevated(self.elevation_method) {
+                            ElevationStatus::AlreadyElevated => self.elevated = true,
+                            ElevationStatus::Relaunched => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+                            ElevationStatus::Failed(e) => self.elevation_error = Some(e),
+                        }
+                    }
+                });
+                if let Some(e) = &self.elevation_error {
+                    ui.label(RichText::new(format!("❌ {}", e)).color(Color32::RED));
                 }
+            }
+            if let Some(status) = &self.apply_status {
+                ui.label(status);
+            }
+            ui.horizontal(|ui| {
+                ui.label(format!("Net Profiler v{} by Paul Cameron", env!("CARGO_PKG_VERSION")));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let status = self.primary_status.as_deref().unwrap_or("No default route");
+                    ui.label(RichText::new(status).small().monospace())
+                        .on_hover_text("Interface with the default route");
+                });
             });
         });
 
-        egui::CentralPanel::default().show(ctx, move |ui| {
+        self.sync_profile_order();
+        let mut visible: Vec<String> = self.profile_order.iter()
+            .filter(|name| self.profiles.get(*name).is_some_and(|profile| profile.matches(&self.profile_filter)))
+            .cloned()
+            .collect();
+        if self.group_by_tag {
+            // Stable, so each group keeps the user's order; untagged profiles go last
+            visible.sort_by_key(|name| {
+                let profile = self.profiles.get(name);
+                (profile.is_none_or(|profile| profile.tags.is_empty()), profile.map(tag_group))
+            });
+        }
+        if self.selected.is_some_and(|i| i >= visible.len()) {
+            self.selected = None;
+        }
+
+        // Keyboard navigation of the profile list, unless a widget has focus
+        let mut scroll_to_selected = false;
+        if ctx.memory(|mem| mem.focused().is_none()) && !visible.is_empty() {
+            let last = visible.len() - 1;
+            let (up, down, enter) = ctx.input(|i| (
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+                i.key_pressed(egui::Key::Enter),
+            ));
+            if down {
+                self.selected = Some(self.selected.map_or(0, |i| (i + 1).min(last)));
+            }
+            if up {
+                self.selected = Some(self.selected.map_or(0, |i| i.saturating_sub(1)));
+            }
+            scroll_to_selected = up || down;
+            if let Some(profile) = self.selected.filter(|_| enter).and_then(|i| visible.get(i)).and_then(|name| self.profiles.get(name)) {
+                self.loader = Some(ProfileLoader::new(profile.clone(), self.settings.confirm_apply));
+            }
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("🔍");
+                ui.add(egui::TextEdit::singleline(&mut self.profile_filter).hint_text("Filter by name or address"));
+                if !self.profile_filter.is_empty() {
+                    if ui.button("✖").on_hover_text("Clear filter").clicked() {
+                        self.profile_filter.clear();
+                    }
+                    ui.label(format!("{} of {}", visible.len(), self.profiles.len()));
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.checkbox(&mut self.group_by_tag, "Group by tag")
+                        .on_hover_text("List profiles under their first tag. Filter with tag:<name> for one tag");
+                    if ui.add_enabled(!self.checked.is_empty(), egui::Button::new("Select None")).clicked() {
+                        self.checked.clear();
+                    }
+                    if ui.button("Select All").on_hover_text("Select every profile the filter shows").clicked() {
+                        self.checked.extend(visible.iter().cloned());
+                    }
+                });
+            });
+            ui.separator();
+
             egui::ScrollArea::vertical().show(ui, |ui| {
                 let mut profiles_to_remove: Vec<NetworkProfile> = Vec::new();
+                // Index of a profile to swap with the one below it in the filtered list
+                let mut swap_down = None;
+                // Profile to open a copy of in the builder, and whether to increment its gateway for Clone +1
+                let mut to_clone: Option<(NetworkProfile, Option<bool>)> = None;
+                // Profile whose name was double-clicked, and whether an in-place rename was committed or dropped
+                let mut start_rename = None;
+                let mut rename_committed = None;
+                let mut to_apply = None;
+
+                let mut group_sizes: BTreeMap<String, usize> = BTreeMap::new();
+                if self.group_by_tag {
+                    for profile in visible.iter().filter_map(|name| self.profiles.get(name)) {
+                        *group_sizes.entry(tag_group(profile)).or_default() += 1;
+                    }
+                }
+                let mut current_group = None;
 
-                for (name, profile) in self.profiles.iter_mut() {
-                    // Background Frame for padding and stylization
-                    egui::Frame::dark_canvas(ui.style()).show(ui, |ui| {
+                let last = visible.len().saturating_sub(1);
+                for (index, name) in visible.iter().enumerate() {
+                    let Some(profile) = self.profiles.get_mut(name) else {
+                        continue;
+                    };
+                    if self.group_by_tag {
+                        // The list is sorted by group, so a new group starts where the tag changes
+                        let group = tag_group(profile);
+                        let collapsed = self.collapsed_groups.contains(&group);
+                        if current_group.as_ref() != Some(&group) {
+                            let arrow = if collapsed { "⏵" } else { "⏷" };
+                            let size = group_sizes.get(&group).copied().unwrap_or_default();
+                            // Headed with the tag as the group's first profile spells it
+                            let heading = profile.tags.first().unwrap_or(&group);
+                            if ui.selectable_label(false, RichText::new(format!("{} {} ({})", arrow, heading, size)).strong()).clicked() {
+                                match collapsed {
+                                    true => self.collapsed_groups.remove(&group),
+                                    false => self.collapsed_groups.insert(group.clone()),
+                                };
+                            }
+                            current_group = Some(group);
+                        }
+                        if collapsed {
+                            continue;
+                        }
+                    }
+                    // Background Frame for padding and stylization, outlined when selected
+                    let mut frame = egui::Frame::dark_canvas(ui.style());
+                    if self.selected == Some(index) {
+                        frame = frame.stroke(ui.visuals().selection.stroke);
+                    }
+                    let response = frame.show(ui, |ui| {
                         // Profile input fields
-                        let open = egui::CollapsingHeader::new(RichText::new(name).color(Color32::WHITE))
-                            .default_open(false)
-                            .show(ui, |ui| {
-                                egui::Frame::default()
-                                    .inner_margin(egui::Margin::same(10.0))
+                        let is_safe = self.safe_profile.as_ref() == Some(name);
+                        let title = match is_safe {
+                            true => format!("{} (Safe)", name),
+                            false => name.clone(),
+                        };
+                        let summary = profile.summary();
+                        let mut open = false;
+                        match self.rename.as_mut().filter(|rename| rename.original == *name) {
+                            Some(rename) => {
+                                let edit = ui.add(egui::TextEdit::singleline(&mut rename.name).hint_text("Profile name"));
+                                if std::mem::take(&mut rename.focus) {
+                                    edit.request_focus();
+                                }
+                                // Enter commits, while Escape or clicking away leaves the name as it was
+                                if edit.lost_focus() {
+                                    rename_committed = Some(ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                                }
+                                if let Some(error) = &rename.error {
+                                    ui.label(RichText::new(format!("❌ {}", error)).color(Color32::RED));
+                                }
+                            }
+                            None => {
+                                let header = egui::CollapsingHeader::new(RichText::new(title).strong())
+                                    .id_source(name)
+                                    .default_open(false)
                                     .show(ui, |ui| {
-                                        display_profile(profile, ui, &self.adapters);
+                                        egui::Frame::default()
+                                            .inner_margin(egui::Margin::same(10.0))
+                                            .show(ui, |ui| {
+                                                display_profile(profile, ui, &self.adapters);
+                                            });
                                     });
-                            })
-                            .fully_open();
+                                open = header.fully_open();
+                                let hover = match profile.description.trim() {
+                                    "" => format!("{}\n\nDouble-click to rename", summary),
+                                    description => format!("{}\n\n{}\n\nDouble-click to rename", description, summary),
+                                };
+                                if header.header_response.on_hover_text(hover).double_clicked() {
+                                    start_rename = Some(name.clone());
+                                }
+                            }
+                        }
+                        if !open {
+                            ui.label(RichText::new(summary).small().weak());
+                        }
 
                         // Profile actions
                         egui::Frame::default()
                             .inner_margin(egui::Margin::same(4.0))
                             .show(ui, |ui| {
                                 ui.horizontal(|ui| {
-                                    if ui.button(RichText::new("Load Profile").color(Color32::WHITE)).clicked() {
-                                        profile.load();
+                                    let mut checked = self.checked.contains(name);
+                                    if ui.checkbox(&mut checked, "").on_hover_text("Select for bulk edits").changed() {
+                                        match checked {
+                                            true => self.checked.insert(name.clone()),
+                                            false => self.checked.remove(name),
+                                        };
+                                    }
+                                    if ui.add_enabled(index > 0, egui::Button::new("⏶")).on_hover_text("Move up").clicked() {
+                                        swap_down = Some(index - 1);
+                                    }
+                                    if ui.add_enabled(index < last, egui::Button::new("⏷")).on_hover_text("Move down").clicked() {
+                                        swap_down = Some(index);
+                                    }
+                                    let busy = network::is_applying(profile.target_adapter())
+                                        || self.applies.iter().any(|apply| apply.adapter == profile.target_adapter());
+                                    if ui.add_enabled(!busy, egui::Button::new(RichText::new("Load Profile").strong())).clicked() {
+                                        to_apply = Some(profile.clone());
                                     }
-                                    if ui.button(RichText::new("Remove Profile").color(Color32::WHITE)).double_clicked() {
+                                    if ui.button(RichText::new("Preview").strong()).clicked() {
+                                        self.loader = Some(ProfileLoader::new(profile.clone(), self.settings.confirm_apply));
+                                    }
+                                    if ui.button(RichText::new("Edit as JSON").strong()).clicked() {
+                                        match serde_json::to_string_pretty(profile) {
+                                            Ok(text) => self.json_editor = Some(JsonEditor { original: name.clone(), text, error: None }),
+                                            Err(e) => log::error!("Error serializing profile {}: {}", name, e),
+                                        }
+                                    }
+                                    if ui.button(RichText::new("Export").strong()).on_hover_text("Save just this profile to a file").clicked() {
+                                        // Names can hold characters file systems don't allow
+                                        let file_name: String = name.chars()
+                                            .map(|c| if c.is_alphanumeric() || " -_.()".contains(c) { c } else { '_' })
+                                            .collect();
+                                        self.file_action = FileAction::ExportProfile(name.clone());
+                                        self.file_dialog.config_mut().default_file_name = format!("{}.nprf", file_name.trim());
+                                        self.file_dialog.save_file();
+                                    }
+                                    if ui.button(RichText::new("Clone").strong()).on_hover_text("Open a copy of this profile in the builder").clicked() {
+                                        to_clone = Some((profile.clone(), None));
+                                    }
+                                    ui.menu_button(RichText::new("Clone +1").strong(), |ui| {
+                                        let mut increment_gateway = None;
+                                        if ui.button("IP only").clicked() {
+                                            increment_gateway = Some(false);
+                                        }
+                                        if ui.button("IP and gateway").clicked() {
+                                            increment_gateway = Some(true);
+                                        }
+                                        if increment_gateway.is_some() {
+                                            to_clone = Some((profile.clone(), increment_gateway));
+                                            ui.close_menu();
+                                        }
+                                    });
+                                    if ui.button(RichText::new("Remove Profile").strong()).double_clicked() {
                                         profiles_to_remove.push(profile.clone());
                                     }
+                                    let safe_label = if is_safe { "Unset Safe" } else { "Set as Safe" };
+                                    if ui.button(RichText::new(safe_label).strong())
+                                        .on_hover_text("The safe profile can be applied from the top bar to restore connectivity")
+                                        .clicked()
+                                    {
+                                        self.safe_profile = (!is_safe).then(|| name.clone());
+                                    }
                                 });
                             });
                     });
 
+                    if scroll_to_selected && self.selected == Some(index) {
+                        response.response.scroll_to_me(None);
+                    }
+
                     ui.separator();
                 }
 
+                if let Some(index) = swap_down {
+                    // Swap where the two profiles sit in the full order, past any hidden by the filter
+                    let position = |name: &String| self.profile_order.iter().position(|other| other == name);
+                    if let (Some(a), Some(b)) = (position(&visible[index]), position(&visible[index + 1])) {
+                        self.profile_order.swap(a, b);
+                    }
+                    // Follow the moved profile with the keyboard selection
+                    self.selected = match self.selected {
+                        Some(i) if i == index => Some(index + 1),
+                        Some(i) if i == index + 1 => Some(index),
+                        selected => selected,
+                    };
+                }
+                if let Some(profile) = to_apply {
+                    self.apply_profile(profile);
+                }
+                if let Some(original) = start_rename {
+                    self.rename = Some(ProfileRename { name: original.clone(), original, error: None, focus: true });
+                }
+                match rename_committed {
+                    Some(true) => self.finish_rename(),
+                    Some(false) => self.rename = None,
+                    None => (),
+                }
+                if let Some((profile, increment_gateway)) = to_clone {
+                    let (clone, warning) = match increment_gateway {
+                        Some(increment_gateway) => clone_incremented(&self.profiles, &profile, increment_gateway),
+                        None => (NetworkProfile { name: network::copy_profile_name(&self.profiles, &profile.name), ..profile }, None),
+                    };
+                    self.builder = Some(clone);
+                    self.builder_warning = warning;
+                }
                 for profile in profiles_to_remove {
-                    self.profiles.remove(&profile.name);
+                    self.remove_profile(&profile.name);
                 }
             });
         });
-
-        egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
-            ui.label(format!("Net Profiler v{} by Paul Cameron", env!("CARGO_PKG_VERSION")));
-        });
     }
 }
 
-fn display_profile(profile: &mut network::NetworkProfile, ui: &mut egui::Ui, adapters: &Vec<String>) {
-    egui::ComboBox::from_label(RichText::new("Adapter").color(Color32::WHITE))
+/// Group a profile is listed under when grouping by tag: its first tag, ignoring case, or "Uncategorized".
+fn tag_group(profile: &NetworkProfile) -> String {
+    profile.tags.first().map(|tag| tag.to_lowercase()).unwrap_or_else(|| "Uncategorized".to_string())
+}
+
+fn display_profile(profile: &mut network::NetworkProfile, ui: &mut egui::Ui, adapters: &[String]) {
+    let label = ui.label(RichText::new("Description").strong());
+    ui.add(egui::TextEdit::multiline(&mut profile.description).desired_rows(2).hint_text("e.g. DR site, VLAN 40, contact NOC"))
+        .labelled_by(label.id);
+
+    ui.horizontal_wrapped(|ui| {
+        let label = ui.label(RichText::new("Tags: ").strong());
+        let mut removed = None;
+        for (i, tag) in profile.tags.iter().enumerate() {
+            if ui.small_button(format!("{} ✖", tag)).on_hover_text("Remove tag").clicked() {
+                removed = Some(i);
+            }
+        }
+        if let Some(i) = removed {
+            profile.tags.remove(i);
+        }
+        // Text being typed for a new tag, kept by egui between frames
+        let id = ui.id().with("new_tag");
+        let mut new_tag = ui.data_mut(|data| data.get_temp::<String>(id)).unwrap_or_default();
+        let response = ui.add(egui::TextEdit::singleline(&mut new_tag).hint_text("Add tag").desired_width(100.0))
+            .labelled_by(label.id)
+            .on_hover_text("Press Enter to add, separate several with commas");
+        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            profile.add_tags(&new_tag);
+            new_tag.clear();
+        }
+        ui.data_mut(|data| data.insert_temp(id, new_tag));
+    });
+
+    ui.horizontal(|ui| {
+        let label = ui.label(RichText::new("Hostname: ").strong());
+        let mut hostname = profile.hostname.clone().unwrap_or_default();
+        if ui.text_edit_singleline(&mut hostname)
+            .labelled_by(label.id)
+            .on_hover_text("Rename the computer when applying, blank to keep its name. Windows needs a restart for it.")
+            .changed()
+        {
+            profile.hostname = (!hostname.is_empty()).then_some(hostname);
+        }
+    });
+    if let Some(Err(e)) = profile.hostname.as_deref().map(network::check_hostname) {
+        show_validation(ui, false, &e.to_string());
+    }
+
+    egui::ComboBox::from_label(RichText::new("Adapter").strong())
         .selected_text(&profile.adapter)
         .show_ui(ui, |ui| {
             for adapter in adapters.iter() {
@@ -184,59 +1766,394 @@ fn display_profile(profile: &mut network::NetworkProfile, ui: &mut egui::Ui, ada
                 }
             }
         });
+    let auto_apply = match profile.adapter.is_empty() {
+        true => "Auto apply when the adapter comes up".to_string(),
+        false => format!("Auto apply on {}", profile.adapter),
+    };
+    ui.checkbox(&mut profile.auto_apply, RichText::new(auto_apply).strong())
+        .on_hover_text("Apply this profile whenever the adapter is plugged in or its link comes up, while Net Profiler is running");
+
+    ui.horizontal(|ui| {
+        let label = ui.label(RichText::new("Bound MAC: ").strong());
+        let mut mac = profile.mac_binding.clone().unwrap_or_default();
+        if ui.text_edit_singleline(&mut mac).labelled_by(label.id).changed() {
+            profile.mac_binding = (!mac.is_empty()).then_some(mac);
+        }
+        if ui.add_enabled(!profile.adapter.is_empty(), egui::Button::new("Bind"))
+            .on_hover_text("Bind to the selected adapter's MAC so the profile follows the NIC if it's renamed")
+            .clicked()
+        {
+            match network::adapter_mac(&profile.adapter) {
+                Some(mac) => profile.mac_binding = Some(mac),
+                None => log::warn!("Adapter {} has no MAC address", profile.adapter),
+            }
+        }
+    });
+    show_validation(ui, profile.mac_binding.as_deref().is_none_or(network::is_mac_address), "Invalid MAC address");
+
+    ui.horizontal(|ui| {
+        let label = ui.label(RichText::new("Set MAC: ").strong());
+        let mut mac = profile.mac_address.clone().unwrap_or_default();
+        if ui.text_edit_singleline(&mut mac).labelled_by(label.id)
+            .on_hover_text("Give the adapter this hardware address when applying, blank to keep its own")
+            .changed()
+        {
+            profile.mac_address = (!mac.is_empty()).then_some(mac);
+        }
+    });
+    if let Some(Err(e)) = profile.mac_address.as_deref().map(network::normalize_mac) {
+        show_validation(ui, false, &e);
+    }
+
+    ui.horizontal(|ui| {
+        for (family, policy) in [("IPv4", &mut profile.ipv4_policy), ("IPv6", &mut profile.ipv6_policy)] {
+            let label = ui.label(RichText::new(format!("{}: ", family)).strong());
+            egui::ComboBox::from_id_source(ui.id().with(family))
+                .selected_text(policy.label())
+                .show_ui(ui, |ui| {
+                    for option in network::FamilyPolicy::ALL {
+                        ui.selectable_value(policy, option, option.label());
+                    }
+                })
+                .response
+                .labelled_by(label.id)
+                .on_hover_text("Configure applies the profile's static settings; profiles have none for IPv6 yet");
+        }
+    });
     
     ui.horizontal(|ui| {
-        let label = ui.label(RichText::new("IP: ").color(Color32::WHITE));
+        let label = ui.label(RichText::new("IP: ").strong());
         ui.text_edit_singleline(&mut profile.ip).labelled_by(label.id);
     });
+    show_validation(ui, profile.ip.is_empty() || profile.ip.parse::<Ipv4Addr>().is_ok(), "Invalid IP address");
 
     ui.separator();
 
     ui.horizontal(|ui| {
-        let label = ui.label(RichText::new("Subnet: ").color(Color32::WHITE));
+        let label = ui.label(RichText::new("Subnet: ").strong());
         ui.text_edit_singleline(&mut profile.subnet).labelled_by(label.id);
+        // Both forms of each mask, so the list also works as a reference while typing
+        let both = |prefix: u8| format!("/{}  {}", prefix, network::normalize_subnet(&prefix.to_string(), MaskFormat::Dotted).unwrap_or_default());
+        let prefix = network::subnet_prefix(&profile.subnet);
+        // Picking a mask keeps the form the field is already in, dotted when it's empty
+        let format = match profile.subnet.contains('.') || profile.subnet.is_empty() {
+            true => MaskFormat::Dotted,
+            false => MaskFormat::Cidr,
+        };
+        egui::ComboBox::from_id_source(ui.id().with("subnet_prefix"))
+            .selected_text(prefix.map(both).unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for option in (0..=32).rev() {
+                    if ui.selectable_label(prefix == Some(option), both(option)).clicked() {
+                        profile.subnet = network::normalize_subnet(&option.to_string(), format).unwrap_or_default();
+                    }
+                }
+            })
+            .response
+            .labelled_by(label.id)
+            .on_hover_text("Pick a mask by prefix length");
     });
+    show_validation(ui, profile.subnet.is_empty() || network::subnet_prefix(&profile.subnet).is_some(), "Invalid subnet mask");
 
     ui.separator();
 
     ui.horizontal(|ui| {
-        let label = ui.label(RichText::new("Gateway: ").color(Color32::WHITE));
+        let label = ui.label(RichText::new("Gateway: ").strong());
         ui.text_edit_singleline(&mut profile.gateway).labelled_by(label.id);
     });
+    show_validation(ui, profile.gateway.is_empty() || profile.gateway.parse::<Ipv4Addr>().is_ok(), "Invalid gateway address");
+
+    ui.horizontal(|ui| {
+        let mut enabled = profile.route_metric.is_some();
+        ui.checkbox(&mut enabled, RichText::new("Route Metric: ").strong())
+            .on_hover_text("Lower metrics are preferred when several interfaces are up");
+        match (enabled, profile.route_metric.as_mut()) {
+            (true, Some(metric)) => {
+                ui.add(egui::DragValue::new(metric).range(0..=network::MAX_ROUTE_METRIC));
+            }
+            (true, None) => profile.route_metric = Some(100),
+            (false, _) => profile.route_metric = None,
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let mut enabled = profile.mtu.is_some();
+        ui.checkbox(&mut enabled, RichText::new("MTU: ").strong())
+            .on_hover_text("Leave unticked to keep the interface's current MTU");
+        match (enabled, profile.mtu.as_mut()) {
+            (true, Some(mtu)) => {
+                ui.add(egui::DragValue::new(mtu).range(network::MTU_RANGE));
+            }
+            (true, None) => profile.mtu = Some(1500),
+            (false, _) => profile.mtu = None,
+        }
+    });
+
+    ui.label(RichText::new("Routes: ").strong());
+    let mut remove = None;
+    for (i, route) in profile.routes.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut route.destination).hint_text("Destination").desired_width(110.0));
+            ui.label("/");
+            ui.add(egui::DragValue::new(&mut route.prefix).range(0..=32));
+            ui.label("via");
+            ui.add(egui::TextEdit::singleline(&mut route.via).hint_text("Gateway").desired_width(110.0));
+            let mut has_metric = route.metric.is_some();
+            if ui.checkbox(&mut has_metric, "Metric").changed() {
+                route.metric = has_metric.then_some(100);
+            }
+            if let Some(metric) = route.metric.as_mut() {
+                ui.add(egui::DragValue::new(metric).range(0..=network::MAX_ROUTE_METRIC));
+            }
+            if ui.small_button("✖").on_hover_text("Remove this route").clicked() {
+                remove = Some(i);
+            }
+        });
+        show_validation(ui, route.destination.parse::<Ipv4Addr>().is_ok(), "Invalid destination address");
+        show_validation(ui, route.via.parse::<Ipv4Addr>().is_ok(), "Invalid gateway address");
+    }
+    if let Some(i) = remove {
+        profile.routes.remove(i);
+    }
+    if ui.small_button("➕ Add Route").clicked() {
+        profile.routes.push(network::StaticRoute { prefix: 24, ..Default::default() });
+    }
 
     ui.separator();
 
     egui::Frame::default()
-        .fill(Color32::from_rgb(30, 30, 30))
+        .fill(ui.visuals().window_fill)
         .inner_margin(egui::Margin::same(2.0))
         .rounding(5.0)
         .show(ui, |ui| {
-            let label = ui.label(RichText::new("DNS Provider: ").color(Color32::WHITE));
+            let label = ui.label(RichText::new("DNS Provider: ").strong());
             ui.horizontal(|ui| {
-                ui.radio_value(&mut profile.dns_provider, network::DNSProvider::None, "None");
+                ui.radio_value(&mut profile.dns_provider, network::DNSProvider::None, "None").labelled_by(label.id);
+                // Addresses as currently configured under File > Settings
+                let addresses = |provider: &network::DNSProvider| network::provider_dns(provider).unwrap_or_default().join("\n");
                 ui.radio_value(&mut profile.dns_provider, network::DNSProvider::Quad9, "Quad9").on_hover_ui(|ui| {
                     ui.style_mut().interaction.selectable_labels = true;
-                    ui.label(RichText::new("9.9.9.9\n149.112.112.112\n(Recommended)").color(Color32::WHITE));
+                    ui.label(RichText::new(format!("{}\n(Recommended)", addresses(&network::DNSProvider::Quad9))).strong());
                 }).labelled_by(label.id);
                 ui.radio_value(&mut profile.dns_provider, network::DNSProvider::Google, "Google").on_hover_ui(|ui| {
                     ui.style_mut().interaction.selectable_labels = true;
-                    ui.label(RichText::new("8.8.8.8\n8.8.4.4").color(Color32::WHITE));
+                    ui.label(RichText::new(addresses(&network::DNSProvider::Google)).strong());
                 }).labelled_by(label.id);
                 ui.radio_value(&mut profile.dns_provider, network::DNSProvider::Cloudflare, "Cloudflare").on_hover_ui(|ui| {
                     ui.style_mut().interaction.selectable_labels = true;
-                    ui.label(RichText::new("1.1.1.2\n1.0.0.2").color(Color32::WHITE));
+                    ui.label(RichText::new(addresses(&network::DNSProvider::Cloudflare)).strong());
                 }).labelled_by(label.id);
                 ui.radio_value(&mut profile.dns_provider, network::DNSProvider::OpenDNS, "OpenDNS").on_hover_ui(|ui| {
                     ui.style_mut().interaction.selectable_labels = true;
-                    ui.label(RichText::new("208.67.222.222\n208.67.220.220").color(Color32::WHITE));
+                    ui.label(RichText::new(addresses(&network::DNSProvider::OpenDNS)).strong());
                 }).labelled_by(label.id);
-                ui.radio_value(&mut profile.dns_provider, network::DNSProvider::Custom, "Custom");
+                ui.radio_value(&mut profile.dns_provider, network::DNSProvider::Custom, "Custom").labelled_by(label.id);
+                ui.radio_value(&mut profile.dns_provider, network::DNSProvider::Raw, "Raw")
+                    .on_hover_text("resolv.conf content: nameserver and search lines are applied")
+                    .labelled_by(label.id);
             });
+            if profile.dns_provider != network::DNSProvider::None {
+                ui.horizontal(|ui| {
+                    let label = ui.label(RichText::new("Encryption: ").strong());
+                    egui::ComboBox::from_id_source(ui.id().with("dns_encryption"))
+                        .selected_text(profile.dns_encryption.label())
+                        .show_ui(ui, |ui| {
+                            for option in network::DnsEncryption::ALL {
+                                ui.selectable_value(&mut profile.dns_encryption, option, option.label());
+                            }
+                        })
+                        .response
+                        .labelled_by(label.id)
+                        .on_hover_text("DNS over HTTPS on Windows 11 with the built-in providers, DNS over TLS on Linux with NetworkManager or systemd-resolved");
+                });
+                if let Err(e) = network::check_dns_encryption(profile.dns_encryption, &profile.dns_provider) {
+                    show_validation(ui, false, &e.to_string());
+                }
+            }
             if profile.dns_provider == network::DNSProvider::Custom {
-                let label = ui.label(RichText::new("Primary DNS: ").color(Color32::WHITE));
+                let label = ui.label(RichText::new("Primary DNS: ").strong());
                 ui.text_edit_singleline(&mut profile.primary_dns).labelled_by(label.id);
-                let label = ui.label(RichText::new("Secondary DNS: ").color(Color32::WHITE));
+                show_validation(ui, profile.primary_dns.is_empty() || profile.primary_dns.parse::<Ipv4Addr>().is_ok(), "Invalid DNS address");
+                let label = ui.label(RichText::new("Secondary DNS: ").strong());
                 ui.text_edit_singleline(&mut profile.secondary_dns).labelled_by(label.id);
+                show_validation(ui, profile.secondary_dns.is_empty() || profile.secondary_dns.parse::<Ipv4Addr>().is_ok(), "Invalid DNS address");
+                let mut remove = None;
+                for (i, server) in profile.extra_dns.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        let label = ui.label(RichText::new(format!("DNS {}: ", i + 3)).strong());
+                        ui.text_edit_singleline(server).labelled_by(label.id);
+                        if ui.small_button("✖").on_hover_text("Remove this server").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                    show_validation(ui, server.is_empty() || server.parse::<Ipv4Addr>().is_ok(), "Invalid DNS address");
+                }
+                if let Some(i) = remove {
+                    profile.extra_dns.remove(i);
+                }
+                if ui.small_button("➕ Add DNS server").clicked() {
+                    profile.extra_dns.push(String::new());
+                }
+            }
+            if profile.dns_provider == network::DNSProvider::Raw {
+                let label = ui.label(RichText::new("resolv.conf: ").strong());
+                ui.add(egui::TextEdit::multiline(&mut profile.raw_dns).code_editor().desired_rows(4)).labelled_by(label.id);
+                show_validation(ui, !network::resolv_conf_entries(&profile.raw_dns, "nameserver").is_empty(), "At least one nameserver line is required");
+            }
+        });
+
+    ui.separator();
+
+    egui::CollapsingHeader::new(RichText::new("Metadata").strong())
+        .id_source(ui.id().with("metadata"))
+        .show(ui, |ui| show_metadata(ui, &mut profile.metadata));
+
+    ui.separator();
+
+    let mut proxied = profile.proxy.is_some();
+    if ui.checkbox(&mut proxied, RichText::new("Proxy").strong())
+        .on_hover_text("Without a proxy, applying clears any proxy set before")
+        .changed()
+    {
+        profile.proxy = proxied.then(|| network::ProxyConfig { port: 8080, ..Default::default() });
+    }
+    if let Some(proxy) = profile.proxy.as_mut() {
+        ui.horizontal(|ui| {
+            let label = ui.label(RichText::new("Host: ").strong());
+            ui.text_edit_singleline(&mut proxy.host).labelled_by(label.id);
+            let label = ui.label(RichText::new("Port: ").strong());
+            ui.add(egui::DragValue::new(&mut proxy.port)).labelled_by(label.id);
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label(RichText::new("Bypass: ").strong());
+            let mut bypass = proxy.bypass.join(", ");
+            if ui.text_edit_singleline(&mut bypass)
+                .labelled_by(label.id)
+                .on_hover_text("Comma separated hosts to reach directly, e.g. localhost, .corp.example")
+                .changed()
+            {
+                // Blank entries are kept while typing and skipped when applying
+                proxy.bypass = bypass.split(',').map(|host| host.trim().to_string()).collect();
+            }
+        });
+        if cfg!(target_os = "windows") {
+            ui.horizontal(|ui| {
+                let label = ui.label(RichText::new("PAC URL: ").strong());
+                ui.text_edit_singleline(&mut proxy.pac_url).labelled_by(label.id);
+            });
+        }
+        if let Err(e) = proxy.validate() {
+            show_validation(ui, false, &e.to_string());
+        }
+    }
+
+    ui.separator();
+
+    let mut bridged = profile.bridge.is_some();
+    if ui.checkbox(&mut bridged, RichText::new("Bridge").strong()).changed() {
+        profile.bridge = bridged.then(|| network::BridgeConfig {
+            name: "br0".to_string(),
+            members: vec![profile.adapter.clone()].into_iter().filter(|a| !a.is_empty()).collect(),
+        });
+    }
+    if let Some(bridge) = profile.bridge.as_mut() {
+        ui.horizontal(|ui| {
+            let label = ui.label(RichText::new("Bridge Name: ").strong());
+            ui.text_edit_singleline(&mut bridge.name).labelled_by(label.id);
+        });
+        let label = ui.label(RichText::new("Members: ").strong());
+        for adapter in adapters.iter() {
+            let mut member = bridge.members.contains(adapter);
+            if ui.checkbox(&mut member, adapter).labelled_by(label.id).changed() {
+                match member {
+                    true => bridge.members.push(adapter.clone()),
+                    false => bridge.members.retain(|m| m != adapter),
+                }
+            }
+        }
+    }
+
+    if cfg!(target_os = "linux") {
+        ui.horizontal(|ui| {
+            let label = ui.label(RichText::new("Namespace: ").strong());
+            let mut netns = profile.netns.clone().unwrap_or_default();
+            if ui.text_edit_singleline(&mut netns)
+                .labelled_by(label.id)
+                .on_hover_text("Apply inside this network namespace instead of the host's")
+                .changed()
+            {
+                profile.netns = (!netns.is_empty()).then_some(netns);
             }
         });
-}
\ No newline at end of file
+        let exists = profile.netns.as_deref().is_none_or(|netns| network::SystemBackend.netns_exists(netns));
+        show_validation(ui, exists, "Network namespace doesn't exist");
+
+        ui.checkbox(&mut profile.persist, RichText::new("Keep after reboot").strong())
+            .on_hover_text("Also save the configuration: in the adapter's NetworkManager connection, or a systemd-networkd .network file where NetworkManager isn't running");
+    }
+}
+
+/// Key/value editor for a profile's informational metadata.
+fn show_metadata(ui: &mut egui::Ui, metadata: &mut BTreeMap<String, String>) {
+    let mut removed = None;
+    egui::Grid::new(ui.id().with("metadata_grid")).show(ui, |ui| {
+        for (key, value) in metadata.iter_mut() {
+            let label = ui.label(RichText::new(key).strong());
+            ui.text_edit_singleline(value).labelled_by(label.id);
+            if ui.button("🗑").on_hover_text(format!("Remove {}", key)).clicked() {
+                removed = Some(key.clone());
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(key) = removed {
+        metadata.remove(&key);
+    }
+
+    // The new entry's key is kept in temporary UI memory until it's added
+    let id = ui.id().with("metadata_new_key");
+    let mut new_key = ui.data_mut(|d| d.get_temp::<String>(id).unwrap_or_default());
+    ui.horizontal(|ui| {
+        let label = ui.label(RichText::new("New key: ").strong());
+        ui.text_edit_singleline(&mut new_key).labelled_by(label.id);
+        let addable = !new_key.trim().is_empty() && !metadata.contains_key(new_key.trim());
+        if ui.add_enabled(addable, egui::Button::new("Add")).clicked() {
+            metadata.insert(new_key.trim().to_string(), String::new());
+            new_key.clear();
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(id, new_key));
+}
+
+/// Copies a profile for the next sequential host, bumping its IP (and optionally gateway) by one.
+/// Fields that can't be incremented are left as is and reported in the returned warning.
+fn clone_incremented(profiles: &HashMap<String, NetworkProfile>, profile: &NetworkProfile, increment_gateway: bool) -> (NetworkProfile, Option<String>) {
+    let mut clone = NetworkProfile {
+        name: network::copy_profile_name(profiles, &profile.name),
+        ..profile.clone()
+    };
+    let mut warnings = Vec::new();
+
+    match network::next_host(&profile.ip, &profile.subnet) {
+        Ok(ip) => {
+            clone.ip = ip.to_string();
+            clone.name = network::unique_profile_name(profiles, &format!("{} {}", profile.name, ip));
+        }
+        Err(e) => warnings.push(format!("IP not incremented: {}", e)),
+    }
+    if increment_gateway {
+        match network::next_host(&profile.gateway, &profile.subnet) {
+            Ok(gateway) => clone.gateway = gateway.to_string(),
+            Err(e) => warnings.push(format!("Gateway not incremented: {}", e)),
+        }
+    }
+
+    (clone, (!warnings.is_empty()).then(|| warnings.join("\n")))
+}
+
+/// Flags invalid input with an icon and message so it isn't conveyed by color alone.
+fn show_validation(ui: &mut egui::Ui, valid: bool, message: &str) {
+    if !valid {
+        ui.label(RichText::new(format!("❌ {}", message)).color(Color32::RED));
+    }
+}
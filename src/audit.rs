@@ -0,0 +1,73 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
+use crate::network::{ApplyOutcome, NetworkProfile};
+
+/// One applied profile in the audit log
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub profile: String,
+    pub adapter: String,
+    pub result: String,
+    pub user: String,
+}
+
+impl AuditEntry {
+    pub fn new(profile: &NetworkProfile, outcome: &ApplyOutcome) -> Self {
+        let result = match outcome {
+            ApplyOutcome::Applied => "Applied".to_string(),
+            ApplyOutcome::Cancelled => "Cancelled".to_string(),
+            ApplyOutcome::Failed(e) => format!("Failed: {}", e),
+            ApplyOutcome::RolledBack(e) => format!("Rolled back: {}", e),
+        };
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            profile: profile.name.clone(),
+            adapter: profile.target_adapter().to_string(),
+            result,
+            user: std::env::var("USERNAME").or_else(|_| std::env::var("USER")).unwrap_or_default(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        [&self.timestamp, &self.profile, &self.adapter, &self.result, &self.user]
+            .map(|field| csv_field(field))
+            .join(",")
+    }
+}
+
+/// The append-only audit log, kept next to the app's saved state.
+pub fn audit_log_path() -> PathBuf {
+    eframe::storage_dir("Net Profiler").unwrap_or_default().join("audit.csv")
+}
+
+/// The app's log of commands run and their errors, next to the audit log.
+pub fn log_path() -> PathBuf {
+    audit_log_path().with_file_name("net_profiler.log")
+}
+
+/// Appends an entry to the audit log, writing the CSV header when the log is new.
+pub fn record_apply(entry: AuditEntry) {
+    let path = audit_log_path();
+    let is_new = !path.exists();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    let result = OpenOptions::new().create(true).append(true).open(&path).and_then(|mut file| {
+        if is_new {
+            writeln!(file, "timestamp,profile,adapter,result,user")?;
+        }
+        writeln!(file, "{}", entry.to_csv())
+    });
+    if let Err(e) = result {
+        log::error!("Error writing audit log {}: {}", path.display(), e);
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,128 @@
+use std::{path::Path, sync::atomic::AtomicBool};
+
+use crate::diagnostics;
+use crate::file_operations;
+use crate::logging;
+use crate::network::{self, ApplyOutcome};
+
+const USAGE: &str = "Usage:
+  net_profiler                                          Start the GUI
+  net_profiler --apply <profile> --file <path> [--adapter <interface>] [--key <key>]
+  net_profiler --list --file <path> [--key <key>]
+  net_profiler --capture <interface>
+  net_profiler --doctor
+
+Files exported with a shared key are verified with --key, or the NET_PROFILER_KEY environment variable.
+Add --console to any of these to also show the log in a console, for debugging.";
+
+/// Runs the command given on the command line, for using the app from scripts and on machines
+/// without a display. Returns the exit code, or None when there's no command and the GUI should start.
+pub fn run(args: &[String]) -> Option<i32> {
+    let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).map(String::as_str);
+    let has = |flag: &str| args.iter().any(|arg| arg == flag);
+    let commands = ["--help", "-h", "--doctor", "--capture", "--apply", "--list"];
+    if !args.iter().any(|arg| commands.contains(&arg.as_str())) {
+        return None;
+    }
+    // Release builds on Windows have no stdout for the command's output otherwise
+    logging::attach_console();
+
+    if has("--help") || has("-h") {
+        println!("{}", USAGE);
+        return Some(0);
+    }
+    if has("--doctor") {
+        let checks = diagnostics::run_diagnostics();
+        print!("{}", diagnostics::format_report(&checks));
+        return Some(if checks.iter().all(|check| check.passed) { 0 } else { 1 });
+    }
+    if has("--capture") {
+        let Some(adapter) = value("--capture") else {
+            return Some(usage_error("--capture needs an interface name"));
+        };
+        return Some(match network::capture_current_profile(adapter) {
+            Ok(profile) => match serde_json::to_string_pretty(&profile) {
+                Ok(json) => {
+                    println!("{}", json);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Error serializing profile: {}", e);
+                    1
+                }
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                1
+            }
+        });
+    }
+    let Some(file) = value("--file") else {
+        return Some(usage_error("--apply and --list need a profile file given with --file"));
+    };
+    // The environment keeps the key out of the process list and shell history
+    let key = value("--key").map(String::from).or_else(|| std::env::var("NET_PROFILER_KEY").ok());
+    let profiles = match file_operations::import_verified(Path::new(file), key.as_deref()) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Some(1);
+        }
+    };
+
+    if has("--list") {
+        let mut names: Vec<&String> = profiles.keys().collect();
+        names.sort();
+        for name in names {
+            println!("{}\t{}", name, profiles[name].summary());
+        }
+        return Some(0);
+    }
+
+    let Some(name) = value("--apply") else {
+        return Some(usage_error("--apply needs a profile name"));
+    };
+    let Some(profile) = profiles.get(name) else {
+        eprintln!("No profile named {} in {}", name, file);
+        return Some(1);
+    };
+    let mut profile = profile.clone();
+    match value("--adapter") {
+        Some(adapter) => profile.adapter = adapter.to_string(),
+        None => if let Some(adapter) = profile.bound_adapter() {
+            profile.adapter = adapter;
+        },
+    }
+    let problems = profile.validation_errors();
+    if !problems.is_empty() {
+        eprintln!("Profile {} is invalid:", name);
+        for problem in problems {
+            eprintln!("  {}", problem);
+        }
+        return Some(1);
+    }
+
+    println!("Applying {} to {}", name, profile.target_adapter());
+    Some(match profile.load_cancellable(&AtomicBool::new(false)) {
+        ApplyOutcome::Applied => {
+            println!("Profile applied");
+            for reason in network::restart_reasons() {
+                println!("Restart the computer for this to take effect: {}", reason);
+            }
+            0
+        }
+        ApplyOutcome::Cancelled => {
+            eprintln!("Apply cancelled");
+            1
+        }
+        ApplyOutcome::Failed(e) | ApplyOutcome::RolledBack(e) => {
+            eprintln!("Error applying profile: {}", e);
+            1
+        }
+    })
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("{}\n\n{}", message, USAGE);
+    2
+}
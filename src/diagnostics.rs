@@ -0,0 +1,98 @@
+use std::{fmt::Write as _, fs::OpenOptions, path::Path, sync::atomic::AtomicBool};
+
+use crate::audit;
+use crate::elevation;
+use crate::network::{self, ApplyOutcome, NetworkProfile};
+
+/// Result of a single environment check
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, passed: bool, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), passed, detail: detail.into() }
+    }
+}
+
+/// Checks that the environment can apply profiles, so problems aren't blamed on a profile.
+pub fn run_diagnostics() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let elevated = elevation::is_elevated();
+    checks.push(Check::new("Elevation", elevated, match elevated {
+        true => "Running with administrator rights",
+        false => "Not elevated, applying profiles will fail",
+    }));
+
+    for (tool, found) in network::check_tooling() {
+        checks.push(Check::new(format!("Tool: {}", tool), found, match found {
+            true => "Found on PATH",
+            false => "Not found on PATH",
+        }));
+    }
+
+    if cfg!(target_os = "linux") {
+        checks.push(Check::new("DNS", true, format!("Set through {}", network::dns_manager())));
+    }
+
+    let adapters = match network::interfaces() {
+        Ok(interfaces) => {
+            checks.push(Check::new("Interface enumeration", !interfaces.is_empty(), format!("{} interfaces found", interfaces.len())));
+            interfaces.into_iter().map(|interface| interface.name).collect()
+        }
+        Err(e) => {
+            checks.push(Check::new("Interface enumeration", false, e.to_string()));
+            Vec::new()
+        }
+    };
+
+    let log_path = audit::audit_log_path();
+    let writable = log_path.parent().map(check_writable).unwrap_or(Err("No state directory".to_string()));
+    checks.push(Check::new("State directory", writable.is_ok(), match writable {
+        Ok(_) => format!("{} is writable", log_path.parent().unwrap_or(Path::new("")).display()),
+        Err(e) => e,
+    }));
+
+    // Dry-run a trivial profile to make sure the command builders work here
+    let sample = NetworkProfile {
+        name: "Diagnostics".to_string(),
+        adapter: adapters.first().cloned().unwrap_or_else(|| "eth0".to_string()),
+        ip: "192.168.0.2".to_string(),
+        subnet: "255.255.255.0".to_string(),
+        ..Default::default()
+    };
+    let (commands, outcome) = sample.dry_run();
+    match outcome {
+        ApplyOutcome::Applied => checks.push(Check::new("Sample dry run", true, commands.join("\n"))),
+        ApplyOutcome::Cancelled => checks.push(Check::new("Sample dry run", false, "Cancelled")),
+        ApplyOutcome::Failed(e) | ApplyOutcome::RolledBack(e) => checks.push(Check::new("Sample dry run", false, e.to_string())),
+    }
+
+    checks
+}
+
+/// Formats the checks as a pass/fail report.
+pub fn format_report(checks: &[Check]) -> String {
+    let mut report = String::new();
+    for check in checks {
+        let _ = writeln!(report, "[{}] {}", if check.passed { "PASS" } else { "FAIL" }, check.name);
+        for line in check.detail.lines() {
+            let _ = writeln!(report, "       {}", line);
+        }
+    }
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    let _ = writeln!(report, "\n{} of {} checks passed", checks.len() - failed, checks.len());
+    report
+}
+
+fn check_writable(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+    let probe = dir.join(".write_test");
+    OpenOptions::new().create(true).truncate(true).write(true).open(&probe)
+        .map_err(|e| format!("Can't write to {}: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(probe);
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::process::Command;
+
+/// How to ask for elevated rights when relaunching on Linux.
+/// Windows always relaunches through the UAC "runas" verb.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ElevationMethod {
+    #[default]
+    Pkexec,
+    Sudo,
+    Gksudo,
+}
+
+impl ElevationMethod {
+    pub const ALL: [ElevationMethod; 3] = [ElevationMethod::Pkexec, ElevationMethod::Sudo, ElevationMethod::Gksudo];
+
+    pub fn program(&self) -> &'static str {
+        match self {
+            ElevationMethod::Pkexec => "pkexec",
+            ElevationMethod::Sudo => "sudo",
+            ElevationMethod::Gksudo => "gksudo",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElevationStatus {
+    AlreadyElevated,
+    /// An elevated copy was started and this process should exit
+    Relaunched,
+    Failed(String),
+}
+
+/// Whether the process has the administrator/root rights needed to apply profiles.
+#[cfg(target_os = "windows")]
+pub fn is_elevated() -> bool {
+    // `net session` is only permitted for administrators
+    Command::new("net").arg("session").output().is_ok_and(|output| output.status.success())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn is_elevated() -> bool {
+    // The second Uid field is the effective user id
+    std::fs::read_to_string("/proc/self/status").is_ok_and(|status| {
+        status.lines()
+            .find_map(|line| line.strip_prefix("Uid:"))
+            .and_then(|uids| uids.split_whitespace().nth(1).map(|euid| euid == "0"))
+            .unwrap_or(false)
+    })
+}
+
+/// Starts an elevated copy of this program with the same arguments unless already elevated.
+/// Nothing is printed, the caller decides how to surface the returned status.
+pub fn check_and_relaunch_elevated(method: ElevationMethod) -> ElevationStatus {
+    if is_elevated() {
+        return ElevationStatus::AlreadyElevated;
+    }
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return ElevationStatus::Failed(format!("Can't find the running executable: {}", e)),
+    };
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match relaunch(method, &exe.to_string_lossy(), &args) {
+        Ok(_) => ElevationStatus::Relaunched,
+        Err(e) => ElevationStatus::Failed(e),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn relaunch(_method: ElevationMethod, exe: &str, args: &[String]) -> Result<(), String> {
+    // Start-Process -Verb RunAs goes through ShellExecute, which shows the UAC prompt
+    let mut command = format!("Start-Process -FilePath '{}' -Verb RunAs", exe.replace('\'', "''"));
+    if !args.is_empty() {
+        let args: Vec<String> = args.iter().map(|arg| format!("'{}'", arg.replace('\'', "''"))).collect();
+        command.push_str(&format!(" -ArgumentList {}", args.join(",")));
+    }
+    Command::new("powershell")
+        .args(["-NoProfile", "-Command", &command])
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to run powershell: {}", e))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn relaunch(method: ElevationMethod, exe: &str, args: &[String]) -> Result<(), String> {
+    let mut command = Command::new(method.program());
+    if method == ElevationMethod::Pkexec {
+        // pkexec clears the environment, so carry over what the GUI needs to reach the display
+        let display_vars = ["DISPLAY", "XAUTHORITY", "WAYLAND_DISPLAY", "XDG_RUNTIME_DIR"];
+        command.arg("env");
+        for var in display_vars {
+            if let Ok(value) = std::env::var(var) {
+                command.arg(format!("{}={}", var, value));
+            }
+        }
+    }
+    command.arg(exe).args(args)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to run {}: {}", method.program(), e))
+}
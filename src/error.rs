@@ -0,0 +1,50 @@
+use std::fmt;
+
+/// Why configuring the network failed, so callers can tell a missing tool from a bad value
+/// from a permissions problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetProfilerError {
+    /// The program isn't installed or isn't on PATH
+    CommandNotFound(String),
+    CommandFailed {
+        program: String,
+        /// What the program said about the failure: its stderr, or its stdout when stderr
+        /// was empty, as netsh reports errors there
+        output: String,
+        code: Option<i32>,
+    },
+    InvalidIp(String),
+    InvalidSubnet(String),
+    /// A DNS server that isn't an IP address, or none at all when empty
+    InvalidDns(String),
+    /// The command needs administrator rights the app doesn't have
+    Elevation(String),
+    /// The profile can't be applied as configured, e.g. no adapter is selected
+    Profile(String),
+    /// The operation isn't available on this platform
+    Unsupported(String),
+}
+
+pub type Result<T> = std::result::Result<T, NetProfilerError>;
+
+impl fmt::Display for NetProfilerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetProfilerError::CommandNotFound(program) => write!(f, "{} was not found", program),
+            NetProfilerError::CommandFailed { program, output, code } => match (output.is_empty(), code) {
+                (false, Some(code)) => write!(f, "{} failed with exit code {}: {}", program, code, output),
+                (false, None) => write!(f, "{} failed: {}", program, output),
+                (true, Some(code)) => write!(f, "{} exited with code {}", program, code),
+                (true, None) => write!(f, "{} was terminated", program),
+            },
+            NetProfilerError::InvalidIp(ip) => write!(f, "Invalid IP address: {}", ip),
+            NetProfilerError::InvalidSubnet(subnet) => write!(f, "Invalid subnet mask: {}", subnet),
+            NetProfilerError::InvalidDns(server) if server.is_empty() => write!(f, "No DNS servers to set"),
+            NetProfilerError::InvalidDns(server) => write!(f, "Invalid DNS server: {}", server),
+            NetProfilerError::Elevation(message) => write!(f, "Administrator rights required: {}", message),
+            NetProfilerError::Profile(message) | NetProfilerError::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for NetProfilerError {}
@@ -0,0 +1,344 @@
+use std::{collections::{BTreeMap, HashMap}, path::{Path, PathBuf}, sync::mpsc::{self, Receiver}};
+
+use hmac::{Hmac, Mac};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+
+use crate::network::{self, MaskFormat, NetworkProfile};
+
+/// Formats profile files can be written in, picked by the file's extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// .nprf or .json
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ProfileFormat {
+    pub const EXTENSIONS: [&'static str; 5] = ["nprf", "json", "toml", "yaml", "yml"];
+
+    /// The format for the path's extension, or None when it isn't a profile file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "nprf" | "json" => Some(ProfileFormat::Json),
+            "toml" => Some(ProfileFormat::Toml),
+            "yaml" | "yml" => Some(ProfileFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Reads profiles from a profile file, in the format its extension names and JSON otherwise.
+/// Adapters are machine specific, so they're cleared.
+pub fn import_profiles_from_file(path: &Path) -> Result<HashMap<String, NetworkProfile>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    let profiles: HashMap<String, NetworkProfile> = match ProfileFormat::from_path(path).unwrap_or(ProfileFormat::Json) {
+        ProfileFormat::Json => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        ProfileFormat::Toml => toml::from_str(&content).map_err(|e| e.to_string()),
+        ProfileFormat::Yaml => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+    }.map_err(|e| format!("Error parsing {}: {}", path.display(), e))?;
+
+    Ok(profiles.into_iter()
+        .map(|(name, profile)| (name, NetworkProfile {
+            adapter: String::new(),
+            ..profile
+        }))
+        .collect())
+}
+
+/// Writes profiles without their machine specific adapters, in the format the path's extension
+/// names and JSON otherwise. Pretty output is meant for sharing and reviewing, compact output
+/// for size; TOML and YAML are always laid out for reading.
+pub fn export_profiles_to_file(path: &Path, profiles: &HashMap<String, NetworkProfile>, pretty: bool) -> Result<(), String> {
+    // Remove adapter field from profiles
+    let export_profiles: HashMap<&String, NetworkProfile> = profiles.iter()
+        .map(|(name, profile)| (name, NetworkProfile {
+            adapter: String::new(),
+            ..profile.clone()
+        }))
+        .collect();
+
+    // Ordered by name so files under version control diff cleanly
+    let export_profiles: BTreeMap<&String, NetworkProfile> = export_profiles.into_iter().collect();
+    let content = match (ProfileFormat::from_path(path).unwrap_or(ProfileFormat::Json), pretty) {
+        (ProfileFormat::Json, true) => serde_json::to_string_pretty(&export_profiles).map_err(|e| e.to_string()),
+        (ProfileFormat::Json, false) => serde_json::to_string(&export_profiles).map_err(|e| e.to_string()),
+        (ProfileFormat::Toml, _) => toml::to_string_pretty(&export_profiles).map_err(|e| e.to_string()),
+        (ProfileFormat::Yaml, _) => serde_yaml::to_string(&export_profiles).map_err(|e| e.to_string()),
+    }?;
+    std::fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// The profile library, kept next to the app's saved state in the export format so it can be
+/// read and hand edited. Unlike an export it keeps adapters, as it belongs to this machine.
+pub fn library_path() -> PathBuf {
+    eframe::storage_dir("Net Profiler").unwrap_or_default().join("profiles.nprf")
+}
+
+pub fn load_library(path: &Path) -> Result<HashMap<String, NetworkProfile>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+}
+
+pub fn save_library(path: &Path, profiles: &HashMap<String, NetworkProfile>) -> Result<(), String> {
+    let profiles: BTreeMap<&String, &NetworkProfile> = profiles.iter().collect();
+    let content = serde_json::to_string_pretty(&profiles).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    // Write beside and rename over, so a crash mid-write can't leave the library truncated
+    let temp = path.with_extension("nprf.tmp");
+    std::fs::write(&temp, content).map_err(|e| e.to_string())?;
+    std::fs::rename(&temp, path).map_err(|e| e.to_string())
+}
+
+/// Writes profiles wrapped with a SHA-256 checksum of their content, plus an HMAC when a shared key is
+/// given, so recipients can tell the file wasn't corrupted or tampered with after publishing.
+pub fn export_signed(path: &Path, profiles: &HashMap<String, NetworkProfile>, key: Option<&str>, pretty: bool) -> Result<(), String> {
+    let export_profiles: HashMap<&String, NetworkProfile> = profiles.iter()
+        .map(|(name, profile)| (name, NetworkProfile {
+            adapter: String::new(),
+            ..profile.clone()
+        }))
+        .collect();
+    let profiles = serde_json::to_value(&export_profiles).map_err(|e| e.to_string())?;
+
+    let payload = profiles.to_string();
+    let mut signed = serde_json::json!({
+        "profiles": profiles,
+        "sha256": hex(&Sha256::digest(payload.as_bytes())),
+    });
+    if let Some(key) = key.filter(|key| !key.is_empty()) {
+        signed["hmac_sha256"] = hmac(key, &payload).into();
+    }
+
+    let content = match pretty {
+        true => serde_json::to_string_pretty(&signed),
+        false => serde_json::to_string(&signed),
+    }.map_err(|e| e.to_string())?;
+    std::fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// Reads profiles from a profile file, verifying its checksum and HMAC when it was exported signed.
+/// Unsigned files are accepted as-is so plain exports keep importing, unless a signing key is
+/// set, in which case only files signed with it are.
+pub fn import_verified(path: &Path, key: Option<&str>) -> Result<HashMap<String, NetworkProfile>, String> {
+    let key = key.filter(|key| !key.is_empty());
+    let unsigned = || format!("{} isn't signed with the shared key, so it can't be verified", path.display());
+    // Only JSON exports are signed
+    if ProfileFormat::from_path(path).is_some_and(|format| format != ProfileFormat::Json) {
+        return match key {
+            Some(_) => Err(unsigned()),
+            None => import_profiles_from_file(path),
+        };
+    }
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?;
+
+    // Profiles are objects, so a string checksum can only belong to a signed file
+    let Some(checksum) = value.get("sha256").and_then(|checksum| checksum.as_str()) else {
+        return match key {
+            Some(_) => Err(unsigned()),
+            None => import_profiles_from_file(path),
+        };
+    };
+    let profiles = value.get("profiles").cloned().unwrap_or_default();
+    // serde_json sorts object keys, so this matches the payload that was signed
+    let payload = profiles.to_string();
+    if hex(&Sha256::digest(payload.as_bytes())) != checksum {
+        return Err(format!("Checksum mismatch in {}: the file was corrupted or modified after export", path.display()));
+    }
+    match (value.get("hmac_sha256").and_then(|mac| mac.as_str()), key) {
+        (Some(mac), Some(key)) if hmac(key, &payload) != mac => {
+            return Err(format!("Signature mismatch in {}: the file was modified or signed with a different key", path.display()));
+        }
+        (Some(_), None) => {
+            return Err(format!("{} is signed with a shared key; set the signing key to verify it", path.display()));
+        }
+        // A checksum alone can be recomputed by whoever edited the file
+        (None, Some(_)) => return Err(unsigned()),
+        _ => {}
+    }
+
+    let profiles: HashMap<String, NetworkProfile> = serde_json::from_value(profiles)
+        .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?;
+    Ok(profiles.into_iter()
+        .map(|(name, profile)| (name, NetworkProfile {
+            adapter: String::new(),
+            ..profile
+        }))
+        .collect())
+}
+
+fn hmac(key: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    hex(&mac.finalize().into_bytes())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Rewrites every profile's subnet mask in one format so a library doesn't mix "/24" and
+/// "255.255.255.0". Returns how many masks were converted; invalid masks are left for validation.
+pub fn normalize_masks(profiles: &mut HashMap<String, NetworkProfile>, format: MaskFormat) -> usize {
+    let mut converted = 0;
+    for profile in profiles.values_mut() {
+        match network::normalize_subnet(&profile.subnet, format) {
+            Some(subnet) if subnet != profile.subnet => {
+                profile.subnet = subnet;
+                converted += 1;
+            }
+            _ => {}
+        }
+    }
+    converted
+}
+
+/// Watches a profile file for changes made outside the app, such as in an editor or by a git pull.
+pub struct FileWatcher {
+    pub path: PathBuf,
+    changes: Receiver<()>,
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Starts watching `path`, calling `on_change` from the watcher's thread after each change.
+    pub fn new(path: &Path, on_change: impl Fn() + Send + 'static) -> Result<Self, String> {
+        let path = path.to_path_buf();
+        let (sender, changes) = mpsc::channel();
+        let watched = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|event| !event.kind.is_access() && event.paths.contains(&watched)) {
+                let _ = sender.send(());
+                on_change();
+            }
+        }).map_err(|e| e.to_string())?;
+
+        // Editors often save by replacing the file, which ends a watch on the file itself
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        watcher.watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Error watching {}: {}", path.display(), e))?;
+        Ok(Self { path, changes, _watcher: watcher })
+    }
+
+    /// Whether the file changed since the last call.
+    pub fn changed(&self) -> bool {
+        self.changes.try_iter().count() > 0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::DNSProvider;
+
+    /// A path in the temp directory unique to the test, with the given file name
+    fn temp_path(test: &str, file: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("net_profiler_{}_{}", std::process::id(), test));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(file)
+    }
+
+    fn profiles() -> HashMap<String, NetworkProfile> {
+        let profile = NetworkProfile {
+            name: "ROV".to_string(),
+            adapter: "eth0".to_string(),
+            ip: "192.168.1.10".to_string(),
+            subnet: "255.255.255.0".to_string(),
+            gateway: "192.168.1.1".to_string(),
+            dns_provider: DNSProvider::Quad9,
+            ..Default::default()
+        };
+        HashMap::from([(profile.name.clone(), profile)])
+    }
+
+    /// The profiles as an import returns them, without their adapters
+    fn exported() -> HashMap<String, NetworkProfile> {
+        profiles().into_iter().map(|(name, profile)| (name, NetworkProfile { adapter: String::new(), ..profile })).collect()
+    }
+
+    /// Rewrites the signed file at `path` through `change`
+    fn edit_signed(path: &Path, change: impl FnOnce(&mut serde_json::Value)) {
+        let mut value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        change(&mut value);
+        std::fs::write(path, value.to_string()).unwrap();
+    }
+
+    #[test]
+    fn signed_exports_import_with_their_key() {
+        let path = temp_path("signed_round_trip", "profiles.nprf");
+        export_signed(&path, &profiles(), Some("secret"), true).unwrap();
+        assert_eq!(import_verified(&path, Some("secret")).unwrap(), exported());
+
+        let path = temp_path("checksum_round_trip", "profiles.nprf");
+        export_signed(&path, &profiles(), None, false).unwrap();
+        assert_eq!(import_verified(&path, None).unwrap(), exported());
+    }
+
+    #[test]
+    fn tampered_payloads_are_refused() {
+        let path = temp_path("tampered", "profiles.nprf");
+        export_signed(&path, &profiles(), Some("secret"), true).unwrap();
+        edit_signed(&path, |value| value["profiles"]["ROV"]["gateway"] = "192.168.1.254".into());
+        assert!(import_verified(&path, Some("secret")).unwrap_err().starts_with("Checksum mismatch"));
+    }
+
+    #[test]
+    fn stripped_signatures_are_refused_when_a_key_is_set() {
+        let path = temp_path("stripped_hmac", "profiles.nprf");
+        export_signed(&path, &profiles(), Some("secret"), true).unwrap();
+        edit_signed(&path, |value| {
+            value.as_object_mut().unwrap().remove("hmac_sha256");
+        });
+        assert!(import_verified(&path, Some("secret")).is_err());
+
+        let path = temp_path("stripped_checksum", "profiles.nprf");
+        export_signed(&path, &profiles(), Some("secret"), true).unwrap();
+        edit_signed(&path, |value| {
+            let signed = value.as_object_mut().unwrap();
+            signed.remove("sha256");
+            signed.remove("hmac_sha256");
+            *value = signed.remove("profiles").unwrap();
+        });
+        assert!(import_verified(&path, Some("secret")).is_err());
+        // Without a key it's just a plain export
+        assert_eq!(import_verified(&path, None).unwrap(), exported());
+    }
+
+    #[test]
+    fn signatures_from_another_key_are_refused() {
+        let path = temp_path("wrong_key", "profiles.nprf");
+        export_signed(&path, &profiles(), Some("secret"), true).unwrap();
+        assert!(import_verified(&path, Some("guess")).unwrap_err().starts_with("Signature mismatch"));
+        assert!(import_verified(&path, None).is_err());
+    }
+
+    #[test]
+    fn toml_and_yaml_keep_custom_dns_empty_lists_and_unset_options() {
+        let profile = NetworkProfile {
+            name: "Lab".to_string(),
+            ip: "10.0.0.5".to_string(),
+            subnet: "/24".to_string(),
+            dns_provider: DNSProvider::Custom,
+            primary_dns: "10.0.0.53".to_string(),
+            secondary_dns: String::new(),
+            extra_dns: Vec::new(),
+            routes: Vec::new(),
+            mtu: None,
+            mac_address: None,
+            proxy: None,
+            ..Default::default()
+        };
+        let profiles = HashMap::from([(profile.name.clone(), profile)]);
+        for file in ["profiles.toml", "profiles.yaml", "profiles.json"] {
+            let path = temp_path("formats", file);
+            export_profiles_to_file(&path, &profiles, true).unwrap();
+            assert_eq!(import_profiles_from_file(&path).unwrap(), profiles, "{}", file);
+        }
+    }
+}
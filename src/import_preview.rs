@@ -0,0 +1,282 @@
+use std::{collections::HashMap, fmt};
+
+use eframe::egui;
+use egui::{Color32, RichText};
+
+use crate::network::{self, NetworkProfile};
+
+/// Window listing what an import would add or overwrite, so the user picks what to take.
+pub struct ImportPreview {
+    source: String,
+    entries: Vec<ImportEntry>,
+    /// The profiles already there, which renamed imports must not collide with
+    current: HashMap<String, NetworkProfile>,
+    strategy: ImportStrategy,
+}
+
+/// How the imported profiles are combined with the ones already there
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ImportStrategy {
+    /// Pick profile by profile what to import
+    Choose,
+    /// Add every imported profile, numbering the ones whose name is taken
+    Append,
+    /// Swap the whole library for the file's profiles
+    Replace,
+    /// Add new profiles and overwrite same named ones with the imported version
+    Merge,
+}
+
+impl ImportStrategy {
+    const ALL: [ImportStrategy; 4] = [ImportStrategy::Choose, ImportStrategy::Append, ImportStrategy::Replace, ImportStrategy::Merge];
+
+    fn label(self) -> &'static str {
+        match self {
+            ImportStrategy::Choose => "Choose each",
+            ImportStrategy::Append => "Append all",
+            ImportStrategy::Replace => "Replace all",
+            ImportStrategy::Merge => "Merge by name (imported wins)",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ImportStrategy::Choose => "Import the profiles ticked below",
+            ImportStrategy::Append => "Add every profile, under a numbered name where the name is taken. Identical ones are skipped",
+            ImportStrategy::Replace => "Remove every current profile and keep only the file's",
+            ImportStrategy::Merge => "Add new profiles and overwrite ones with the same name, for syncing a master file",
+        }
+    }
+}
+
+/// Counts of what an import did, e.g. "imported 5, updated 2, skipped 1"
+#[derive(Default, Clone, Copy)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "imported {}, updated {}, skipped {}", self.imported, self.updated, self.skipped)
+    }
+}
+
+struct ImportEntry {
+    profile: NetworkProfile,
+    status: ImportStatus,
+    selected: bool,
+    /// Import a colliding profile under a numbered name instead of overwriting
+    keep_both: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ImportStatus {
+    New,
+    /// A different profile already has this name and would be overwritten
+    Collision,
+    /// Matches an existing profile apart from the machine specific adapter
+    Identical,
+}
+
+pub enum ImportAction {
+    None,
+    Import {
+        profiles: Vec<NetworkProfile>,
+        /// Remove the current profiles first
+        replace_all: bool,
+        summary: ImportSummary,
+    },
+    Close,
+}
+
+impl ImportPreview {
+    /// Compares the file's profiles against the current ones. New profiles start selected,
+    /// overwrites have to be opted into and identical duplicates are left out.
+    pub fn new(source: String, imported: HashMap<String, NetworkProfile>, current: &HashMap<String, NetworkProfile>) -> Self {
+        let mut entries: Vec<ImportEntry> = imported.into_iter()
+            .map(|(name, profile)| {
+                // Profiles are stored under their file key, so keep the name in step with it
+                let profile = NetworkProfile { name, ..profile };
+                let status = match current.get(&profile.name) {
+                    None => ImportStatus::New,
+                    Some(existing) if NetworkProfile { adapter: String::new(), ..existing.clone() } == profile => ImportStatus::Identical,
+                    Some(_) => ImportStatus::Collision,
+                };
+                ImportEntry { selected: status == ImportStatus::New, profile, status, keep_both: false }
+            })
+            .collect();
+        entries.sort_by(|a, b| a.status.cmp(&b.status).then_with(|| a.profile.name.cmp(&b.profile.name)));
+        Self { source, entries, current: current.clone(), strategy: ImportStrategy::Choose }
+    }
+
+    /// The profiles importing with the chosen strategy would store, and what that amounts to.
+    fn plan(&self) -> (Vec<NetworkProfile>, ImportSummary) {
+        let mut taken = match self.strategy {
+            ImportStrategy::Replace => HashMap::new(),
+            _ => self.current.clone(),
+        };
+        let mut profiles = Vec::new();
+        let mut summary = ImportSummary::default();
+        for entry in self.entries.iter() {
+            let (take, rename) = match self.strategy {
+                ImportStrategy::Choose => (entry.selected, entry.keep_both),
+                ImportStrategy::Append => (entry.status != ImportStatus::Identical, true),
+                ImportStrategy::Replace => (true, false),
+                ImportStrategy::Merge => (entry.status != ImportStatus::Identical, false),
+            };
+            if !take {
+                summary.skipped += 1;
+                continue;
+            }
+            let mut profile = entry.profile.clone();
+            if rename {
+                profile.name = network::unique_profile_name(&taken, &profile.name);
+            }
+            match taken.contains_key(&profile.name) {
+                true => summary.updated += 1,
+                false => summary.imported += 1,
+            }
+            taken.insert(profile.name.clone(), profile.clone());
+            profiles.push(profile);
+        }
+        (profiles, summary)
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> ImportAction {
+        let mut action = ImportAction::None;
+        egui::Window::new("Import Preview").show(ctx, |ui| {
+            ui.label(format!("From {}", self.source));
+            let count = |status| self.entries.iter().filter(|entry| entry.status == status).count();
+            ui.label(format!(
+                "{} new, {} would overwrite, {} identical",
+                count(ImportStatus::New),
+                count(ImportStatus::Collision),
+                count(ImportStatus::Identical),
+            ));
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                for strategy in ImportStrategy::ALL {
+                    ui.radio_value(&mut self.strategy, strategy, strategy.label())
+                        .on_hover_text(strategy.description());
+                }
+            });
+            if self.strategy == ImportStrategy::Replace && !self.current.is_empty() {
+                ui.label(RichText::new(format!("⚠ All {} current profiles will be removed", self.current.len())).color(ui.visuals().warn_fg_color));
+            }
+            let choosing = self.strategy == ImportStrategy::Choose;
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for entry in self.entries.iter_mut() {
+                    ui.horizontal(|ui| {
+                        let (label, color) = match entry.status {
+                            ImportStatus::New => ("new", Color32::GREEN),
+                            ImportStatus::Collision => ("overwrites", ui.visuals().warn_fg_color),
+                            ImportStatus::Identical => ("identical", Color32::GRAY),
+                        };
+                        ui.add_enabled(choosing && entry.status != ImportStatus::Identical, egui::Checkbox::new(&mut entry.selected, &entry.profile.name))
+                            .on_hover_text(entry.profile.summary());
+                        ui.label(RichText::new(label).color(color).small());
+                        if choosing && entry.status == ImportStatus::Collision && entry.selected {
+                            ui.checkbox(&mut entry.keep_both, "Keep both")
+                                .on_hover_text("Import under a numbered name instead of overwriting");
+                        }
+                    });
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                let (profiles, summary) = self.plan();
+                let replace_all = self.strategy == ImportStrategy::Replace;
+                let button = egui::Button::new(format!("Import {}", profiles.len()));
+                if ui.add_enabled(!profiles.is_empty(), button).on_hover_text(summary.to_string()).clicked() {
+                    action = ImportAction::Import { profiles, replace_all, summary };
+                }
+                if ui.button("Cancel").clicked() {
+                    action = ImportAction::Close;
+                }
+            });
+        });
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, ip: &str) -> NetworkProfile {
+        NetworkProfile { name: name.to_string(), adapter: "eth0".to_string(), ip: ip.to_string(), ..Default::default() }
+    }
+
+    /// Office matches a current profile apart from the adapter, Lab collides with one and Home is new.
+    fn preview() -> ImportPreview {
+        let current = HashMap::from([
+            ("Office".to_string(), profile("Office", "10.0.0.10")),
+            ("Lab".to_string(), profile("Lab", "10.0.1.10")),
+        ]);
+        let imported = HashMap::from([
+            ("Office".to_string(), NetworkProfile { adapter: String::new(), ..profile("Office", "10.0.0.10") }),
+            ("Lab".to_string(), profile("Lab", "10.0.1.99")),
+            ("Home".to_string(), profile("Home", "192.168.1.10")),
+        ]);
+        ImportPreview::new("profiles.nprf".to_string(), imported, &current)
+    }
+
+    fn names(profiles: &[NetworkProfile]) -> Vec<&str> {
+        profiles.iter().map(|profile| profile.name.as_str()).collect()
+    }
+
+    #[test]
+    fn entries_are_classified_against_the_current_profiles() {
+        let preview = preview();
+        let statuses: Vec<(&str, ImportStatus, bool)> = preview.entries.iter()
+            .map(|entry| (entry.profile.name.as_str(), entry.status, entry.selected))
+            .collect();
+        assert!(statuses == [
+            ("Home", ImportStatus::New, true),
+            ("Lab", ImportStatus::Collision, false),
+            ("Office", ImportStatus::Identical, false),
+        ]);
+    }
+
+    #[test]
+    fn plans_follow_the_strategy() {
+        let mut preview = preview();
+        let (profiles, summary) = preview.plan();
+        assert_eq!(names(&profiles), ["Home"]);
+        assert_eq!((summary.imported, summary.updated, summary.skipped), (1, 0, 2));
+
+        // A collision kept alongside the current profile is numbered rather than overwriting it
+        preview.entries[1].selected = true;
+        preview.entries[1].keep_both = true;
+        let (profiles, summary) = preview.plan();
+        assert_eq!(names(&profiles), ["Home", "Lab (2)"]);
+        assert_eq!((summary.imported, summary.updated, summary.skipped), (2, 0, 1));
+
+        preview.strategy = ImportStrategy::Append;
+        let (profiles, summary) = preview.plan();
+        assert_eq!(names(&profiles), ["Home", "Lab (2)"]);
+        assert_eq!((summary.imported, summary.updated, summary.skipped), (2, 0, 1));
+
+        preview.strategy = ImportStrategy::Merge;
+        let (profiles, summary) = preview.plan();
+        assert_eq!(names(&profiles), ["Home", "Lab"]);
+        assert_eq!(profiles[1].ip, "10.0.1.99");
+        assert_eq!((summary.imported, summary.updated, summary.skipped), (1, 1, 1));
+    }
+
+    #[test]
+    fn replace_starts_from_no_profiles() {
+        let mut preview = preview();
+        preview.strategy = ImportStrategy::Replace;
+        let (profiles, summary) = preview.plan();
+        // Nothing is taken, so no name is numbered and nothing counts as an update
+        assert_eq!(names(&profiles), ["Home", "Lab", "Office"]);
+        assert_eq!((summary.imported, summary.updated, summary.skipped), (3, 0, 0));
+    }
+}
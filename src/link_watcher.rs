@@ -0,0 +1,61 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver}, Arc}, time::Duration};
+
+use crate::network;
+
+/// How often the watcher re-reads link states
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Watches interfaces on a background thread for ones that appear or get a link, such as a USB
+/// NIC being plugged in or a cable connected.
+pub struct LinkWatcher {
+    came_up: Receiver<String>,
+    stop: Arc<AtomicBool>,
+}
+
+impl LinkWatcher {
+    /// Starts polling, calling `on_change` from the watcher's thread whenever an interface comes up.
+    /// Interfaces already up when it starts don't count, so starting it applies nothing by itself.
+    pub fn new(on_change: impl Fn() + Send + 'static) -> Self {
+        let (sender, came_up) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        std::thread::spawn(move || {
+            let mut previous = network::link_statuses();
+            while !stopped.load(Ordering::Relaxed) {
+                std::thread::sleep(POLL_INTERVAL);
+                let current = network::link_statuses();
+                for adapter in links_up(&previous, &current) {
+                    log::info!("{} came up", adapter);
+                    if sender.send(adapter).is_err() {
+                        return;
+                    }
+                    on_change();
+                }
+                previous = current;
+            }
+        });
+        Self { came_up, stop }
+    }
+
+    /// Interfaces that came up since the last call.
+    pub fn links_up(&self) -> Vec<String> {
+        self.came_up.try_iter().collect()
+    }
+}
+
+impl Drop for LinkWatcher {
+    fn drop(&mut self) {
+        // The thread ends after its current sleep, rather than holding up the UI thread joining it
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Interfaces up in `current` that were down or missing in `previous`.
+fn links_up(previous: &HashMap<String, network::LinkStatus>, current: &HashMap<String, network::LinkStatus>) -> Vec<String> {
+    let mut adapters: Vec<String> = current.iter()
+        .filter(|(adapter, status)| status.up && !previous.get(*adapter).is_some_and(|previous| previous.up))
+        .map(|(adapter, _)| adapter.clone())
+        .collect();
+    adapters.sort();
+    adapters
+}
@@ -0,0 +1,826 @@
+use std::{collections::{BTreeSet, HashMap, HashSet}, sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver}, Arc}, thread::JoinHandle, time::{Duration, Instant}};
+
+use eframe::egui;
+use egui::RichText;
+use network_interface::Addr;
+
+use crate::error::{self, NetProfilerError};
+use crate::network::{self, ApplyOutcome, ApplySections, ApplyStep, Backend, DiffEntry, NetworkProfile};
+use crate::notification::{self, NotificationKind};
+
+/// Window that previews what applying a profile will change before it's applied.
+pub struct ProfileLoader {
+    pub profile: NetworkProfile,
+    /// Configuration the adapter has now, which the changes applying makes are shown against
+    current: NetworkProfile,
+    /// Configuration the adapter had when Apply was clicked, for the app to keep as a profile
+    pub previous: Option<NetworkProfile>,
+    /// What the system will literally receive, with presets and masks resolved
+    effective: String,
+    /// Everything wrong with the profile, which has to be fixed before it can be applied
+    problems: Vec<NetProfilerError>,
+    /// Set when the gateway isn't on the address's network, so likely unreachable
+    gateway_warning: Option<String>,
+    /// Set when applying looks likely to cut off a remote session
+    disconnect_warning: Option<String>,
+    /// Apply only lists the commands it would run instead of running them
+    preview_only: bool,
+    preview: Option<Vec<String>>,
+    addresses: Vec<Addr>,
+    status: String,
+    apply: Option<ApplyTask>,
+    /// Stay open after an apply so the profile can be applied to another interface
+    keep_open: bool,
+    /// Undo the steps already taken when a later one fails
+    transactional: bool,
+    /// Parts of the profile Apply changes, the rest of the adapter's settings are kept
+    sections: ApplySections,
+    /// Ask before applying, as set in the app's settings
+    confirm_apply: bool,
+    /// Apply was clicked and is waiting on the user to confirm it
+    confirming: bool,
+    /// Probe for another host using the address before applying
+    check_conflicts: bool,
+    /// The probe running on a worker thread, which Apply waits on
+    conflict_check: Option<JoinHandle<error::Result<bool>>>,
+    /// Set when another host answered for the address, until the user applies anyway or cancels
+    conflict: Option<String>,
+    /// Put the previous configuration back after an apply unless the user keeps the changes
+    auto_revert: bool,
+    /// Configuration captured before the apply, which auto revert restores
+    revert_to: Option<NetworkProfile>,
+    revert: Option<RevertTimer>,
+    results: Vec<String>,
+    /// Include loopback and virtual interfaces in the interface list
+    show_all: bool,
+    /// Interfaces given the profile's settings, other than its address, alongside its own
+    also_apply_to: BTreeSet<String>,
+    /// Interfaces enumerated when the loader opened or was last refreshed
+    adapters: Vec<String>,
+    /// Link state of each interface, so one without a link isn't picked by mistake
+    links: HashMap<String, network::LinkStatus>,
+    /// Reads link states on a worker thread, since it runs external tools on some platforms
+    link_poll: Option<JoinHandle<HashMap<String, network::LinkStatus>>>,
+    virtual_adapters: HashSet<String>,
+    enumeration_error: Option<String>,
+    /// Set when an interface to apply to was gone by the time Apply was clicked
+    missing_adapter: Option<String>,
+}
+
+/// A profile being applied on a worker thread
+struct ApplyTask {
+    /// How the apply went on each interface, the profile's own first
+    handle: JoinHandle<Vec<(String, ApplyOutcome)>>,
+    cancel: Arc<AtomicBool>,
+    /// Resetting the adapter to DHCP rather than applying the profile
+    reset: bool,
+    /// Each step as the worker starts it, with the interface it's on
+    progress: Receiver<(String, ApplyStep)>,
+    steps_done: usize,
+    /// Steps the whole apply takes, or 0 when it doesn't report any
+    steps_total: usize,
+}
+
+/// How long an auto reverting apply waits for the user to keep its changes
+const REVERT_AFTER: Duration = Duration::from_secs(30);
+
+/// Restores the previous configuration on a worker thread once the deadline passes, unless the
+/// changes are kept first. It runs on even if the window closes, so a session cut off by the
+/// apply gets its connection back.
+struct RevertTimer {
+    deadline: Instant,
+    keep: Arc<AtomicBool>,
+    revert_now: Arc<AtomicBool>,
+    /// The revert's outcome, or None when the changes were kept
+    handle: JoinHandle<Option<ApplyOutcome>>,
+}
+
+impl RevertTimer {
+    fn start(previous: NetworkProfile, applied: NetworkProfile) -> Self {
+        let deadline = Instant::now() + REVERT_AFTER;
+        let keep = Arc::new(AtomicBool::new(false));
+        let revert_now = Arc::new(AtomicBool::new(false));
+        let (kept, now) = (keep.clone(), revert_now.clone());
+        let handle = std::thread::spawn(move || {
+            while Instant::now() < deadline && !kept.load(Ordering::Relaxed) && !now.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            match kept.load(Ordering::Relaxed) {
+                true => None,
+                false => Some(previous.revert_from(&applied)),
+            }
+        });
+        Self { deadline, keep, revert_now, handle }
+    }
+}
+
+pub enum LoaderAction {
+    None,
+    SaveReport,
+    Close,
+}
+
+impl ProfileLoader {
+    pub fn new(mut profile: NetworkProfile, confirm_apply: bool) -> Self {
+        // Follow the NIC the profile is bound to, whatever it's called on this boot
+        if let Some(adapter) = profile.bound_adapter() {
+            profile.adapter = adapter;
+        }
+        let mut loader = Self {
+            profile,
+            current: NetworkProfile::default(),
+            previous: None,
+            effective: String::new(),
+            problems: Vec::new(),
+            gateway_warning: None,
+            disconnect_warning: None,
+            preview_only: false,
+            preview: None,
+            addresses: Vec::new(),
+            status: String::new(),
+            apply: None,
+            keep_open: false,
+            transactional: false,
+            sections: ApplySections::all(),
+            confirm_apply,
+            confirming: false,
+            check_conflicts: true,
+            conflict_check: None,
+            conflict: None,
+            auto_revert: false,
+            revert_to: None,
+            revert: None,
+            results: Vec::new(),
+            show_all: false,
+            also_apply_to: BTreeSet::new(),
+            adapters: Vec::new(),
+            links: HashMap::new(),
+            link_poll: None,
+            virtual_adapters: HashSet::new(),
+            enumeration_error: None,
+            missing_adapter: None,
+        };
+        loader.refresh_interfaces();
+        loader
+    }
+
+    /// Re-enumerates interfaces, since permissions or hardware may have changed since the app started.
+    pub fn refresh_interfaces(&mut self) {
+        match network::interfaces() {
+            Ok(interfaces) => {
+                self.virtual_adapters = interfaces.iter()
+                    .filter(|interface| network::is_virtual_interface(&interface.name, interface))
+                    .map(|interface| interface.name.clone())
+                    .collect();
+                self.adapters = interfaces.into_iter().map(|interface| interface.name).collect();
+                self.adapters.dedup();
+                self.enumeration_error = None;
+            }
+            Err(e) => self.enumeration_error = Some(format!("Error listing interfaces: {}", e)),
+        }
+        self.refresh();
+    }
+
+    /// Targets a different interface, updating the current configuration and report to match.
+    pub fn select_adapter(&mut self, adapter: String) {
+        self.missing_adapter = None;
+        self.also_apply_to.remove(&adapter);
+        self.profile.adapter = adapter;
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        // With the MTU, MAC and routes too, which the report compares
+        self.current = network::SystemBackend.current_profile(self.profile.target_adapter());
+        let default_route = network::default_route().map(|(interface, _)| interface);
+        self.disconnect_warning = network::disconnect_warning(
+            &self.current,
+            &self.profile,
+            network::remote_session_address(),
+            default_route.as_deref(),
+        );
+        self.problems = self.profile.validation_errors();
+        let profile = &self.profile;
+        let configures_gateway = profile.ipv4_policy == network::FamilyPolicy::Configure && !profile.ip.is_empty() && !profile.gateway.is_empty();
+        self.gateway_warning = (configures_gateway && !network::gateway_in_subnet(&profile.ip, &profile.subnet, &profile.gateway))
+            .then(|| {
+                let network = match network::subnet_prefix(&profile.subnet) {
+                    Some(prefix) => format!("{}/{}", profile.ip, prefix),
+                    None => profile.ip.clone(),
+                };
+                format!("Gateway {} is outside {}, it probably won't be reachable", profile.gateway, network)
+            });
+        self.effective = network::effective_config(&self.profile);
+        self.addresses = network::interface_addresses(self.profile.target_adapter());
+        self.link_poll = Some(std::thread::spawn(network::link_statuses));
+    }
+
+    /// Shows the window, sending an OS notification when an apply finishes if `notify` is set.
+    pub fn show(&mut self, ctx: &egui::Context, notify: bool) -> LoaderAction {
+        let mut action = LoaderAction::None;
+
+        if self.link_poll.as_ref().is_some_and(|poll| poll.is_finished()) {
+            self.links = self.link_poll.take().and_then(|poll| poll.join().ok()).unwrap_or_default();
+        }
+        if self.link_poll.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        if let Some(task) = self.apply.as_mut() {
+            for (adapter, step) in task.progress.try_iter() {
+                task.steps_done += 1;
+                self.status = format!("{}: {}", adapter, step);
+            }
+        }
+        if self.apply.as_ref().is_some_and(|task| task.handle.is_finished()) {
+            let task = self.apply.take().unwrap();
+            let (outcome, additional) = match task.handle.join() {
+                Ok(mut outcomes) => {
+                    let (_, outcome) = outcomes.remove(0);
+                    (Ok(outcome), outcomes)
+                }
+                Err(e) => (Err(e), Vec::new()),
+            };
+            let (status, mut kind) = match &outcome {
+                Ok(outcome) => outcome_message(outcome, task.reset),
+                Err(_) => ("Error applying profile: worker thread panicked".to_string(), NotificationKind::Failure),
+            };
+            let mut status = format!("{}: {}", self.profile.target_adapter(), status);
+            let all_applied = additional.iter().all(|(_, outcome)| *outcome == ApplyOutcome::Applied);
+            if !all_applied {
+                status.push_str(", but not every other interface took it, see Applied");
+                kind = NotificationKind::Failure;
+            }
+            match kind {
+                NotificationKind::Success => log::info!("{}", status),
+                NotificationKind::Failure => log::error!("{}", status),
+            }
+            if notify {
+                notification::notify(&self.profile.name, &status, kind);
+            }
+
+            let revert_to = self.revert_to.take().filter(|_| self.can_revert() && !task.reset);
+            if let Some(previous) = revert_to.filter(|_| matches!(outcome, Ok(ApplyOutcome::Applied))) {
+                // Stay open for the user to keep the changes
+                // Routes it didn't add aren't the apply's to remove
+                let mut applied = self.profile.clone();
+                if !self.sections.contains(ApplySections::ROUTES) {
+                    applied.routes.clear();
+                }
+                self.revert = Some(RevertTimer::start(previous, applied));
+            } else if task.reset {
+                // Stay open so the profile can still be applied, against the adapter's new state
+                self.refresh();
+            } else if self.keep_open {
+                // Ready the loader for the next interface in the rotation
+                self.results.push(status.clone());
+                self.select_adapter(String::new());
+            } else if matches!(outcome, Ok(ApplyOutcome::Applied)) && all_applied {
+                action = LoaderAction::Close;
+            }
+            for (adapter, outcome) in additional.iter() {
+                let (message, _) = outcome_message(outcome, false);
+                self.results.push(format!("{}: {}", adapter, message));
+            }
+            self.status = status;
+        }
+
+        if self.conflict_check.as_ref().is_some_and(|check| check.is_finished()) {
+            let check = self.conflict_check.take().unwrap();
+            match check.join() {
+                Ok(Ok(false)) => self.request_apply(),
+                Ok(Ok(true)) => {
+                    self.conflict = Some(format!("Another host already answers for {} on {}", self.profile.ip, self.profile.target_adapter()));
+                }
+                // An inconclusive probe shouldn't stop the apply
+                Ok(Err(e)) => {
+                    log::error!("Error checking {} for conflicts: {}", self.profile.ip, e);
+                    self.request_apply();
+                }
+                Err(_) => {
+                    log::error!("Error checking {} for conflicts: worker thread panicked", self.profile.ip);
+                    self.request_apply();
+                }
+            }
+        }
+
+        if self.revert.as_ref().is_some_and(|timer| timer.handle.is_finished()) {
+            let timer = self.revert.take().unwrap();
+            self.status = match timer.handle.join() {
+                Ok(None) => format!("{}: Changes kept", self.profile.target_adapter()),
+                Ok(Some(ApplyOutcome::Applied)) => format!("{}: Reverted to the previous configuration", self.profile.target_adapter()),
+                Ok(Some(outcome)) => format!("{}: Reverting failed. {}", self.profile.target_adapter(), outcome_message(&outcome, false).0),
+                Err(_) => "Error reverting: worker thread panicked".to_string(),
+            };
+            log::info!("{}", self.status);
+            self.refresh();
+        }
+
+        egui::Window::new("Profile Loader").show(ctx, |ui| {
+            let mut selected = None;
+            ui.add_enabled_ui(self.apply.is_none(), |ui| {
+                egui::ComboBox::from_label("Interface")
+                    .selected_text(adapter_label(&self.profile.adapter, self.links.get(&self.profile.adapter)))
+                    .show_ui(ui, |ui| {
+                        let shown = self.adapters.iter().filter(|adapter| self.show_all || !self.virtual_adapters.contains(*adapter));
+                        for adapter in shown {
+                            let label = adapter_label(adapter, self.links.get(adapter));
+                            if ui.selectable_label(self.profile.adapter == *adapter, label).clicked() {
+                                selected = Some(adapter.clone());
+                            }
+                        }
+                    });
+                ui.checkbox(&mut self.show_all, "Show all")
+                    .on_hover_text("Include loopback, container, VM and VPN interfaces");
+                if ui.button("⟳").on_hover_text("Refresh interfaces").clicked() {
+                    self.refresh_interfaces();
+                }
+            });
+            let others: Vec<&String> = self.adapters.iter()
+                .filter(|adapter| **adapter != self.profile.adapter && (self.show_all || !self.virtual_adapters.contains(*adapter)))
+                .collect();
+            if !self.profile.adapter.is_empty() && !others.is_empty() {
+                let title = match self.also_apply_to.len() {
+                    0 => "Also apply to".to_string(),
+                    count => format!("Also apply to ({})", count),
+                };
+                ui.add_enabled_ui(self.apply.is_none(), |ui| {
+                    egui::CollapsingHeader::new(title).id_source("also_apply_to").show(ui, |ui| {
+                        ui.label(RichText::new("These get the DNS and other settings, but not the address, gateway, routes or MAC address").small().weak());
+                        for adapter in others {
+                            let mut checked = self.also_apply_to.contains(adapter);
+                            if ui.checkbox(&mut checked, adapter).changed() {
+                                match checked {
+                                    true => self.also_apply_to.insert(adapter.clone()),
+                                    false => self.also_apply_to.remove(adapter),
+                                };
+                            }
+                        }
+                    });
+                });
+            }
+            if let Some(error) = &self.enumeration_error {
+                ui.label(RichText::new(format!("❌ {}", error)).color(egui::Color32::RED));
+            }
+            if let Some(error) = &self.missing_adapter {
+                ui.label(RichText::new(format!("❌ {}", error)).color(egui::Color32::RED));
+            }
+            if let Some(adapter) = selected {
+                self.select_adapter(adapter);
+            }
+
+            let link = self.links.get(self.profile.target_adapter());
+            egui::CollapsingHeader::new("Current Configuration").show(ui, |ui| {
+                if let Some(link) = link {
+                    ui.label(RichText::new("Link").strong());
+                    ui.label(RichText::new(link.summary()).monospace());
+                }
+                show_addresses(ui, &self.addresses);
+            });
+            if let Some(link) = link.filter(|link| !link.up) {
+                let state = if link.state.is_empty() { "down" } else { &link.state };
+                ui.label(RichText::new(format!("⚠ {} is {}, the profile may not take effect until it has a link", self.profile.target_adapter(), state))
+                    .color(ui.visuals().warn_fg_color));
+            }
+            egui::CollapsingHeader::new("Effective Configuration").show(ui, |ui| {
+                ui.label(RichText::new(&self.effective).monospace());
+            });
+            if !self.profile.metadata.is_empty() {
+                egui::CollapsingHeader::new("Metadata").show(ui, |ui| {
+                    egui::Grid::new("loader_metadata").show(ui, |ui| {
+                        for (key, value) in self.profile.metadata.iter() {
+                            ui.label(RichText::new(key).strong());
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+
+            // The markdown report says the same, so it's only kept for Save Report
+            egui::CollapsingHeader::new("Changes").default_open(true).show(ui, |ui| {
+                let diff = self.current.diff_sections(&self.profile, self.sections);
+                if diff.is_empty() {
+                    ui.label("Nothing to change");
+                }
+                let added_color = match ui.visuals().dark_mode {
+                    true => egui::Color32::LIGHT_GREEN,
+                    false => egui::Color32::DARK_GREEN,
+                };
+                let removed_color = ui.visuals().error_fg_color;
+                let added = |ui: &mut egui::Ui, setting: &str, value: &str| {
+                    ui.label(RichText::new(format!("+ {:<8}{}", setting, value)).monospace().color(added_color));
+                };
+                let removed = |ui: &mut egui::Ui, setting: &str, value: &str| {
+                    ui.label(RichText::new(format!("- {:<8}{}", setting, value)).monospace().color(removed_color));
+                };
+                for entry in diff.entries.iter() {
+                    match entry {
+                        DiffEntry::Added { setting, value } => added(ui, setting, value),
+                        DiffEntry::Removed { setting, value } => removed(ui, setting, value),
+                        DiffEntry::Changed { setting, from, to } => {
+                            removed(ui, setting, from);
+                            added(ui, setting, to);
+                        }
+                    }
+                }
+            });
+
+            for problem in self.problems.iter() {
+                ui.label(RichText::new(format!("❌ {}", problem)).color(egui::Color32::RED));
+            }
+            if let Some(warning) = &self.gateway_warning {
+                ui.label(RichText::new(format!("⚠ {}", warning)).color(ui.visuals().warn_fg_color));
+            }
+            if let Some(warning) = &self.disconnect_warning {
+                ui.label(RichText::new(format!("⚠ {}", warning)).color(ui.visuals().warn_fg_color).strong());
+            }
+
+            ui.horizontal(|ui| {
+                match &self.apply {
+                    Some(task) => {
+                        ui.spinner();
+                        if task.steps_total > 0 {
+                            let fraction = task.steps_done as f32 / task.steps_total as f32;
+                            ui.add(egui::ProgressBar::new(fraction.min(1.0)).desired_width(150.0).show_percentage());
+                        }
+                        let cancelling = task.cancel.load(Ordering::Relaxed);
+                        if ui.add_enabled(!cancelling, egui::Button::new("Cancel")).clicked() {
+                            task.cancel.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    None => {
+                        // Another window may be applying to the same adapter
+                        let ready = !self.profile.adapter.is_empty() && self.problems.is_empty()
+                            && !network::is_applying(self.profile.target_adapter());
+                        let ready = ready && self.revert.is_none() && !self.sections.is_empty() && self.conflict_check.is_none();
+                        if ui.add_enabled(ready, egui::Button::new("Apply")).clicked() {
+                            match self.preview_only {
+                                true => self.preview_apply(),
+                                false if self.needs_conflict_check() => self.start_conflict_check(),
+                                false => self.request_apply(),
+                            }
+                        }
+                        if self.conflict_check.is_some() {
+                            ui.spinner();
+                            ui.label(format!("Checking {} isn't in use", self.profile.ip));
+                        }
+                        ui.checkbox(&mut self.preview_only, "Preview")
+                            .on_hover_text("List the commands Apply would run without running them");
+                        let can_reset = !self.profile.adapter.is_empty() && !network::is_applying(self.profile.target_adapter());
+                        if ui.add_enabled(can_reset, egui::Button::new("Reset to DHCP"))
+                            .on_hover_text("Drop static addresses, gateway and DNS and get them from DHCP instead")
+                            .clicked()
+                        {
+                            self.start_reset();
+                        }
+                    }
+                }
+                if ui.button("Save Report").clicked() {
+                    action = LoaderAction::SaveReport;
+                }
+                if ui.button("Close").clicked() {
+                    action = LoaderAction::Close;
+                }
+            });
+
+            if let Some(commands) = &self.preview {
+                egui::CollapsingHeader::new(format!("Commands ({})", commands.len())).default_open(true).show(ui, |ui| {
+                    egui::ScrollArea::vertical().id_source("preview_commands").max_height(150.0).show(ui, |ui| {
+                        for command in commands {
+                            ui.label(RichText::new(command).monospace());
+                        }
+                    });
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Apply:");
+                for (section, label) in ApplySections::LABELS {
+                    let mut checked = self.sections.contains(section);
+                    if ui.add_enabled(self.apply.is_none(), egui::Checkbox::new(&mut checked, label)).changed() {
+                        self.sections.set(section, checked);
+                        self.preview = None;
+                    }
+                }
+            }).response.on_hover_text("Sections left unchecked are skipped, the interface keeps its current settings for them");
+            ui.checkbox(&mut self.keep_open, "Keep open after apply")
+                .on_hover_text("Apply this profile to several interfaces one after another");
+            ui.checkbox(&mut self.check_conflicts, "Check for address conflicts")
+                .on_hover_text(match cfg!(target_os = "windows") {
+                    // Windows can only ping, which isn't tied to the interface
+                    true => "Before applying, make sure no other host already answers at the address, on any network this machine reaches",
+                    false => "Before applying, make sure no other host on the network already has the address",
+                });
+            ui.checkbox(&mut self.transactional, "Roll back on failure")
+                .on_hover_text("If a step fails, restore the interface's previous address, gateway, DNS, MAC address and MTU");
+            let revertable = self.apply.is_none() && self.revert.is_none() && self.also_apply_to.is_empty();
+            ui.add_enabled(revertable, egui::Checkbox::new(&mut self.auto_revert, "Revert unless confirmed"))
+                .on_hover_text(format!(
+                    "Restore the previous configuration {} seconds after applying unless the changes are kept, for remote machines. \
+                     The proxy and computer name are kept either way",
+                    REVERT_AFTER.as_secs(),
+                ))
+                .on_disabled_hover_text("Only the profile's own interface is captured, so applies to other interfaces as well can't be reverted");
+
+            if let Some(timer) = &self.revert {
+                let remaining = timer.deadline.saturating_duration_since(Instant::now()).as_secs() + 1;
+                ui.label(RichText::new(format!("⚠ Reverting to the previous configuration in {} s", remaining)).color(ui.visuals().warn_fg_color).strong());
+                ui.horizontal(|ui| {
+                    if ui.button("Keep changes").clicked() {
+                        timer.keep.store(true, Ordering::Relaxed);
+                    }
+                    if ui.button("Revert now").clicked() {
+                        timer.revert_now.store(true, Ordering::Relaxed);
+                    }
+                });
+            }
+
+            if !self.status.is_empty() {
+                ui.label(&self.status);
+            }
+            if !self.results.is_empty() {
+                egui::CollapsingHeader::new(format!("Applied ({})", self.results.len())).show(ui, |ui| {
+                    for result in self.results.iter() {
+                        ui.label(result);
+                    }
+                });
+            }
+        });
+
+        if self.confirming {
+            let adapters = std::iter::once(self.profile.target_adapter())
+                .chain(self.also_apply_to.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(", ");
+            egui::Window::new("Confirm Apply")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(format!("Apply {} to {}?", self.profile.name, adapters));
+                    if self.sections != ApplySections::all() {
+                        let sections: Vec<&str> = ApplySections::LABELS.iter()
+                            .filter(|(section, _)| self.sections.contains(*section))
+                            .map(|(_, label)| *label)
+                            .collect();
+                        ui.label(format!("Only {} will be changed", sections.join(", ")));
+                    }
+                    ui.label(RichText::new("⚠ Reconfiguring the interface may disconnect you, including remote sessions over it")
+                        .color(ui.visuals().warn_fg_color));
+                    if let Some(warning) = &self.disconnect_warning {
+                        ui.label(RichText::new(warning).color(ui.visuals().warn_fg_color).strong());
+                    }
+                    if self.also_apply_to.is_empty() {
+                        ui.checkbox(&mut self.auto_revert, format!("Revert in {} s unless I keep the changes", REVERT_AFTER.as_secs()));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Apply").clicked() {
+                            self.confirming = false;
+                            self.start_apply();
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.confirming = false;
+                        }
+                    });
+                });
+        }
+
+        if let Some(conflict) = self.conflict.clone() {
+            egui::Window::new("Address Conflict")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(RichText::new(format!("⚠ {}", conflict)).color(ui.visuals().warn_fg_color).strong());
+                    ui.label("Applying anyway leaves two hosts with the same address, and both may lose connectivity.");
+                    ui.horizontal(|ui| {
+                        if ui.button("Apply Anyway").clicked() {
+                            self.conflict = None;
+                            self.start_apply();
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.conflict = None;
+                        }
+                    });
+                });
+        }
+
+        if self.apply.is_some() || self.revert.is_some() || self.conflict_check.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if let (LoaderAction::Close, Some(task)) = (&action, &self.apply) {
+            // Don't leave the worker mutating the network after the window is gone
+            task.cancel.store(true, Ordering::Relaxed);
+        }
+        action
+    }
+
+    /// The markdown change report for what Apply would change with the sections checked now.
+    pub fn report(&self) -> String {
+        network::generate_change_report(&self.current, &self.profile, self.sections)
+    }
+
+    /// Whether the apply is reverted unless kept. Only the profile's own interface is captured
+    /// beforehand, so applying to other interfaces as well rules it out.
+    fn can_revert(&self) -> bool {
+        self.auto_revert && self.also_apply_to.is_empty()
+    }
+
+    /// Applies as the Apply button does, for applies started outside the window like the profile
+    /// list's Load Profile. A profile with problems isn't applied, the window shows them instead.
+    pub fn apply(&mut self) {
+        if !self.problems.is_empty() {
+            return;
+        }
+        match self.needs_conflict_check() {
+            true => self.start_conflict_check(),
+            false => self.request_apply(),
+        }
+    }
+
+    /// Asks for confirmation before applying when the settings want it, otherwise applies.
+    fn request_apply(&mut self) {
+        match self.confirm_apply {
+            true => self.confirming = true,
+            false => self.start_apply(),
+        }
+    }
+
+    /// Whether Apply should first probe for the profile's address being in use: only when it
+    /// sets a static address the adapter doesn't already have, as the adapter would answer itself.
+    fn needs_conflict_check(&self) -> bool {
+        let profile = &self.profile;
+        let already_assigned = self.addresses.iter().any(|addr| matches!(addr, Addr::V4(addr) if addr.ip.to_string() == profile.ip));
+        // The probe runs outside the namespace, where it can't see the adapter's network
+        let in_netns = profile.netns.as_deref().is_some_and(|ns| !ns.is_empty());
+        self.check_conflicts && self.sections.contains(ApplySections::ADDRESS) && profile.ipv4_policy == network::FamilyPolicy::Configure
+            && !profile.ip.is_empty() && !already_assigned && !in_netns
+    }
+
+    fn start_conflict_check(&mut self) {
+        let (ip, adapter) = (self.profile.ip.clone(), self.profile.target_adapter().to_string());
+        self.conflict_check = Some(std::thread::spawn(move || network::check_address_conflict(&ip, &adapter)));
+    }
+
+    fn preview_apply(&mut self) {
+        let (commands, outcome) = self.profile.dry_run_sections(self.sections);
+        self.status = match outcome {
+            ApplyOutcome::Failed(e) | ApplyOutcome::RolledBack(e) => format!("Preview: apply would stop with {}", e),
+            _ => format!("Preview: {} commands would run, nothing was changed", commands.len()),
+        };
+        self.preview = Some(commands);
+    }
+
+    /// Re-enumerates interfaces and checks the ones about to be configured are still there, as
+    /// USB NICs and VPN taps come and go while the window is open. Refreshes the interface list
+    /// either way, and records why when one has gone.
+    fn adapters_present(&mut self, adapters: &[String]) -> bool {
+        self.refresh_interfaces();
+        self.missing_adapter = None;
+        // Interfaces inside a namespace aren't visible from here, so leave those to the apply
+        if self.profile.netns.as_deref().is_some_and(|ns| !ns.is_empty()) || self.enumeration_error.is_some() {
+            return true;
+        }
+        if self.adapters.is_empty() {
+            self.missing_adapter = Some("No network interfaces found, plug one in and try again".to_string());
+            return false;
+        }
+        let missing: Vec<&str> = adapters.iter()
+            .filter(|adapter| !self.adapters.contains(adapter))
+            .map(String::as_str)
+            .collect();
+        if missing.is_empty() {
+            return true;
+        }
+        self.missing_adapter = Some(match missing.as_slice() {
+            [adapter] => format!("{} is no longer present, select another interface", adapter),
+            _ => format!("{} are no longer present, select other interfaces", missing.join(", ")),
+        });
+        self.also_apply_to.retain(|adapter| self.adapters.contains(adapter));
+        false
+    }
+
+    fn start_reset(&mut self) {
+        let selected = self.profile.adapter.clone();
+        if !self.adapters_present(std::slice::from_ref(&selected)) {
+            return;
+        }
+        let adapter = self.profile.target_adapter().to_string();
+        self.status = "Resetting to DHCP...".to_string();
+        // A reset is a single step, so it has no progress to report
+        let (_, progress) = mpsc::channel();
+        self.apply = Some(ApplyTask {
+            handle: std::thread::spawn(move || {
+                let outcome = network::reset_adapter_to_dhcp(&adapter);
+                vec![(adapter, outcome)]
+            }),
+            cancel: Arc::new(AtomicBool::new(false)),
+            reset: true,
+            progress,
+            steps_done: 0,
+            steps_total: 0,
+        });
+    }
+
+    fn start_apply(&mut self) {
+        let profile = self.profile.clone();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let token = cancel.clone();
+        let transactional = self.transactional;
+        let sections = self.sections;
+        // The profile's own adapter first, as only it takes the address
+        let adapters: Vec<String> = std::iter::once(self.profile.adapter.clone())
+            .chain(self.also_apply_to.iter().filter(|adapter| **adapter != self.profile.adapter).cloned())
+            .collect();
+        if !self.adapters_present(&adapters) {
+            return;
+        }
+        self.status = "Applying...".to_string();
+        // Namespaced interfaces can't be read from here, so there's nothing to snapshot
+        if self.profile.netns.as_deref().is_none_or(str::is_empty) {
+            match network::capture_current_profile(self.profile.target_adapter()) {
+                Ok(previous) => {
+                    self.revert_to = Some(previous.clone());
+                    self.previous = Some(previous);
+                }
+                Err(e) => log::error!("Error capturing the current configuration: {}", e),
+            }
+        }
+        let steps_total = profile.planned_steps(&adapters, sections);
+        let (sender, progress) = mpsc::channel();
+        self.apply = Some(ApplyTask {
+            handle: std::thread::spawn(move || {
+                profile.load_multi_progress(&adapters, &token, transactional, sections, |adapter, step| {
+                    // The window may have closed, there's then no one to tell
+                    let _ = sender.send((adapter.to_string(), step));
+                })
+            }),
+            cancel,
+            reset: false,
+            progress,
+            steps_done: 0,
+            steps_total,
+        });
+    }
+}
+
+/// Interface name with its link state, dimmed when the interface has no link.
+fn adapter_label(adapter: &str, link: Option<&network::LinkStatus>) -> RichText {
+    match link {
+        Some(link) if link.up => RichText::new(format!("{} ({})", adapter, link.state)),
+        Some(link) => RichText::new(format!("{} ({})", adapter, if link.state.is_empty() { "down" } else { &link.state })).weak(),
+        None => RichText::new(adapter),
+    }
+}
+
+/// Status line for how an apply finished.
+fn outcome_message(outcome: &ApplyOutcome, reset: bool) -> (String, NotificationKind) {
+    match outcome {
+        ApplyOutcome::Applied if reset => ("Reset to DHCP".to_string(), NotificationKind::Success),
+        ApplyOutcome::Applied => ("Profile applied successfully".to_string(), NotificationKind::Success),
+        ApplyOutcome::Cancelled => ("Apply cancelled and rolled back".to_string(), NotificationKind::Success),
+        ApplyOutcome::Failed(e) => (failure_message(e), NotificationKind::Failure),
+        ApplyOutcome::RolledBack(e) => (format!("Profile applied and rolled back. {}", failure_message(e)), NotificationKind::Failure),
+    }
+}
+
+/// Explains a failed apply in terms of what the user can do about it.
+fn failure_message(error: &NetProfilerError) -> String {
+    match error {
+        NetProfilerError::CommandNotFound(program) => format!("{} isn't installed or isn't on the PATH, run Tools > Run Diagnostics", program),
+        NetProfilerError::Elevation(_) => "Applying profiles needs administrator rights, relaunch elevated and try again".to_string(),
+        NetProfilerError::InvalidIp(_) | NetProfilerError::InvalidSubnet(_) | NetProfilerError::InvalidDns(_) => format!("Fix the profile and try again: {}", error),
+        _ => format!("Error applying profile: {}", error),
+    }
+}
+
+/// Lists addresses grouped by family, separating IPv6 link-local clutter from global addresses.
+fn show_addresses(ui: &mut egui::Ui, addresses: &[Addr]) {
+    let mut v4 = Vec::new();
+    let mut link_local = Vec::new();
+    let mut global = Vec::new();
+    for addr in addresses {
+        match addr {
+            Addr::V4(addr) => {
+                let mask = addr.netmask.map(|mask| format!(" / {}", mask)).unwrap_or_default();
+                v4.push(format!("{}{}", addr.ip, mask));
+            }
+            Addr::V6(addr) if addr.ip.segments()[0] & 0xffc0 == 0xfe80 => link_local.push(addr.ip.to_string()),
+            Addr::V6(addr) => global.push(addr.ip.to_string()),
+        }
+    }
+
+    if v4.is_empty() && link_local.is_empty() && global.is_empty() {
+        ui.label("No addresses assigned");
+    }
+    for (title, group) in [("IPv4", v4), ("IPv6 (global)", global), ("IPv6 (link-local)", link_local)] {
+        if group.is_empty() {
+            continue;
+        }
+        ui.label(RichText::new(title).strong());
+        for address in group {
+            ui.label(RichText::new(address).monospace());
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::{fs::{File, OpenOptions}, io::Write, path::Path, sync::Mutex};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Writes log records to the log file, and to the console as well when debugging with --console.
+struct Logger {
+    file: Option<Mutex<File>>,
+    console: bool,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LevelFilter::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{}] {:<5} {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), record.level(), record.args());
+        if let Some(file) = &self.file {
+            let _ = writeln!(file.lock().unwrap_or_else(|e| e.into_inner()), "{}", line);
+        }
+        if self.console {
+            eprintln!("{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+    }
+}
+
+/// Sends the `log` output to the file at `path`, and to a console too when `console` is set.
+/// On Windows release builds have no console, so one is attached or opened for it.
+pub fn init(path: &Path, console: bool) {
+    if console {
+        attach_console();
+    }
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let file = match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => Some(Mutex::new(file)),
+        Err(e) => {
+            eprintln!("Error opening log {}: {}", path.display(), e);
+            None
+        }
+    };
+    if log::set_boxed_logger(Box::new(Logger { file, console })).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+/// Gives a Windows release build, which starts without a console, the terminal it was run from
+/// or a new console window, for output people or scripts read.
+#[cfg(target_os = "windows")]
+pub(crate) fn attach_console() {
+    use windows_sys::Win32::System::Console::{AllocConsole, AttachConsole, ATTACH_PARENT_PROCESS};
+    // Use the terminal the app was started from, or open a console window when there isn't one
+    unsafe {
+        if AttachConsole(ATTACH_PARENT_PROCESS) == 0 {
+            AllocConsole();
+        }
+    }
+}
+
+/// Other platforms always have their stderr, so there's nothing to attach.
+#[cfg(not(target_os = "windows"))]
+pub(crate) fn attach_console() {}
@@ -7,11 +7,28 @@ use network_interface::{NetworkInterface, NetworkInterfaceConfig};
 
 
 mod app;
+mod audit;
+mod cli;
+mod diagnostics;
+mod elevation;
+mod error;
+mod file_operations;
+mod import_preview;
+mod link_watcher;
+mod loader;
+mod logging;
 mod network;
+mod notification;
+mod palette;
+mod settings;
 
 fn main()  -> eframe::Result {
-    let adapters: Vec<String> = NetworkInterface::show().unwrap().iter().map(|adapter| adapter.name.clone()).collect();
-    println!("{:?}", adapters);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    logging::init(&audit::log_path(), args.iter().any(|arg| arg == "--console"));
+
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -29,7 +46,7 @@ fn main()  -> eframe::Result {
         native_options,
         Box::new(|cc| {
             let mut app = app::NetProfiler::new(cc);
-            app.adapters = adapters;
+            app.refresh_adapters();
             Ok(Box::new(app))
         })
     )
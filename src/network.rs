@@ -1,84 +1,4274 @@
-use std::{path::PathBuf, process::Command, net::Ipv4Addr};
+use std::{cell::Cell, collections::{BTreeMap, BTreeSet, HashMap}, fmt::Write, io::ErrorKind, path::PathBuf, process::Command, net::{IpAddr, Ipv4Addr}, sync::{atomic::{AtomicBool, Ordering}, Mutex, OnceLock, PoisonError, RwLock}, time::{Duration, Instant}};
+
+use network_interface::{Addr, NetworkInterface, NetworkInterfaceConfig};
+
+use crate::audit::{self, AuditEntry};
+use crate::error::{self, NetProfilerError};
 
 #[derive(serde::Deserialize, serde::Serialize)]
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(default)]
 pub struct NetworkProfile {
     pub name: String,
+    /// Free text notes, e.g. which site the profile is for and who to contact
+    pub description: String,
+    /// Categories like a site or purpose, which the profile list can be grouped and filtered by
+    pub tags: Vec<String>,
     pub adapter: String,
+    /// MAC of the NIC this profile belongs to, which stays stable when interface names change
+    pub mac_binding: Option<String>,
+    /// Hardware address to give the adapter when applying, in place of its own
+    pub mac_address: Option<String>,
     pub ip: String,
     pub subnet: String,
     pub gateway: String,
+    /// Routes to specific networks, added alongside the default route
+    pub routes: Vec<StaticRoute>,
+    /// What to do with each address family, so dual-stack results don't depend on the OS
+    pub ipv4_policy: FamilyPolicy,
+    pub ipv6_policy: FamilyPolicy,
     pub dns_provider: DNSProvider,
     pub primary_dns: String,
     pub secondary_dns: String,
+    /// Further custom servers, used in order after the secondary
+    pub extra_dns: Vec<String>,
+    /// resolv.conf style content used by `DNSProvider::Raw`
+    pub raw_dns: String,
+    /// Whether queries to the DNS servers are encrypted, where the OS supports it
+    pub dns_encryption: DnsEncryption,
+    pub bridge: Option<BridgeConfig>,
+    /// Preference of this interface's routes when several interfaces are up, lower wins
+    pub route_metric: Option<u32>,
+    /// Largest packet the interface sends, e.g. 9000 for jumbo frames
+    pub mtu: Option<u32>,
+    /// Linux network namespace the adapter lives in, for container and test setups
+    pub netns: Option<String>,
+    /// Computer name to give the machine, for site profiles that include one
+    pub hostname: Option<String>,
+    /// System wide web proxy; a profile without one clears any proxy set before
+    pub proxy: Option<ProxyConfig>,
+    /// Also save the configuration so it survives a reboot, see `persist_config`. Windows always
+    /// does, as netsh changes are persistent.
+    pub persist: bool,
+    /// Informational notes such as switch port or patch panel location
+    pub metadata: BTreeMap<String, String>,
+    /// Apply the profile whenever its adapter appears or its link comes up, while the app is running
+    pub auto_apply: bool,
 }
 
 impl NetworkProfile {
-    pub fn load(&self) {
+    /// The interface the addressing is applied to, which is the bridge for bridged profiles on Linux.
+    pub fn target_adapter(&self) -> &str {
+        match &self.bridge {
+            Some(bridge) if cfg!(target_os = "linux") => &bridge.name,
+            _ => &self.adapter,
+        }
+    }
+
+    /// The DNS servers this profile sets, resolving the built-in providers to their addresses.
+    pub fn dns_servers(&self) -> Vec<String> {
+        match self.dns_provider {
+            DNSProvider::Custom => [&self.primary_dns, &self.secondary_dns].into_iter()
+                .chain(self.extra_dns.iter())
+                .cloned()
+                .collect(),
+            DNSProvider::Raw => resolv_conf_entries(&self.raw_dns, "nameserver").into_iter().map(String::from).collect(),
+            ref provider => match provider_dns(provider) {
+                Some(servers) => servers.to_vec(),
+                None => vec![String::new(), String::new()],
+            },
+        }
+    }
+
+    /// One line overview such as "192.168.1.10/24 · gw 192.168.1.1 · Quad9".
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.ip.is_empty() {
+            parts.push(match subnet_prefix(&self.subnet) {
+                Some(prefix) => format!("{}/{}", self.ip, prefix),
+                None => self.ip.clone(),
+            });
+        }
+        if !self.gateway.is_empty() {
+            parts.push(format!("gw {}", self.gateway));
+        }
+        match self.dns_provider {
+            DNSProvider::None => {}
+            DNSProvider::Custom | DNSProvider::Raw => {
+                if let Some(server) = self.dns_servers().first().filter(|server| !server.is_empty()) {
+                    parts.push(format!("DNS {}", server));
+                }
+            }
+            ref provider => parts.push(format!("{:?}", provider)),
+        }
+        if let Some(bridge) = &self.bridge {
+            parts.push(format!("bridge {}", bridge.name));
+        }
+        parts.join(" · ")
+    }
+
+    /// What changes going from this profile to `other`, e.g. from the live configuration to a
+    /// profile about to be applied. Settings `other` leaves alone aren't compared.
+    pub fn diff(&self, other: &NetworkProfile) -> ProfileDiff {
+        self.diff_sections(other, ApplySections::all())
+    }
+
+    /// Like `diff`, for applying only some sections of `other`.
+    pub fn diff_sections(&self, other: &NetworkProfile, sections: ApplySections) -> ProfileDiff {
+        let mut diff = ProfileDiff::default();
+        if other.ipv4_policy == FamilyPolicy::Configure {
+            let address = |profile: &NetworkProfile| match subnet_prefix(&profile.subnet) {
+                Some(prefix) if !profile.ip.is_empty() => format!("{}/{}", profile.ip, prefix),
+                _ => profile.ip.clone(),
+            };
+            if sections.contains(ApplySections::ADDRESS) {
+                diff.compare("IP", address(self), address(other));
+            }
+            if sections.contains(ApplySections::GATEWAY) {
+                diff.compare("Gateway", self.gateway.clone(), other.gateway.clone());
+            }
+        }
+        if other.dns_provider != DNSProvider::None && sections.contains(ApplySections::DNS) {
+            let from: Vec<String> = self.dns_servers().into_iter().filter(|server| !server.is_empty()).collect();
+            let to: Vec<String> = other.dns_servers().into_iter().filter(|server| !server.is_empty()).collect();
+            for server in from.iter().filter(|server| !to.contains(server)) {
+                diff.entries.push(DiffEntry::Removed { setting: "DNS", value: server.clone() });
+            }
+            for server in to.iter().filter(|server| !from.contains(server)) {
+                diff.entries.push(DiffEntry::Added { setting: "DNS", value: server.clone() });
+            }
+        }
+        diff
+    }
+
+    /// Whether the name or any of the addresses contain the query, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return true;
+        }
+        // "tag:" narrows to one tag rather than anything containing the text
+        if let Some(tag) = query.strip_prefix("tag:") {
+            return self.tags.iter().any(|other| other.to_lowercase() == tag.trim());
+        }
+        let routes = self.routes.iter().map(StaticRoute::to_string);
+        [self.name.clone(), self.description.clone(), self.ip.clone(), self.gateway.clone()]
+            .into_iter()
+            .chain(self.tags.iter().cloned())
+            .chain(self.dns_servers())
+            .chain(routes)
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    /// Adds the comma separated tags in `text`, skipping blanks and ones the profile already has
+    /// in any case.
+    pub fn add_tags(&mut self, text: &str) {
+        for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+            if !self.tags.iter().any(|other| other.eq_ignore_ascii_case(tag)) {
+                self.tags.push(tag.to_string());
+            }
+        }
+    }
+
+    /// Search domains to set alongside the DNS servers, which only raw DNS can express.
+    pub fn dns_search_domains(&self) -> Vec<&str> {
+        match self.dns_provider {
+            DNSProvider::Raw => resolv_conf_entries(&self.raw_dns, "search"),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The interface currently carrying the bound MAC, if the profile is bound and the NIC is present.
+    pub fn bound_adapter(&self) -> Option<String> {
+        let binding = self.mac_binding.as_deref()?;
+        // Once applied, the NIC answers to the MAC the profile gave it
+        adapter_for_mac(binding).or_else(|| self.mac_address.as_deref().and_then(adapter_for_mac))
+    }
+
+    /// Checks the fields the UI validates, for profiles edited or imported outside of it.
+    pub fn validate(&self) -> error::Result<()> {
+        match self.validation_errors().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Every problem with the profile, so they can all be fixed in one go.
+    pub fn validation_errors(&self) -> Vec<NetProfilerError> {
+        let is_address = |value: &str| value.is_empty() || value.parse::<Ipv4Addr>().is_ok();
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push(NetProfilerError::Profile("Profile name is empty".to_string()));
+        }
+        // A Linux bridge is applied to by its own name, which needs checking too
+        let bridge = Some(self.target_adapter()).filter(|bridge| *bridge != self.adapter);
+        for adapter in [self.adapter.as_str()].into_iter().chain(bridge).filter(|adapter| !adapter.is_empty()) {
+            if let Err(e) = check_adapter_name(adapter) {
+                errors.push(e);
+            }
+        }
+        if !is_address(&self.ip) {
+            errors.push(NetProfilerError::InvalidIp(self.ip.clone()));
+        }
+        if !self.subnet.is_empty() && subnet_prefix(&self.subnet).is_none() {
+            errors.push(NetProfilerError::InvalidSubnet(self.subnet.clone()));
+        }
+        if let Some(mac) = self.mac_binding.as_deref().filter(|mac| !is_mac_address(mac)) {
+            errors.push(NetProfilerError::Profile(format!("Invalid MAC address: {}", mac)));
+        }
+        if let Some(Err(e)) = self.mac_address.as_deref().map(normalize_mac) {
+            errors.push(NetProfilerError::Profile(e));
+        }
+        if !is_address(&self.gateway) {
+            errors.push(NetProfilerError::InvalidIp(self.gateway.clone()));
+        }
+        for route in self.routes.iter() {
+            if route.destination.parse::<Ipv4Addr>().is_err() {
+                errors.push(NetProfilerError::InvalidIp(route.destination.clone()));
+            }
+            if route.prefix > 32 {
+                errors.push(NetProfilerError::InvalidSubnet(format!("/{}", route.prefix)));
+            } else if let Ok(destination) = route.destination.parse::<Ipv4Addr>() {
+                // The OS tools refuse a destination with host bits set, e.g. 10.1.2.3/8
+                let network = u32::from(destination) & u32::MAX.checked_shl(32 - route.prefix as u32).unwrap_or(0);
+                if network != u32::from(destination) {
+                    errors.push(NetProfilerError::Profile(format!(
+                        "Route destination {}/{} isn't a network address, did you mean {}/{}?",
+                        route.destination, route.prefix, Ipv4Addr::from(network), route.prefix,
+                    )));
+                }
+            }
+            if route.via.parse::<Ipv4Addr>().is_err() {
+                errors.push(NetProfilerError::InvalidIp(route.via.clone()));
+            }
+            errors.extend(route.metric.and_then(route_metric_error));
+        }
+        errors.extend(self.route_metric.and_then(route_metric_error));
+        if let Some(Err(e)) = self.hostname.as_deref().filter(|name| !name.is_empty()).map(check_hostname) {
+            errors.push(e);
+        }
+        if let Some(Err(e)) = self.proxy.as_ref().map(ProxyConfig::validate) {
+            errors.push(e);
+        }
+        if self.mtu.is_some_and(|mtu| !MTU_RANGE.contains(&mtu)) {
+            errors.push(NetProfilerError::Profile(format!("MTU must be between {} and {}", MTU_RANGE.start(), MTU_RANGE.end())));
+        }
+        if let DNSProvider::Custom = self.dns_provider {
+            let servers = [&self.primary_dns, &self.secondary_dns].into_iter().chain(self.extra_dns.iter());
+            errors.extend(servers.filter(|dns| !is_address(dns)).map(|dns| NetProfilerError::InvalidDns(dns.to_string())));
+        }
+        if let DNSProvider::Raw = self.dns_provider {
+            if resolv_conf_entries(&self.raw_dns, "nameserver").is_empty() {
+                errors.push(NetProfilerError::Profile("Raw DNS needs at least one nameserver line".to_string()));
+            }
+        }
+        if self.dns_provider != DNSProvider::None {
+            if let Err(e) = check_dns_encryption(self.dns_encryption, &self.dns_provider) {
+                errors.push(e);
+            }
+        }
+        errors
+    }
+
+    /// The commands applying this profile would run, recorded without running them. The outcome
+    /// is what the apply would end with, e.g. the error it would stop at after those commands.
+    pub fn dry_run(&self) -> (Vec<String>, ApplyOutcome) {
+        self.dry_run_sections(ApplySections::all())
+    }
+
+    /// Like `dry_run`, for applying only some sections of the profile.
+    pub fn dry_run_sections(&self, sections: ApplySections) -> (Vec<String>, ApplyOutcome) {
+        let mut backend = DryRunBackend::default();
+        let outcome = self.apply(&mut backend, &AtomicBool::new(false), false, sections, &mut |_| {});
+        (backend.commands, outcome)
+    }
+
+    /// Applies the profile to this machine and records the result in the audit log.
+    pub fn load_cancellable(&self, cancel: &AtomicBool) -> ApplyOutcome {
+        self.load_system(cancel, false, ApplySections::all(), &mut |_| {})
+    }
+
+    /// Puts this configuration, captured before `applied` was applied, back on the system: the
+    /// routes `applied` added are removed and this profile's adapter settings applied. The proxy
+    /// and computer name aren't captured, so they stay as `applied` left them.
+    pub fn revert_from(&self, applied: &NetworkProfile) -> ApplyOutcome {
+        let mut backend = SystemBackend;
+        for route in applied.routes.iter() {
+            if let Err(e) = delete_route(&mut backend, applied.target_adapter(), route) {
+                log::warn!("Error removing route to {}: {}", route, e);
+            }
+        }
+        self.load_system(&AtomicBool::new(false), false, ApplySections::ADAPTER, &mut |_| {})
+    }
+
+    /// Like `load_cancellable`, but a failure partway through puts the adapter back how it was.
+    pub fn load_transactional(&self, cancel: &AtomicBool) -> ApplyOutcome {
+        self.load_system(cancel, true, ApplySections::all(), &mut |_| {})
+    }
+
+    fn load_system(&self, cancel: &AtomicBool, transactional: bool, sections: ApplySections, progress: &mut dyn FnMut(ApplyStep)) -> ApplyOutcome {
+        // Two applies interleaving commands on one adapter would leave it in neither profile's state
+        let Some(_lock) = ApplyLock::acquire(self.target_adapter()) else {
+            return ApplyOutcome::Failed(NetProfilerError::Profile(format!("A profile is already being applied to {}", self.target_adapter())));
+        };
+        log::info!("Applying profile {} to {}", self.name, self.target_adapter());
+        let renaming = self.renames_computer(&SystemBackend, sections);
+        let outcome = self.apply(&mut SystemBackend, cancel, transactional, sections, progress);
+        if let (ApplyOutcome::Applied, Some(name)) = (&outcome, renaming) {
+            if cfg!(target_os = "windows") {
+                restart_needed(format!("Computer name changed to {}", name));
+            }
+        }
+        match &outcome {
+            ApplyOutcome::Applied => log::info!("Applied profile {}", self.name),
+            ApplyOutcome::Cancelled => log::warn!("Cancelled applying profile {}", self.name),
+            ApplyOutcome::Failed(e) => log::error!("Error applying profile {}: {}", self.name, e),
+            ApplyOutcome::RolledBack(e) => log::error!("Rolled back profile {}: {}", self.name, e),
+        }
+        audit::record_apply(AuditEntry::new(self, &outcome));
+        outcome
+    }
+
+    /// Applies `sections` of the profile to several adapters in turn, returning how each went. Only
+    /// the first adapter is given the IPv4 address and MAC address, see `for_additional_adapter`.
+    pub fn load_multi(&self, adapters: &[String], cancel: &AtomicBool, transactional: bool, sections: ApplySections) -> Vec<(String, ApplyOutcome)> {
+        self.load_multi_progress(adapters, cancel, transactional, sections, |_, _| {})
+    }
+
+    /// Like `load_multi`, calling `progress` with the adapter and step as each step starts.
+    pub fn load_multi_progress(
+        &self,
+        adapters: &[String],
+        cancel: &AtomicBool,
+        transactional: bool,
+        sections: ApplySections,
+        mut progress: impl FnMut(&str, ApplyStep),
+    ) -> Vec<(String, ApplyOutcome)> {
+        self.per_adapter(adapters, sections)
+            .map(|(profile, sections)| {
+                let outcome = profile.load_system(cancel, transactional, sections, &mut |step| progress(&profile.adapter, step));
+                (profile.adapter, outcome)
+            })
+            .collect()
+    }
+
+    /// Like `load_multi`, running the commands through `backend`.
+    pub fn load_multi_with(&self, backend: &mut dyn Backend, adapters: &[String], cancel: &AtomicBool, sections: ApplySections) -> Vec<(String, ApplyOutcome)> {
+        self.per_adapter(adapters, sections)
+            .map(|(profile, sections)| {
+                let outcome = profile.apply(backend, cancel, false, sections, &mut |_| {});
+                (profile.adapter, outcome)
+            })
+            .collect()
+    }
+
+    /// How many steps applying `sections` to `adapters` takes, to measure `load_multi_progress` against.
+    pub fn planned_steps(&self, adapters: &[String], sections: ApplySections) -> usize {
+        self.per_adapter(adapters, sections)
+            .map(|(profile, sections)| profile.apply_steps(&SystemBackend, sections).len())
+            .sum()
+    }
+
+    /// The steps applying `sections` reports, in order, ending with `Done`.
+    fn apply_steps(&self, backend: &dyn Backend, sections: ApplySections) -> Vec<ApplyStep> {
+        let sections = self.applicable_sections(sections);
+        let mut steps = Vec::new();
+        if self.bridge.is_some() && sections.contains(ApplySections::ADDRESS) && cfg!(target_os = "linux") {
+            steps.push(ApplyStep::CreatingBridge);
+        }
+        if self.mac_address.as_deref().is_some_and(|mac| !mac.is_empty()) && sections.contains(ApplySections::MAC) {
+            steps.push(ApplyStep::SettingMac);
+        }
+        if self.mtu.is_some() && sections.contains(ApplySections::MTU) {
+            steps.push(ApplyStep::SettingMtu);
+        }
+        if self.gateway_only(sections) {
+            steps.push(ApplyStep::SettingGateway);
+        } else if sections.contains(ApplySections::ADDRESS) {
+            steps.push(ApplyStep::SettingAddress);
+        }
+        if sections.contains(ApplySections::ROUTES) {
+            if self.route_metric.is_some() {
+                steps.push(ApplyStep::SettingRouteMetric);
+            }
+            steps.extend((1..=self.routes.len()).map(ApplyStep::AddingRoute));
+        }
+        if !matches!(self.dns_provider, DNSProvider::None) && sections.contains(ApplySections::DNS) {
+            steps.push(ApplyStep::SettingDns);
+        }
+        if self.persist {
+            steps.push(ApplyStep::SavingConfig);
+        }
+        if sections.contains(ApplySections::PROXY) {
+            steps.push(ApplyStep::SettingProxy);
+        }
+        if self.renames_computer(backend, sections).is_some() {
+            steps.push(ApplyStep::SettingHostname);
+        }
+        steps.push(ApplyStep::Done);
+        steps
+    }
+
+    /// The part of `sections` this profile can apply. A namespace has its own interfaces but
+    /// shares the host's files and computer name, so the proxy and hostname are left alone in one.
+    fn applicable_sections(&self, sections: ApplySections) -> ApplySections {
+        match cfg!(target_os = "linux") && self.netns.as_deref().is_some_and(|ns| !ns.is_empty()) {
+            true => sections - ApplySections::PROXY - ApplySections::HOSTNAME,
+            false => sections,
+        }
+    }
+
+    /// Whether only the default route changes, as the address section is left out but the gateway isn't.
+    fn gateway_only(&self, sections: ApplySections) -> bool {
+        !sections.contains(ApplySections::ADDRESS) && sections.contains(ApplySections::GATEWAY)
+            && self.ipv4_policy == FamilyPolicy::Configure && !self.gateway.is_empty()
+    }
+
+    /// The profile and sections to apply to each adapter. The proxy is system wide, so it's only
+    /// set along with the first.
+    fn per_adapter<'a>(&'a self, adapters: &'a [String], sections: ApplySections) -> impl Iterator<Item = (NetworkProfile, ApplySections)> + 'a {
+        adapters.iter().enumerate().map(move |(index, adapter)| match index {
+            0 => (NetworkProfile { adapter: adapter.clone(), ..self.clone() }, sections),
+            _ => (self.for_additional_adapter(adapter), sections - ApplySections::PROXY),
+        })
+    }
+
+    /// The profile as applied to an adapter besides its own: DNS, MTU and the rest, but not the
+    /// static address, gateway or MAC address, which would conflict if two interfaces had them.
+    /// Routes go through gateways on the first adapter's network, which the others usually aren't
+    /// on, so they're left out too. The computer is renamed with the first adapter, so not again here.
+    pub fn for_additional_adapter(&self, adapter: &str) -> NetworkProfile {
+        NetworkProfile {
+            adapter: adapter.to_string(),
+            ipv4_policy: match self.ipv4_policy {
+                FamilyPolicy::Configure => FamilyPolicy::LeaveAlone,
+                policy => policy,
+            },
+            mac_address: None,
+            hostname: None,
+            routes: Vec::new(),
+            // The bridge is the first adapter's, applying to it again would re-address it
+            bridge: None,
+            ..self.clone()
+        }
+    }
+
+    /// Applies the profile one step at a time, checking `cancel` between steps.
+    /// When cancelled, the steps already taken are undone before returning.
+    pub fn load_with(&self, backend: &mut dyn Backend, cancel: &AtomicBool) -> ApplyOutcome {
+        self.apply(backend, cancel, false, ApplySections::all(), &mut |_| {})
+    }
+
+    /// Like `load_with`, calling `progress` as each step starts.
+    pub fn load_with_progress(&self, backend: &mut dyn Backend, cancel: &AtomicBool, progress: &mut dyn FnMut(ApplyStep)) -> ApplyOutcome {
+        self.apply(backend, cancel, false, ApplySections::all(), progress)
+    }
+
+    /// Like `load_with`, but only applies `sections` of the profile. The sections left out are
+    /// skipped entirely, the adapter keeps whatever it had for them.
+    pub fn load_sections_with(&self, backend: &mut dyn Backend, cancel: &AtomicBool, sections: ApplySections) -> ApplyOutcome {
+        self.apply(backend, cancel, false, sections, &mut |_| {})
+    }
+
+    /// Like `load_with`, but a step failing also undoes the steps before it, restoring the
+    /// adapter's previous address, gateway, DNS, MAC address and MTU instead of leaving it half configured.
+    pub fn load_transactional_with(&self, backend: &mut dyn Backend, cancel: &AtomicBool) -> ApplyOutcome {
+        self.apply(backend, cancel, true, ApplySections::all(), &mut |_| {})
+    }
+
+    /// The new computer name when applying `sections` changes it.
+    fn renames_computer(&self, backend: &dyn Backend, sections: ApplySections) -> Option<&str> {
+        self.hostname.as_deref()
+            .filter(|name| !name.is_empty() && sections.contains(ApplySections::HOSTNAME))
+            .filter(|name| !backend.hostname().eq_ignore_ascii_case(name))
+    }
+
+    fn apply(&self, backend: &mut dyn Backend, cancel: &AtomicBool, transactional: bool, sections: ApplySections, progress: &mut dyn FnMut(ApplyStep)) -> ApplyOutcome {
         // Check if adapter is blank
         if self.adapter.is_empty() {
-            return;
+            return ApplyOutcome::Failed(NetProfilerError::Profile("No adapter selected".to_string()));
+        }
+        // Refuse up front rather than leave the adapter half configured when a bad value is reached
+        if let Err(e) = self.validate() {
+            return ApplyOutcome::Failed(e);
+        }
+        let sections = self.applicable_sections(sections);
+
+        let mut namespaced;
+        let backend: &mut dyn Backend = match self.netns.as_deref().filter(|ns| !ns.is_empty()) {
+            Some(ns) if cfg!(target_os = "linux") => {
+                if !backend.netns_exists(ns) {
+                    return ApplyOutcome::Failed(NetProfilerError::Profile(format!("Network namespace {} doesn't exist", ns)));
+                }
+                namespaced = NamespacedBackend { inner: backend, netns: ns };
+                &mut namespaced
+            }
+            Some(ns) => {
+                log::warn!("Network namespaces are only supported on Linux, ignoring {}", ns);
+                backend
+            }
+            None => backend,
+        };
+
+        let adapter = self.target_adapter();
+        let previous = backend.current_profile(adapter);
+        let mut created_bridge = None;
+        match &self.bridge {
+            // The bridge carries the addressing, so it's left alone along with it
+            Some(_) if !sections.contains(ApplySections::ADDRESS) => {}
+            Some(bridge) if cfg!(target_os = "linux") => {
+                if !backend.interface_exists(&bridge.name) {
+                    created_bridge = Some(bridge.name.as_str());
+                }
+                progress(ApplyStep::CreatingBridge);
+                if let Err(e) = create_bridge(backend, bridge) {
+                    if !transactional {
+                        return ApplyOutcome::Failed(e);
+                    }
+                    // The bridge may have been created before a later command failed
+                    if let Some(name) = created_bridge.filter(|name| backend.interface_exists(name)) {
+                        log::info!("Rolling back: removing bridge {}", name);
+                        if let Err(e) = delete_bridge(backend, name) {
+                            log::error!("Error removing bridge {}: {}", name, e);
+                        }
+                    }
+                    return ApplyOutcome::RolledBack(e);
+                }
+            }
+            Some(_) => log::warn!("Bridge interfaces are not supported on this platform, applying to {}", self.adapter),
+            None => {}
         }
 
-        // Set the windows adapters values to the profile values
-        let adapter = self.adapter.clone();
+        // Without the address section the default route can still be changed on its own
+        let gateway_only = self.gateway_only(sections);
+        let routes_added = Cell::new(0);
+        // The MAC address and MTU belong to the adapter itself, even under a bridge
+        let previous_link = match adapter == self.adapter {
+            true => previous.clone(),
+            false => backend.current_profile(&self.adapter),
+        };
+        let (mac_set, mtu_set) = (Cell::new(false), Cell::new(false));
+        let rollback = |backend: &mut dyn Backend, address_set: bool, dns_set: bool| {
+            if dns_set {
+                let servers = previous.dns_servers();
+                let servers: Vec<&str> = servers.iter().map(String::as_str).filter(|server| !server.is_empty()).collect();
+                if servers.is_empty() {
+                    log::warn!("Rolling back: {} had no DNS servers to restore", adapter);
+                } else {
+                    log::info!("Rolling back: restoring DNS on {} to {}", adapter, servers.join(" "));
+                    if let Err(e) = set_dns(backend, adapter, &servers, &[]) {
+                        log::error!("Error restoring DNS on {}: {}", adapter, e);
+                    }
+                }
+            }
+            for route in self.routes[..routes_added.get()].iter().rev() {
+                // `ip route replace` took over a route the adapter already had, which has to stay
+                if previous.routes.iter().any(|existing| existing.destination == route.destination && existing.prefix == route.prefix) {
+                    log::info!("Rolling back: keeping route to {}, {} had it before", route, adapter);
+                    continue;
+                }
+                log::info!("Rolling back: removing route to {}", route);
+                if let Err(e) = delete_route(backend, adapter, route) {
+                    log::error!("Error removing route to {}: {}", route, e);
+                }
+            }
+            // Before the address, as changing the MAC address bounces the link
+            if let Some(mtu) = previous_link.mtu.filter(|_| mtu_set.get()) {
+                log::info!("Rolling back: restoring the MTU of {} to {}", self.adapter, mtu);
+                if let Err(e) = set_mtu(backend, &self.adapter, mtu) {
+                    log::error!("Error restoring MTU on {}: {}", self.adapter, e);
+                }
+            }
+            if let Some(mac) = previous_link.mac_address.as_deref().filter(|_| mac_set.get()) {
+                log::info!("Rolling back: restoring the MAC address of {} to {}", self.adapter, mac);
+                if let Err(e) = set_mac_address(backend, &self.adapter, mac) {
+                    log::error!("Error restoring MAC address on {}: {}", self.adapter, e);
+                }
+            }
+            if address_set && gateway_only {
+                if !previous.gateway.is_empty() {
+                    log::info!("Rolling back: restoring the gateway of {} to {}", adapter, previous.gateway);
+                    if let Err(e) = set_gateway(backend, adapter, &previous.gateway) {
+                        log::error!("Error restoring gateway on {}: {}", adapter, e);
+                    }
+                }
+            } else if address_set && previous.ipv4_policy == FamilyPolicy::Dhcp {
+                // Pinning the leased address would outlive the lease
+                log::info!("Rolling back: putting {} back on DHCP", adapter);
+                if let Err(e) = set_dhcp(backend, adapter, "ipv4") {
+                    log::error!("Error restoring DHCP on {}: {}", adapter, e);
+                }
+            } else if address_set && !previous.ip.is_empty() {
+                log::info!("Rolling back: restoring {} to {}/{}", adapter, previous.ip, previous.subnet);
+                if let Err(e) = set_address(backend, adapter, &previous.ip, &previous.subnet, &previous.gateway) {
+                    log::error!("Error restoring address on {}: {}", adapter, e);
+                }
+            }
+            if let Some(name) = created_bridge {
+                log::info!("Rolling back: removing bridge {}", name);
+                if let Err(e) = delete_bridge(backend, name) {
+                    log::error!("Error removing bridge {}: {}", name, e);
+                }
+            }
+        };
+        // Any address change starts by clearing the old one, so even a failed one needs restoring
+        let address_touched = match sections.contains(ApplySections::ADDRESS) {
+            true => self.ipv4_policy != FamilyPolicy::LeaveAlone,
+            false => gateway_only,
+        };
+        let fail = |backend: &mut dyn Backend, e: NetProfilerError, dns_set: bool| {
+            if !transactional {
+                return ApplyOutcome::Failed(e);
+            }
+            rollback(backend, address_touched, dns_set);
+            ApplyOutcome::RolledBack(e)
+        };
+
+        if cancel.load(Ordering::Relaxed) {
+            rollback(backend, false, false);
+            return ApplyOutcome::Cancelled;
+        }
+
+        // Taking the link down for a MAC change drops its routes, so this goes before addressing
+        if let Some(mac) = self.mac_address.as_deref().filter(|mac| !mac.is_empty() && sections.contains(ApplySections::MAC)) {
+            progress(ApplyStep::SettingMac);
+            // Set before trying, as a failure can come after the address changed
+            mac_set.set(true);
+            if let Err(e) = set_mac_address(backend, &self.adapter, mac) {
+                return fail(backend, e, false);
+            }
+        }
 
-        let ip_address: &String = &self.ip;
-        let subnet: &String = &self.subnet;
-        let gateway: &String = &self.gateway;
-        let dns_servers: Vec<&str> = match self.dns_provider {
-            DNSProvider::Quad9 => vec!["9.9.9.9","149.112.112.112"],
-            DNSProvider::Google => vec!["8.8.8.8","8.8.4.4"],
-            DNSProvider::Cloudflare => vec!["1.1.1.2","1.0.0.2"],
-            DNSProvider::OpenDNS => vec!["208.67.222.222","208.67.220.220"],
-            DNSProvider::Custom => vec![self.primary_dns.as_str(), self.secondary_dns.as_str()],
-            _ => vec!["",""],
+        if let Some(mtu) = self.mtu.filter(|_| sections.contains(ApplySections::MTU)) {
+            progress(ApplyStep::SettingMtu);
+            mtu_set.set(true);
+            if let Err(e) = set_mtu(backend, &self.adapter, mtu) {
+                return fail(backend, e, false);
+            }
+        }
+
+        // Set IP subnet and gateway. Setting the address drops the default route, so without the
+        // gateway section the one the adapter had is put back
+        let gateway = if sections.contains(ApplySections::GATEWAY) { self.gateway.as_str() } else { previous.gateway.as_str() };
+        if gateway_only {
+            progress(ApplyStep::SettingGateway);
+        } else if sections.contains(ApplySections::ADDRESS) {
+            progress(ApplyStep::SettingAddress);
+        }
+        let address = match self.ipv4_policy {
+            _ if gateway_only => set_gateway(backend, adapter, gateway),
+            _ if !sections.contains(ApplySections::ADDRESS) => Ok(()),
+            FamilyPolicy::Configure => set_address(backend, adapter, &self.ip, &self.subnet, gateway),
+            FamilyPolicy::Dhcp => set_dhcp(backend, adapter, "ipv4"),
+            FamilyPolicy::LeaveAlone => Ok(()),
         };
+        if let Err(e) = address {
+            return fail(backend, e, false);
+        }
+        match self.ipv6_policy {
+            _ if !sections.contains(ApplySections::ADDRESS) => {}
+            FamilyPolicy::Dhcp => if let Err(e) = set_dhcp(backend, adapter, "ipv6") {
+                return fail(backend, e, false);
+            }
+            // Profiles have no static IPv6 settings to program yet
+            FamilyPolicy::Configure | FamilyPolicy::LeaveAlone => {}
+        }
+        if let Some(metric) = self.route_metric.filter(|_| sections.contains(ApplySections::ROUTES)) {
+            progress(ApplyStep::SettingRouteMetric);
+            if let Err(e) = set_route_metric(backend, adapter, metric) {
+                return fail(backend, e, false);
+            }
+        }
+        let routes: &[StaticRoute] = if sections.contains(ApplySections::ROUTES) { &self.routes } else { &[] };
+        for (index, route) in routes.iter().enumerate() {
+            progress(ApplyStep::AddingRoute(index + 1));
+            if let Err(e) = add_route(backend, adapter, route) {
+                return fail(backend, e, false);
+            }
+            routes_added.set(routes_added.get() + 1);
+        }
 
-        // Set IP subnet and gateway
-        let output = Command::new("powershell")
-            .arg("-Command")
-            .arg(format!(
-                "netsh interface ip set address \"{}\" static {} {} {}",
-                adapter, ip_address, subnet, gateway
-            ))
-            .output()
-            .expect("Failed to set DNS servers");
+        if cancel.load(Ordering::Relaxed) {
+            rollback(backend, address_touched, false);
+            return ApplyOutcome::Cancelled;
+        }
 
         // Set DNS servers
-        if let DNSProvider::None = self.dns_provider { return }
-        let _output = Command::new("powershell")
-            .arg("-Command")
-            .arg(format!(
-                "netsh interface ip set dns \"{}\" static {} primary validate=no; netsh interface ip add dns \"{}\" {} validate=no",
-                adapter, dns_servers[0], adapter, dns_servers[1]
-            ))
-            .output()
-            .expect("Failed to set DNS servers");
+        let set_dns_servers = !matches!(self.dns_provider, DNSProvider::None) && sections.contains(ApplySections::DNS);
+        if set_dns_servers {
+            progress(ApplyStep::SettingDns);
+            let servers = self.dns_servers();
+            let servers: Vec<&str> = servers.iter().map(String::as_str).filter(|server| !server.is_empty()).collect();
+            if servers.is_empty() {
+                return fail(backend, NetProfilerError::InvalidDns(String::new()), false);
+            }
+            if let Err(e) = set_dns(backend, adapter, &servers, &self.dns_search_domains()) {
+                return fail(backend, e, true);
+            }
+            if self.dns_encryption != DnsEncryption::Off {
+                if let Err(e) = set_dns_encryption(backend, adapter, &servers, self.dns_encryption, doh_template(&self.dns_provider)) {
+                    return fail(backend, e, true);
+                }
+            }
+        }
+
+        if self.persist {
+            progress(ApplyStep::SavingConfig);
+            if let Err(e) = persist_config(backend, self, adapter, sections) {
+                return fail(backend, e, set_dns_servers);
+            }
+        }
+
+        // Cleared when the profile has none, so the result doesn't depend on what was applied before
+        if sections.contains(ApplySections::PROXY) {
+            progress(ApplyStep::SettingProxy);
+            let proxy = match &self.proxy {
+                Some(proxy) => set_proxy(backend, proxy),
+                None => clear_proxy(backend),
+            };
+            if let Err(e) = proxy {
+                return fail(backend, e, set_dns_servers);
+            }
+        }
+
+        // Last, since renaming the computer isn't rolled back
+        if let Some(name) = self.renames_computer(backend, sections) {
+            progress(ApplyStep::SettingHostname);
+            if let Err(e) = set_hostname(backend, name) {
+                return fail(backend, e, set_dns_servers);
+            }
+        }
+        progress(ApplyStep::Done);
+        ApplyOutcome::Applied
     }
 }
 
-impl From<serde_json::Value> for NetworkProfile {
-    fn from(value: serde_json::Value) -> Self {
-        serde_json::from_value(value).unwrap_or_default()
+/// Adapters with an apply in progress on this machine
+static APPLYING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Claim on an adapter for the length of an apply, released when dropped
+struct ApplyLock(String);
+
+impl ApplyLock {
+    fn acquire(adapter: &str) -> Option<Self> {
+        let mut applying = APPLYING.lock().unwrap_or_else(PoisonError::into_inner);
+        applying.insert(adapter.to_string()).then(|| ApplyLock(adapter.to_string()))
     }
 }
 
-impl Into<serde_json::Value> for NetworkProfile {
-    fn into(self) -> serde_json::Value {
-        serde_json::to_value(&self).unwrap_or_default()
+impl Drop for ApplyLock {
+    fn drop(&mut self) {
+        APPLYING.lock().unwrap_or_else(PoisonError::into_inner).remove(&self.0);
+        APPLIED_AT.lock().unwrap_or_else(PoisonError::into_inner).insert(self.0.clone(), Instant::now());
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
-#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
-pub enum DNSProvider {
-    #[default]
-    None,
-    Quad9,
-    Google,
-    Cloudflare,
-    OpenDNS,
-    Custom,
-}
\ No newline at end of file
+/// When an apply to each adapter last finished
+static APPLIED_AT: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+/// Whether a profile is being applied to the adapter right now.
+pub fn is_applying(adapter: &str) -> bool {
+    APPLYING.lock().unwrap_or_else(PoisonError::into_inner).contains(adapter)
+}
+
+/// Whether a profile is being applied to the adapter or finished being applied within `within`,
+/// as changing the MAC address or addressing can bounce the link.
+pub fn applied_within(adapter: &str, within: Duration) -> bool {
+    is_applying(adapter) || APPLIED_AT.lock().unwrap_or_else(PoisonError::into_inner)
+        .get(adapter)
+        .is_some_and(|at| at.elapsed() < within)
+}
+
+/// Runs the commands that configure the system and reads back its state,
+/// so applying a profile can be exercised without touching real interfaces.
+pub trait Backend {
+    fn run(&mut self, program: &str, args: &[&str]) -> error::Result<()>;
+    fn interface_exists(&self, name: &str) -> bool;
+    /// Whether the interface is administratively up
+    fn interface_up(&self, name: &str) -> bool;
+    fn netns_exists(&self, name: &str) -> bool;
+    /// Whether an interface inside a network namespace is administratively up, or None when the
+    /// namespace has no such interface
+    fn netns_interface_up(&self, netns: &str, name: &str) -> Option<bool>;
+    fn current_profile(&self, adapter: &str) -> NetworkProfile;
+    /// Replaces a file's contents, for settings no installed tool manages
+    fn write_file(&mut self, path: &str, contents: &str) -> error::Result<()>;
+    /// What sets DNS, on Linux where it depends on what's installed
+    fn dns_manager(&self) -> DnsManager;
+    /// The machine's current hostname
+    fn hostname(&self) -> String;
+    /// Whether systemd-networkd is running to manage interfaces, on Linux
+    fn networkd_running(&self) -> bool;
+}
+
+/// Backend that executes commands on this machine
+pub struct SystemBackend;
+
+impl Backend for SystemBackend {
+    fn run(&mut self, program: &str, args: &[&str]) -> error::Result<()> {
+        log::info!("Running {} {}", program, args.join(" "));
+        // The error carries the command's stderr, which is what explains the failure
+        run(program, args).inspect_err(|e| log::error!("{}", e))
+    }
+
+    fn interface_exists(&self, name: &str) -> bool {
+        PathBuf::from("/sys/class/net").join(name).exists()
+    }
+
+    fn interface_up(&self, name: &str) -> bool {
+        interface_up(name)
+    }
+
+    fn netns_exists(&self, name: &str) -> bool {
+        // Where `ip netns add` creates named namespaces
+        PathBuf::from("/run/netns").join(name).exists()
+    }
+
+    fn netns_interface_up(&self, netns: &str, name: &str) -> Option<bool> {
+        // "2: eth0: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 ..."
+        let output = run_output("ip", &["-n", netns, "-o", "link", "show", "dev", name]).ok()?;
+        let flags = output.split_once('<')?.1.split_once('>')?.0;
+        Some(flags.split(',').any(|flag| flag == "UP"))
+    }
+
+    fn current_profile(&self, adapter: &str) -> NetworkProfile {
+        let mut profile = profile_from_interface(adapter);
+        if address_is_dhcp(adapter) {
+            profile.ipv4_policy = FamilyPolicy::Dhcp;
+        }
+        profile.mac_address = adapter_mac(adapter);
+        profile.mtu = adapter_mtu(adapter);
+        profile.routes = adapter_routes(adapter);
+        profile
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> error::Result<()> {
+        log::info!("Writing {}", path);
+        std::fs::write(path, contents)
+            .map_err(|e| match e.kind() {
+                ErrorKind::PermissionDenied => NetProfilerError::Elevation(format!("{} can't be written: {}", path, e)),
+                _ => NetProfilerError::Profile(format!("Error writing {}: {}", path, e)),
+            })
+            .inspect_err(|e| log::error!("{}", e))
+    }
+
+    fn dns_manager(&self) -> DnsManager {
+        dns_manager()
+    }
+
+    fn hostname(&self) -> String {
+        current_hostname()
+    }
+
+    fn networkd_running(&self) -> bool {
+        // networkd creates this while it runs, installed isn't enough
+        PathBuf::from("/run/systemd/netif").exists()
+    }
+}
+
+/// Backend that records the commands a profile would run without executing them,
+/// while still reading the real state of the machine.
+#[derive(Default)]
+pub struct DryRunBackend {
+    pub commands: Vec<String>,
+}
+
+impl Backend for DryRunBackend {
+    fn run(&mut self, program: &str, args: &[&str]) -> error::Result<()> {
+        self.commands.push(format!("{} {}", program, args.join(" ")));
+        Ok(())
+    }
+
+    fn interface_exists(&self, name: &str) -> bool {
+        SystemBackend.interface_exists(name)
+    }
+
+    fn interface_up(&self, name: &str) -> bool {
+        SystemBackend.interface_up(name)
+    }
+
+    fn netns_exists(&self, name: &str) -> bool {
+        SystemBackend.netns_exists(name)
+    }
+
+    fn netns_interface_up(&self, netns: &str, name: &str) -> Option<bool> {
+        SystemBackend.netns_interface_up(netns, name)
+    }
+
+    fn current_profile(&self, adapter: &str) -> NetworkProfile {
+        SystemBackend.current_profile(adapter)
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> error::Result<()> {
+        self.commands.push(describe_write(path, contents));
+        Ok(())
+    }
+
+    fn dns_manager(&self) -> DnsManager {
+        SystemBackend.dns_manager()
+    }
+
+    fn hostname(&self) -> String {
+        SystemBackend.hostname()
+    }
+
+    fn networkd_running(&self) -> bool {
+        SystemBackend.networkd_running()
+    }
+}
+
+/// How a file write is listed alongside commands, e.g. in a dry run.
+fn describe_write(path: &str, contents: &str) -> String {
+    format!("write {}: {}", path, contents.trim_end().replace('\n', "; "))
+}
+
+/// Runs another backend's commands inside a network namespace: `ip` through its `-n` option,
+/// everything else through `ip netns exec`.
+struct NamespacedBackend<'a> {
+    inner: &'a mut dyn Backend,
+    netns: &'a str,
+}
+
+impl Backend for NamespacedBackend<'_> {
+    fn run(&mut self, program: &str, args: &[&str]) -> error::Result<()> {
+        match program {
+            "ip" => self.inner.run("ip", &[&["-n", self.netns], args].concat()),
+            _ => self.inner.run("ip", &[&["netns", "exec", self.netns, program], args].concat()),
+        }
+    }
+
+    fn interface_exists(&self, name: &str) -> bool {
+        self.inner.netns_interface_up(self.netns, name).is_some()
+    }
+
+    fn interface_up(&self, name: &str) -> bool {
+        self.inner.netns_interface_up(self.netns, name).unwrap_or(false)
+    }
+
+    fn netns_exists(&self, name: &str) -> bool {
+        self.inner.netns_exists(name)
+    }
+
+    fn netns_interface_up(&self, netns: &str, name: &str) -> Option<bool> {
+        self.inner.netns_interface_up(netns, name)
+    }
+
+    /// Interface enumeration only sees the host, so the configuration inside the namespace isn't
+    /// known. Nothing is restored from it rather than the host's settings of the same name.
+    fn current_profile(&self, adapter: &str) -> NetworkProfile {
+        NetworkProfile { name: adapter.to_string(), adapter: adapter.to_string(), ..Default::default() }
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> error::Result<()> {
+        // `ip netns exec` mounts the namespace's own resolv.conf over the system one
+        match path {
+            RESOLV_CONF => self.inner.write_file(&format!("/etc/netns/{}/resolv.conf", self.netns), contents),
+            _ => self.inner.write_file(path, contents),
+        }
+    }
+
+    fn dns_manager(&self) -> DnsManager {
+        self.inner.dns_manager()
+    }
+
+    fn hostname(&self) -> String {
+        self.inner.hostname()
+    }
+
+    fn networkd_running(&self) -> bool {
+        self.inner.networkd_running()
+    }
+}
+
+bitflags::bitflags! {
+    /// Parts of a profile to apply, so e.g. DNS can be changed without touching the addressing of
+    /// a remote machine. The route metric goes with the routes, and a bridge with the address.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ApplySections: u8 {
+        const ADDRESS = 1;
+        const GATEWAY = 1 << 1;
+        const DNS = 1 << 2;
+        const ROUTES = 1 << 3;
+        const MAC = 1 << 4;
+        const MTU = 1 << 5;
+        const HOSTNAME = 1 << 6;
+        const PROXY = 1 << 7;
+    }
+}
+
+impl ApplySections {
+    /// The sections that configure the adapter itself, rather than the whole system
+    pub const ADAPTER: ApplySections = ApplySections::ADDRESS
+        .union(ApplySections::GATEWAY)
+        .union(ApplySections::DNS)
+        .union(ApplySections::ROUTES)
+        .union(ApplySections::MAC)
+        .union(ApplySections::MTU);
+
+    pub const LABELS: [(ApplySections, &'static str); 8] = [
+        (ApplySections::ADDRESS, "IPs"),
+        (ApplySections::GATEWAY, "Gateway"),
+        (ApplySections::DNS, "DNS"),
+        (ApplySections::ROUTES, "Routes"),
+        (ApplySections::MAC, "MAC"),
+        (ApplySections::MTU, "MTU"),
+        (ApplySections::HOSTNAME, "Hostname"),
+        (ApplySections::PROXY, "Proxy"),
+    ];
+}
+
+/// How applying a profile finished
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    Applied,
+    Cancelled,
+    Failed(NetProfilerError),
+    /// A step failed and the steps before it were undone
+    RolledBack(NetProfilerError),
+}
+
+/// A step of applying a profile, reported as it starts so the UI can show how far along it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyStep {
+    CreatingBridge,
+    SettingMac,
+    SettingMtu,
+    /// The static address and gateway, or DHCP
+    SettingAddress,
+    /// Only the default route, when the address is left as it is
+    SettingGateway,
+    SettingRouteMetric,
+    /// The nth static route, counting from 1
+    AddingRoute(usize),
+    SettingDns,
+    /// Saving the configuration to survive a reboot
+    SavingConfig,
+    SettingProxy,
+    SettingHostname,
+    Done,
+}
+
+impl std::fmt::Display for ApplyStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyStep::CreatingBridge => write!(f, "Creating bridge..."),
+            ApplyStep::SettingMac => write!(f, "Setting MAC address..."),
+            ApplyStep::SettingMtu => write!(f, "Setting MTU..."),
+            ApplyStep::SettingAddress => write!(f, "Setting address..."),
+            ApplyStep::SettingGateway => write!(f, "Setting gateway..."),
+            ApplyStep::SettingRouteMetric => write!(f, "Setting route metric..."),
+            ApplyStep::AddingRoute(n) => write!(f, "Adding route {}...", n),
+            ApplyStep::SettingDns => write!(f, "Setting DNS..."),
+            ApplyStep::SavingConfig => write!(f, "Saving configuration..."),
+            ApplyStep::SettingProxy => write!(f, "Setting proxy..."),
+            ApplyStep::SettingHostname => write!(f, "Renaming computer..."),
+            ApplyStep::Done => write!(f, "Done"),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn set_address(backend: &mut dyn Backend, adapter: &str, ip: &str, subnet: &str, gateway: &str) -> error::Result<()> {
+    // netsh only takes dotted masks
+    let Some(subnet) = normalize_subnet(subnet, MaskFormat::Dotted) else {
+        return Err(NetProfilerError::InvalidSubnet(subnet.to_string()));
+    };
+    // netsh is run directly, each value its own argument, so names with spaces stay whole
+    let mut args = vec!["interface", "ip", "set", "address", adapter, "static", ip, subnet.as_str()];
+    // An empty argument reaches netsh as a gateway of ""
+    if !gateway.is_empty() {
+        args.push(gateway);
+    }
+    backend.run("netsh", &args)
+}
+
+#[cfg(target_os = "linux")]
+fn set_address(backend: &mut dyn Backend, adapter: &str, ip: &str, subnet: &str, gateway: &str) -> error::Result<()> {
+    let Some(prefix) = subnet_prefix(subnet) else {
+        return Err(NetProfilerError::InvalidSubnet(subnet.to_string()));
+    };
+
+    let address = format!("{}/{}", ip, prefix);
+    backend.run("ip", &["addr", "flush", "dev", adapter])?;
+    backend.run("ip", &["addr", "add", &address, "dev", adapter])?;
+    if !gateway.is_empty() {
+        set_gateway(backend, adapter, gateway)?;
+    }
+    Ok(())
+}
+
+/// Points the default route at `gateway` without touching the adapter's addresses.
+#[cfg(target_os = "windows")]
+fn set_gateway(backend: &mut dyn Backend, adapter: &str, gateway: &str) -> error::Result<()> {
+    backend.run("powershell", &[
+        "-Command",
+        &format!(
+            "Remove-NetRoute -InterfaceAlias {0} -DestinationPrefix 0.0.0.0/0 -Confirm:$false -ErrorAction SilentlyContinue; \
+             New-NetRoute -InterfaceAlias {0} -DestinationPrefix 0.0.0.0/0 -NextHop {1}",
+            powershell_quote(adapter), gateway,
+        ),
+    ])
+}
+
+#[cfg(target_os = "linux")]
+fn set_gateway(backend: &mut dyn Backend, adapter: &str, gateway: &str) -> error::Result<()> {
+    backend.run("ip", &["route", "replace", "default", "via", gateway, "dev", adapter])
+}
+
+/// Gives the adapter a new hardware address. The driver only picks up the NetworkAddress
+/// setting when the adapter starts, so an adapter that's up is restarted for it.
+#[cfg(target_os = "windows")]
+pub fn set_mac_address(backend: &mut dyn Backend, adapter: &str, mac: &str) -> error::Result<()> {
+    let mac = normalize_mac(mac).map_err(NetProfilerError::Profile)?;
+    let was_up = backend.interface_up(adapter);
+    let value = mac.replace(':', "").to_ascii_uppercase();
+    backend.run("powershell", &[
+        "-Command",
+        &format!("Set-NetAdapterAdvancedProperty -Name {} -RegistryKeyword NetworkAddress -RegistryValue {} -NoRestart", powershell_quote(adapter), value),
+    ])?;
+    if was_up {
+        backend.run("powershell", &["-Command", &format!("Restart-NetAdapter -Name {} -Confirm:$false", powershell_quote(adapter))])?;
+    }
+    Ok(())
+}
+
+/// Gives the adapter a new hardware address. The link has to be down for the change, so an
+/// adapter that's up is taken down around it and brought back up even if the change fails,
+/// while one that was down is left down.
+#[cfg(target_os = "linux")]
+pub fn set_mac_address(backend: &mut dyn Backend, adapter: &str, mac: &str) -> error::Result<()> {
+    let mac = normalize_mac(mac).map_err(NetProfilerError::Profile)?;
+    let was_up = backend.interface_up(adapter);
+    if was_up {
+        backend.run("ip", &["link", "set", "dev", adapter, "down"])?;
+    }
+    let result = backend.run("ip", &["link", "set", "dev", adapter, "address", &mac]);
+    let restored = match was_up {
+        true => backend.run("ip", &["link", "set", "dev", adapter, "up"]),
+        false => Ok(()),
+    };
+    result.and(restored)
+}
+
+#[cfg(target_os = "linux")]
+fn interface_up(adapter: &str) -> bool {
+    // IFF_UP is the lowest bit of the interface flags
+    std::fs::read_to_string(PathBuf::from("/sys/class/net").join(adapter).join("flags"))
+        .ok()
+        .and_then(|flags| u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok())
+        .is_some_and(|flags| flags & 1 == 1)
+}
+
+#[cfg(target_os = "windows")]
+fn interface_up(adapter: &str) -> bool {
+    run_output("powershell", &["-Command", &format!("(Get-NetAdapter -Name {}).AdminStatus", powershell_quote(adapter))])
+        .is_ok_and(|status| status.trim() == "Up")
+}
+
+/// Switches an address family ("ipv4" or "ipv6") to automatic configuration.
+#[cfg(target_os = "windows")]
+fn set_dhcp(backend: &mut dyn Backend, adapter: &str, family: &str) -> error::Result<()> {
+    match family {
+        "ipv4" => backend.run("netsh", &["interface", "ipv4", "set", "address", adapter, "dhcp"]),
+        _ => backend.run("netsh", &["interface", "ipv6", "set", "interface", adapter, "routerdiscovery=enabled", "managedaddress=enabled"]),
+    }
+}
+
+/// Switches an address family ("ipv4" or "ipv6") to automatic configuration, through whatever
+/// manages the adapter as `reset_to_dhcp` does.
+#[cfg(target_os = "linux")]
+fn set_dhcp(backend: &mut dyn Backend, adapter: &str, family: &str) -> error::Result<()> {
+    if backend.dns_manager() == DnsManager::NetworkManager {
+        return backend.run("nmcli", &["device", "modify", adapter, &format!("{}.method", family), "auto"]);
+    }
+    if backend.networkd_running() {
+        let dhcp = match family {
+            "ipv4" => NetworkProfile { ipv4_policy: FamilyPolicy::Dhcp, ..Default::default() },
+            _ => NetworkProfile { ipv4_policy: FamilyPolicy::LeaveAlone, ipv6_policy: FamilyPolicy::Dhcp, ..Default::default() },
+        };
+        backend.write_file(&networkd_path(adapter), &networkd_config(&dhcp, adapter))?;
+        backend.run("networkctl", &["reload"])?;
+        return backend.run("networkctl", &["reconfigure", adapter]);
+    }
+    match family {
+        "ipv4" => backend.run("dhclient", &[adapter]),
+        _ => backend.run("dhclient", &["-6", adapter]),
+    }
+}
+
+/// Puts the adapter back on DHCP, dropping the static addresses, gateway and DNS a profile left.
+#[cfg(target_os = "windows")]
+pub fn reset_to_dhcp(backend: &mut dyn Backend, adapter: &str) -> error::Result<()> {
+    backend.run("netsh", &["interface", "ip", "set", "address", adapter, "dhcp"])?;
+    backend.run("netsh", &["interface", "ip", "set", "dnsservers", adapter, "dhcp"])
+}
+
+/// Puts the adapter back on DHCP, dropping the static addresses, gateway and DNS a profile left.
+/// Whatever manages the adapter is switched over before the addresses are flushed, so a machine
+/// without it isn't left with no address at all.
+#[cfg(target_os = "linux")]
+pub fn reset_to_dhcp(backend: &mut dyn Backend, adapter: &str) -> error::Result<()> {
+    if backend.dns_manager() == DnsManager::NetworkManager {
+        modify_connection(backend, adapter, &[
+            ("ipv4.method", "auto".to_string()),
+            ("ipv4.addresses", String::new()),
+            ("ipv4.gateway", String::new()),
+            ("ipv4.dns", String::new()),
+            ("ipv4.dns-search", String::new()),
+        ])?;
+        backend.run("ip", &["addr", "flush", "dev", adapter])?;
+        return backend.run("nmcli", &["device", "reapply", adapter]);
+    }
+    if backend.networkd_running() {
+        let dhcp = NetworkProfile { ipv4_policy: FamilyPolicy::Dhcp, ..Default::default() };
+        backend.write_file(&networkd_path(adapter), &networkd_config(&dhcp, adapter))?;
+        backend.run("networkctl", &["reload"])?;
+        backend.run("ip", &["addr", "flush", "dev", adapter])?;
+        return backend.run("networkctl", &["reconfigure", adapter]);
+    }
+    // Releasing a lease first fails without dhclient, before anything is flushed
+    backend.run("dhclient", &["-r", adapter])?;
+    backend.run("ip", &["addr", "flush", "dev", adapter])?;
+    backend.run("dhclient", &[adapter])
+}
+
+/// Resets the adapter on this machine to DHCP and records it in the audit log like an apply.
+pub fn reset_adapter_to_dhcp(adapter: &str) -> ApplyOutcome {
+    let Some(_lock) = ApplyLock::acquire(adapter) else {
+        return ApplyOutcome::Failed(NetProfilerError::Profile(format!("A profile is already being applied to {}", adapter)));
+    };
+    let outcome = match reset_to_dhcp(&mut SystemBackend, adapter) {
+        Ok(()) => ApplyOutcome::Applied,
+        Err(e) => ApplyOutcome::Failed(e),
+    };
+    let reset = NetworkProfile {
+        name: "Reset to DHCP".to_string(),
+        adapter: adapter.to_string(),
+        ipv4_policy: FamilyPolicy::Dhcp,
+        ..Default::default()
+    };
+    audit::record_apply(AuditEntry::new(&reset, &outcome));
+    outcome
+}
+
+/// Whether `gateway` is another host on the network `ip`/`subnet` belongs to, and so reachable
+/// without an extra route. False when any of them doesn't parse.
+pub fn gateway_in_subnet(ip: &str, subnet: &str, gateway: &str) -> bool {
+    let (Ok(ip), Ok(gateway), Some(prefix)) = (ip.parse::<Ipv4Addr>(), gateway.parse::<Ipv4Addr>(), subnet_prefix(subnet)) else {
+        return false;
+    };
+    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+    ip != gateway && u32::from(ip) & mask == u32::from(gateway) & mask
+}
+
+#[cfg(target_os = "windows")]
+fn add_route(backend: &mut dyn Backend, adapter: &str, route: &StaticRoute) -> error::Result<()> {
+    let destination = format!("{}/{}", route.destination, route.prefix);
+    let mut args = vec!["interface", "ipv4", "add", "route", &destination, adapter, &route.via];
+    let metric = route.metric.map(|metric| format!("metric={}", metric));
+    if let Some(metric) = &metric {
+        args.push(metric);
+    }
+    backend.run("netsh", &args)
+}
+
+/// Replaces rather than adds, so applying a profile twice doesn't fail on its own routes.
+#[cfg(target_os = "linux")]
+fn add_route(backend: &mut dyn Backend, adapter: &str, route: &StaticRoute) -> error::Result<()> {
+    let destination = format!("{}/{}", route.destination, route.prefix);
+    let mut args = vec!["route", "replace", &destination, "via", &route.via, "dev", adapter];
+    let metric = route.metric.map(|metric| metric.to_string());
+    if let Some(metric) = &metric {
+        args.extend(["metric", metric]);
+    }
+    backend.run("ip", &args)
+}
+
+#[cfg(target_os = "windows")]
+fn delete_route(backend: &mut dyn Backend, adapter: &str, route: &StaticRoute) -> error::Result<()> {
+    let destination = format!("{}/{}", route.destination, route.prefix);
+    backend.run("netsh", &["interface", "ipv4", "delete", "route", &destination, adapter, &route.via])
+}
+
+#[cfg(target_os = "linux")]
+fn delete_route(backend: &mut dyn Backend, adapter: &str, route: &StaticRoute) -> error::Result<()> {
+    let destination = format!("{}/{}", route.destination, route.prefix);
+    backend.run("ip", &["route", "del", &destination, "via", &route.via, "dev", adapter])
+}
+
+/// MTUs a profile can set: the IPv4 minimum up to the largest jumbo frames switches commonly take
+pub const MTU_RANGE: std::ops::RangeInclusive<u32> = 68..=9216;
+
+#[cfg(target_os = "windows")]
+fn set_mtu(backend: &mut dyn Backend, adapter: &str, mtu: u32) -> error::Result<()> {
+    backend.run("netsh", &["interface", "ipv4", "set", "subinterface", adapter, &format!("mtu={}", mtu), "store=persistent"])
+}
+
+/// Saves the profile's configuration of `adapter` so it's restored at boot, as the `ip` commands
+/// only change the running system. Where NetworkManager runs, the adapter's connection profile is
+/// modified with `nmcli connection modify`; elsewhere a systemd-networkd `.network` file is written
+/// for it, which needs networkd running to take effect. Only whole profiles are saved, as a saved
+/// subset would drop the adapter's other settings; the hostname and proxy aren't the adapter's.
+#[cfg(target_os = "linux")]
+fn persist_config(backend: &mut dyn Backend, profile: &NetworkProfile, adapter: &str, sections: ApplySections) -> error::Result<()> {
+    if profile.netns.as_deref().is_some_and(|ns| !ns.is_empty()) {
+        log::warn!("Configuration inside network namespaces isn't saved, {} won't survive a reboot", adapter);
+        return Ok(());
+    }
+    if !sections.contains(ApplySections::ADAPTER) {
+        log::info!("Not saving the configuration of {}, only part of the profile was applied", adapter);
+        return Ok(());
+    }
+    match backend.dns_manager() {
+        DnsManager::NetworkManager => {
+            let settings = network_manager_settings(profile);
+            if settings.is_empty() {
+                return Ok(());
+            }
+            modify_connection(backend, adapter, &settings)
+        }
+        DnsManager::Resolved | DnsManager::ResolvConf if backend.networkd_running() => {
+            backend.write_file(&networkd_path(adapter), &networkd_config(profile, adapter))
+        }
+        DnsManager::Resolved | DnsManager::ResolvConf => Err(NetProfilerError::Unsupported(format!(
+            "Neither NetworkManager nor systemd-networkd is running, so the configuration of {} can't be saved",
+            adapter,
+        ))),
+    }
+}
+
+/// Changes settings of the NetworkManager connection `adapter` is using, whatever it's called,
+/// so they're kept across reboots. They take effect when the connection is next activated.
+#[cfg(target_os = "linux")]
+fn modify_connection(backend: &mut dyn Backend, adapter: &str, settings: &[(&str, String)]) -> error::Result<()> {
+    let connection = format!("\"$(nmcli -g GENERAL.CONNECTION device show {})\"", shell_quote(adapter));
+    let settings: Vec<String> = settings.iter().map(|(key, value)| format!("{} {}", key, shell_quote(value))).collect();
+    backend.run("sh", &["-c", &format!("nmcli connection modify {} {}", connection, settings.join(" "))])
+}
+
+/// Where the systemd-networkd configuration for `adapter` is saved.
+fn networkd_path(adapter: &str) -> String {
+    format!("/etc/systemd/network/10-net-profiler-{}.network", adapter)
+}
+
+/// Windows keeps netsh changes across reboots already.
+#[cfg(target_os = "windows")]
+fn persist_config(_backend: &mut dyn Backend, _profile: &NetworkProfile, _adapter: &str, _sections: ApplySections) -> error::Result<()> {
+    Ok(())
+}
+
+/// NetworkManager connection settings matching the profile, as key and value pairs.
+pub fn network_manager_settings(profile: &NetworkProfile) -> Vec<(&'static str, String)> {
+    let mut settings = Vec::new();
+    if let Some(mac) = profile.mac_address.as_deref().filter(|mac| !mac.is_empty()) {
+        settings.push(("802-3-ethernet.cloned-mac-address", normalize_mac(mac).unwrap_or_else(|_| mac.to_string())));
+    }
+    if let Some(mtu) = profile.mtu {
+        settings.push(("802-3-ethernet.mtu", mtu.to_string()));
+    }
+    match profile.ipv4_policy {
+        FamilyPolicy::Configure => {
+            settings.push(("ipv4.method", "manual".to_string()));
+            if let Some(prefix) = subnet_prefix(&profile.subnet).filter(|_| !profile.ip.is_empty()) {
+                settings.push(("ipv4.addresses", format!("{}/{}", profile.ip, prefix)));
+            }
+            if !profile.gateway.is_empty() {
+                settings.push(("ipv4.gateway", profile.gateway.clone()));
+            }
+        }
+        FamilyPolicy::Dhcp => settings.push(("ipv4.method", "auto".to_string())),
+        FamilyPolicy::LeaveAlone => {}
+    }
+    if profile.ipv6_policy == FamilyPolicy::Dhcp {
+        settings.push(("ipv6.method", "auto".to_string()));
+    }
+    if !profile.routes.is_empty() {
+        let routes: Vec<String> = profile.routes.iter()
+            .map(|route| match route.metric {
+                Some(metric) => format!("{}/{} {} {}", route.destination, route.prefix, route.via, metric),
+                None => format!("{}/{} {}", route.destination, route.prefix, route.via),
+            })
+            .collect();
+        settings.push(("ipv4.routes", routes.join(", ")));
+    }
+    if let Some(metric) = profile.route_metric {
+        settings.push(("ipv4.route-metric", metric.to_string()));
+    }
+    if profile.dns_provider != DNSProvider::None {
+        let servers: Vec<String> = profile.dns_servers().into_iter().filter(|server| !server.is_empty()).collect();
+        settings.push(("ipv4.dns", servers.join(",")));
+        let search = profile.dns_search_domains();
+        if !search.is_empty() {
+            settings.push(("ipv4.dns-search", search.join(",")));
+        }
+    }
+    settings
+}
+
+/// A systemd-networkd `.network` file configuring `adapter` as the profile does.
+pub fn networkd_config(profile: &NetworkProfile, adapter: &str) -> String {
+    let mut config = format!("# Written by Net Profiler\n[Match]\nName={}\n", adapter);
+    if let Some(mac) = profile.mac_address.as_deref().filter(|mac| !mac.is_empty()) {
+        let _ = write!(config, "\n[Link]\nMACAddress={}\n", normalize_mac(mac).unwrap_or_else(|_| mac.to_string()));
+    }
+    if let Some(mtu) = profile.mtu {
+        if profile.mac_address.as_deref().is_none_or(str::is_empty) {
+            config.push_str("\n[Link]\n");
+        }
+        let _ = writeln!(config, "MTUBytes={}", mtu);
+    }
+    config.push_str("\n[Network]\n");
+    let dhcp = match (profile.ipv4_policy, profile.ipv6_policy) {
+        (FamilyPolicy::Dhcp, FamilyPolicy::Dhcp) => Some("yes"),
+        (FamilyPolicy::Dhcp, _) => Some("ipv4"),
+        (_, FamilyPolicy::Dhcp) => Some("ipv6"),
+        _ => None,
+    };
+    if let Some(dhcp) = dhcp {
+        let _ = writeln!(config, "DHCP={}", dhcp);
+    }
+    if profile.ipv4_policy == FamilyPolicy::Configure {
+        if let Some(prefix) = subnet_prefix(&profile.subnet).filter(|_| !profile.ip.is_empty()) {
+            let _ = writeln!(config, "Address={}/{}", profile.ip, prefix);
+        }
+        if !profile.gateway.is_empty() {
+            let _ = writeln!(config, "Gateway={}", profile.gateway);
+        }
+    }
+    if profile.dns_provider != DNSProvider::None {
+        for server in profile.dns_servers().into_iter().filter(|server| !server.is_empty()) {
+            let _ = writeln!(config, "DNS={}", server);
+        }
+        let search = profile.dns_search_domains();
+        if !search.is_empty() {
+            let _ = writeln!(config, "Domains={}", search.join(" "));
+        }
+    }
+    for route in profile.routes.iter() {
+        let _ = write!(config, "\n[Route]\nDestination={}/{}\nGateway={}\n", route.destination, route.prefix, route.via);
+        if let Some(metric) = route.metric {
+            let _ = writeln!(config, "Metric={}", metric);
+        }
+    }
+    config
+}
+
+/// Quotes a value for `sh -c`, so spaces and quotes in it stay part of one argument.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Where the proxy environment variables are written for login shells on Linux
+const PROXY_PROFILE: &str = "/etc/profile.d/net-profiler-proxy.sh";
+
+/// Points WinHTTP and WinINET, which browsers and most apps use, at the proxy. WinHTTP can't
+/// take a PAC file from netsh, so a PAC URL only reaches WinINET.
+#[cfg(target_os = "windows")]
+fn set_proxy(backend: &mut dyn Backend, proxy: &ProxyConfig) -> error::Result<()> {
+    let mut commands = Vec::new();
+    if proxy.host.is_empty() {
+        commands.push("netsh winhttp reset proxy".to_string());
+        commands.push(format!("Set-ItemProperty -Path '{}' -Name ProxyEnable -Value 0", INTERNET_SETTINGS));
+    } else {
+        let server = format!("{}:{}", proxy.host, proxy.port);
+        let bypass = proxy.bypass_hosts().join(";");
+        commands.push(match bypass.is_empty() {
+            true => format!("netsh winhttp set proxy proxy-server=\"{}\"", server),
+            false => format!("netsh winhttp set proxy proxy-server=\"{}\" bypass-list=\"{}\"", server, bypass),
+        });
+        commands.push(format!("Set-ItemProperty -Path '{}' -Name ProxyServer -Value '{}'", INTERNET_SETTINGS, server));
+        commands.push(format!("Set-ItemProperty -Path '{}' -Name ProxyOverride -Value '{}'", INTERNET_SETTINGS, bypass));
+        commands.push(format!("Set-ItemProperty -Path '{}' -Name ProxyEnable -Value 1", INTERNET_SETTINGS));
+    }
+    commands.push(match proxy.pac_url.is_empty() {
+        true => format!("Remove-ItemProperty -Path '{}' -Name AutoConfigURL -ErrorAction SilentlyContinue", INTERNET_SETTINGS),
+        false => format!("Set-ItemProperty -Path '{}' -Name AutoConfigURL -Value '{}'", INTERNET_SETTINGS, proxy.pac_url),
+    });
+    backend.run("powershell", &["-Command", &commands.join("; ")])
+}
+
+/// Sets the proxy environment variables for new login shells, which command line tools and
+/// most desktop apps follow. Running elevated, the desktop's own settings can't be reached.
+#[cfg(target_os = "linux")]
+fn set_proxy(backend: &mut dyn Backend, proxy: &ProxyConfig) -> error::Result<()> {
+    backend.write_file(PROXY_PROFILE, &proxy_profile(Some(proxy)))
+}
+
+#[cfg(target_os = "windows")]
+fn clear_proxy(backend: &mut dyn Backend) -> error::Result<()> {
+    set_proxy(backend, &ProxyConfig::default())
+}
+
+#[cfg(target_os = "linux")]
+fn clear_proxy(backend: &mut dyn Backend) -> error::Result<()> {
+    backend.write_file(PROXY_PROFILE, &proxy_profile(None))
+}
+
+/// Registry key holding the WinINET proxy settings
+#[cfg(target_os = "windows")]
+const INTERNET_SETTINGS: &str = r"HKCU:\Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+
+/// Shell script exporting the proxy variables, in both cases as tools disagree on which they read.
+pub fn proxy_profile(proxy: Option<&ProxyConfig>) -> String {
+    let mut contents = String::from("# Written by Net Profiler\n");
+    let Some(proxy) = proxy.filter(|proxy| !proxy.host.is_empty()) else {
+        return contents;
+    };
+    let url = format!("http://{}:{}", proxy.host, proxy.port);
+    for name in ["http_proxy", "https_proxy", "HTTP_PROXY", "HTTPS_PROXY"] {
+        let _ = writeln!(contents, "export {}=\"{}\"", name, url);
+    }
+    let bypass = proxy.bypass_hosts();
+    if !bypass.is_empty() {
+        for name in ["no_proxy", "NO_PROXY"] {
+            let _ = writeln!(contents, "export {}=\"{}\"", name, bypass.join(","));
+        }
+    }
+    contents
+}
+
+/// Checks `name` can be handed to the OS tools as an adapter name. Linux interface names are at
+/// most 15 bytes with no slashes, colons or whitespace. Windows names like "Ethernet 2" may have
+/// spaces, but not the quotes or control characters that would end or split the argument.
+pub fn check_adapter_name(name: &str) -> error::Result<()> {
+    let invalid = |reason: &str| Err(NetProfilerError::Profile(format!("Invalid adapter name {:?}: {}", name, reason)));
+    if name.chars().any(char::is_control) {
+        return invalid("control characters aren't allowed");
+    }
+    if cfg!(target_os = "linux") {
+        if name.len() > 15 {
+            return invalid("Linux interface names are at most 15 characters");
+        }
+        if name == "." || name == ".." || name.chars().any(|c| c == '/' || c == ':' || c.is_whitespace()) {
+            return invalid("slashes, colons and spaces aren't allowed");
+        }
+    }
+    if cfg!(target_os = "windows") && name.contains(['"', '`']) {
+        return invalid("quotes aren't allowed");
+    }
+    Ok(())
+}
+
+/// Quotes a value for `powershell -Command` as a literal string, so `$`, `;` and spaces in it
+/// aren't run as PowerShell.
+fn powershell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Checks `name` is a valid hostname: dot separated RFC 1123 labels of up to 63 letters, digits and
+/// inner hyphens. Windows computer names are a single label of at most 15 characters, for NetBIOS.
+pub fn check_hostname(name: &str) -> error::Result<()> {
+    let invalid = |reason: &str| Err(NetProfilerError::Profile(format!("Invalid hostname {}: {}", name, reason)));
+    if cfg!(target_os = "windows") && (name.contains('.') || name.len() > 15) {
+        return invalid("Windows computer names are a single label of at most 15 characters");
+    }
+    if name.len() > 253 {
+        return invalid("longer than 253 characters");
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return invalid("each label must be 1 to 63 characters");
+        }
+        if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return invalid("only letters, digits and hyphens are allowed");
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return invalid("labels can't start or end with a hyphen");
+        }
+    }
+    Ok(())
+}
+
+/// Renames the computer. Windows only picks the new name up after a restart.
+#[cfg(target_os = "windows")]
+fn set_hostname(backend: &mut dyn Backend, name: &str) -> error::Result<()> {
+    backend.run("powershell", &["-Command", &format!("Rename-Computer -NewName \"{}\" -Force", name)])
+}
+
+#[cfg(target_os = "linux")]
+fn set_hostname(backend: &mut dyn Backend, name: &str) -> error::Result<()> {
+    backend.run("hostnamectl", &["set-hostname", name])
+}
+
+/// The name the computer will have, which is the pending one after a rename until the restart.
+#[cfg(target_os = "windows")]
+fn current_hostname() -> String {
+    // COMPUTERNAME keeps the name the session started with, the registry has the pending one
+    run_output("reg", &["query", r"HKLM\SYSTEM\CurrentControlSet\Control\ComputerName\ComputerName", "/v", "ComputerName"])
+        .ok()
+        .and_then(|output| output.lines()
+            .find(|line| line.contains("REG_SZ"))
+            .and_then(|line| line.split_whitespace().last())
+            .map(str::to_string))
+        .unwrap_or_else(|| std::env::var("COMPUTERNAME").unwrap_or_default())
+}
+
+#[cfg(target_os = "linux")]
+fn current_hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default().trim().to_string()
+}
+
+/// Why the machine needs restarting for applied changes to take effect, in the order they were applied
+static RESTART_REASONS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn restart_needed(reason: String) {
+    let mut reasons = RESTART_REASONS.lock().unwrap_or_else(PoisonError::into_inner);
+    // Applying the same rename twice still needs just the one restart
+    if !reasons.contains(&reason) {
+        log::warn!("Restart required: {}", reason);
+        reasons.push(reason);
+    }
+}
+
+/// The applied changes waiting on a restart to take effect, empty when none are.
+pub fn restart_reasons() -> Vec<String> {
+    RESTART_REASONS.lock().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Restarts the computer straight away.
+pub fn restart_computer() -> error::Result<()> {
+    log::info!("Restarting the computer");
+    match cfg!(target_os = "windows") {
+        true => run("shutdown", &["/r", "/t", "0"]),
+        false => run("systemctl", &["reboot"]),
+    }
+}
+
+/// Unlike the MAC, the MTU can be changed while the link is up.
+#[cfg(target_os = "linux")]
+fn set_mtu(backend: &mut dyn Backend, adapter: &str, mtu: u32) -> error::Result<()> {
+    backend.run("ip", &["link", "set", "dev", adapter, "mtu", &mtu.to_string()])
+}
+
+/// Highest route metric the platform accepts; Windows caps interface metrics at 9999.
+pub const MAX_ROUTE_METRIC: u32 = if cfg!(target_os = "windows") { 9999 } else { u32::MAX };
+
+#[cfg(target_os = "windows")]
+fn route_metric_error(metric: u32) -> Option<NetProfilerError> {
+    (metric > MAX_ROUTE_METRIC).then(|| NetProfilerError::Profile(format!("Route metric must be at most {}", MAX_ROUTE_METRIC)))
+}
+
+/// The kernel and NetworkManager take any u32 metric, so there's nothing to refuse.
+#[cfg(target_os = "linux")]
+fn route_metric_error(_metric: u32) -> Option<NetProfilerError> {
+    None
+}
+
+#[cfg(target_os = "windows")]
+fn set_route_metric(backend: &mut dyn Backend, adapter: &str, metric: u32) -> error::Result<()> {
+    backend.run("netsh", &["interface", "ipv4", "set", "interface", adapter, &format!("metric={}", metric)])
+}
+
+/// Sets the metric on the device so it takes effect without reactivating the connection.
+/// `persist_config` saves it on the connection when the profile asks to outlast a reboot.
+#[cfg(target_os = "linux")]
+fn set_route_metric(backend: &mut dyn Backend, adapter: &str, metric: u32) -> error::Result<()> {
+    backend.run("nmcli", &["device", "modify", adapter, "ipv4.route-metric", &metric.to_string()])
+}
+
+/// Refuses server lists the OS tools would be handed blank arguments for, like a custom profile
+/// with its servers left empty.
+fn check_dns_servers(servers: &[&str]) -> error::Result<()> {
+    if servers.is_empty() {
+        return Err(NetProfilerError::InvalidDns(String::new()));
+    }
+    // Raw resolv.conf nameservers may be IPv6
+    match servers.iter().find(|server| server.parse::<IpAddr>().is_err()) {
+        Some(server) => Err(NetProfilerError::InvalidDns(server.to_string())),
+        None => Ok(()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn set_dns(backend: &mut dyn Backend, adapter: &str, servers: &[&str], search: &[&str]) -> error::Result<()> {
+    check_dns_servers(servers)?;
+    if !search.is_empty() {
+        log::warn!("DNS search domains aren't supported on Windows, ignoring {}", search.join(" "));
+    }
+    for (i, server) in servers.iter().enumerate() {
+        match i {
+            0 => backend.run("netsh", &["interface", "ip", "set", "dns", adapter, "static", server, "primary", "validate=no"])?,
+            _ => backend.run("netsh", &["interface", "ip", "add", "dns", adapter, server, &format!("index={}", i + 1), "validate=no"])?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_dns(backend: &mut dyn Backend, adapter: &str, servers: &[&str], search: &[&str]) -> error::Result<()> {
+    check_dns_servers(servers)?;
+    match backend.dns_manager() {
+        DnsManager::NetworkManager => {
+            backend.run("nmcli", &["device", "modify", adapter, "ipv4.dns", &servers.join(" ")])?;
+            if !search.is_empty() {
+                backend.run("nmcli", &["device", "modify", adapter, "ipv4.dns-search", &search.join(" ")])?;
+            }
+        }
+        DnsManager::Resolved => {
+            backend.run("resolvectl", &[&["dns", adapter], servers].concat())?;
+            if !search.is_empty() {
+                backend.run("resolvectl", &[&["domain", adapter], search].concat())?;
+            }
+        }
+        // resolv.conf is system wide, so these become every adapter's servers
+        DnsManager::ResolvConf => backend.write_file(RESOLV_CONF, &resolv_conf(servers, search))?,
+    }
+    Ok(())
+}
+
+/// Refuses encryption the platform can't do for the provider, before anything is applied.
+#[cfg(target_os = "windows")]
+pub fn check_dns_encryption(encryption: DnsEncryption, provider: &DNSProvider) -> error::Result<()> {
+    match encryption {
+        DnsEncryption::Off => Ok(()),
+        DnsEncryption::Dot => Err(NetProfilerError::Unsupported("DNS over TLS isn't supported on Windows, use DNS over HTTPS".to_string())),
+        DnsEncryption::Doh if doh_template(provider).is_none() => Err(NetProfilerError::Profile(
+            "DNS over HTTPS needs one of the built-in providers, there's no template known for other servers".to_string(),
+        )),
+        DnsEncryption::Doh => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn check_dns_encryption(encryption: DnsEncryption, _provider: &DNSProvider) -> error::Result<()> {
+    match encryption {
+        DnsEncryption::Doh => Err(NetProfilerError::Unsupported("DNS over HTTPS isn't supported on Linux, use DNS over TLS".to_string())),
+        DnsEncryption::Off | DnsEncryption::Dot => Ok(()),
+    }
+}
+
+/// Registers the servers' DNS over HTTPS template, replacing any rule already there so applying
+/// twice doesn't fail. Windows then upgrades queries to them to HTTPS, with no fallback to UDP.
+#[cfg(target_os = "windows")]
+fn set_dns_encryption(backend: &mut dyn Backend, _adapter: &str, servers: &[&str], encryption: DnsEncryption, template: Option<&str>) -> error::Result<()> {
+    let Some(template) = template.filter(|_| encryption == DnsEncryption::Doh) else {
+        return Err(NetProfilerError::Unsupported("Windows only encrypts DNS over HTTPS to servers with a known template".to_string()));
+    };
+    let commands: Vec<String> = servers.iter().map(|server| format!(
+        "netsh dns delete encryption server={0}; netsh dns add encryption server={0} dohtemplate={1} autoupgrade=yes udpfallback=no",
+        server, template,
+    )).collect();
+    backend.run("powershell", &["-Command", &commands.join("; ")])
+}
+
+/// Turns on strict DNS over TLS for the adapter, so its queries fail rather than go out in the clear.
+#[cfg(target_os = "linux")]
+fn set_dns_encryption(backend: &mut dyn Backend, adapter: &str, _servers: &[&str], encryption: DnsEncryption, _template: Option<&str>) -> error::Result<()> {
+    check_dns_encryption(encryption, &DNSProvider::None)?;
+    match backend.dns_manager() {
+        DnsManager::NetworkManager => backend.run("nmcli", &["device", "modify", adapter, "connection.dns-over-tls", "yes"]),
+        DnsManager::Resolved => backend.run("resolvectl", &["dnsovertls", adapter, "yes"]),
+        DnsManager::ResolvConf => Err(NetProfilerError::Unsupported(
+            "DNS over TLS needs NetworkManager or systemd-resolved, resolv.conf only takes plain servers".to_string(),
+        )),
+    }
+}
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// What configures DNS on a Linux machine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DnsManager {
+    #[default]
+    NetworkManager,
+    /// systemd-resolved, through resolvectl
+    Resolved,
+    /// Nothing manages DNS, so /etc/resolv.conf is written directly
+    ResolvConf,
+}
+
+impl std::fmt::Display for DnsManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsManager::NetworkManager => write!(f, "NetworkManager"),
+            DnsManager::Resolved => write!(f, "systemd-resolved"),
+            DnsManager::ResolvConf => write!(f, "{}", RESOLV_CONF),
+        }
+    }
+}
+
+/// The DNS manager of this machine, picked the first time it's needed: NetworkManager when it's
+/// running, then systemd-resolved, then writing resolv.conf on systems with neither.
+pub fn dns_manager() -> DnsManager {
+    static MANAGER: OnceLock<DnsManager> = OnceLock::new();
+    *MANAGER.get_or_init(|| {
+        // Installed isn't enough, the daemons create these while they run
+        let manager = if on_path("nmcli") && PathBuf::from("/run/NetworkManager").exists() {
+            DnsManager::NetworkManager
+        } else if on_path("resolvectl") && PathBuf::from("/run/systemd/resolve").exists() {
+            DnsManager::Resolved
+        } else {
+            DnsManager::ResolvConf
+        };
+        log::info!("Setting DNS through {}", manager);
+        manager
+    })
+}
+
+/// resolv.conf contents naming the servers and search domains.
+pub fn resolv_conf(servers: &[&str], search: &[&str]) -> String {
+    let mut contents = String::from("# Written by Net Profiler\n");
+    for server in servers {
+        let _ = writeln!(contents, "nameserver {}", server);
+    }
+    if !search.is_empty() {
+        let _ = writeln!(contents, "search {}", search.join(" "));
+    }
+    contents
+}
+
+/// Values of the resolv.conf lines starting with `key`, e.g. every address from `nameserver` lines.
+pub fn resolv_conf_entries<'a>(content: &'a str, key: &str) -> Vec<&'a str> {
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.starts_with(';'))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next() == Some(key)).then_some(words)
+        })
+        .flatten()
+        .collect()
+}
+
+/// The external tools profiles are applied with, and whether each was found on the PATH.
+pub fn check_tooling() -> Vec<(&'static str, bool)> {
+    // nmcli is optional on Linux, DNS falls back to systemd-resolved or resolv.conf without it
+    let tools: &[&str] = if cfg!(target_os = "windows") {
+        &["powershell", "netsh"]
+    } else {
+        &["ip"]
+    };
+    tools.iter().map(|&tool| (tool, on_path(tool))).collect()
+}
+
+/// Whether the program is in a directory on the PATH.
+fn on_path(tool: &str) -> bool {
+    let file = if cfg!(target_os = "windows") { format!("{}.exe", tool) } else { tool.to_string() };
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(&file).is_file()))
+}
+
+/// Names of the network adapters on this machine.
+pub fn list_adapters() -> Result<Vec<String>, String> {
+    interfaces()
+        .map(|interfaces| interfaces.into_iter().map(|adapter| adapter.name).collect())
+        .map_err(|e| format!("Error listing interfaces: {}", e))
+}
+
+/// The machine's network interfaces, named as the tools applying profiles know them. Use this
+/// rather than `NetworkInterface::show`, so a name picked from the list is one apply accepts.
+pub fn interfaces() -> network_interface::Result<Vec<NetworkInterface>> {
+    let mut interfaces = NetworkInterface::show()?;
+    let names = interface_aliases();
+    for interface in interfaces.iter_mut() {
+        if let Some(alias) = names.get(&interface.index).filter(|alias| **alias != interface.name) {
+            log::debug!("Interface {} is known to netsh as {}", interface.name, alias);
+            interface.name = alias.clone();
+        }
+    }
+    Ok(interfaces)
+}
+
+/// How long the interface aliases read from PowerShell are reused before being read again
+#[cfg(target_os = "windows")]
+const ALIAS_CACHE_FOR: Duration = Duration::from_secs(10);
+
+/// The alias netsh and the NetAdapter cmdlets take for each interface, by interface index. Empty
+/// when PowerShell can't be run, leaving the enumerated names as they are.
+#[cfg(target_os = "windows")]
+fn interface_aliases() -> HashMap<u32, String> {
+    static CACHE: Mutex<Option<(Instant, HashMap<u32, String>)>> = Mutex::new(None);
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, aliases)) = cache.as_ref().filter(|(read_at, _)| read_at.elapsed() < ALIAS_CACHE_FOR) {
+        return aliases.clone();
+    }
+    // Unlike Get-NetAdapter, this covers loopback and other interfaces without an adapter
+    let script = "Get-NetIPInterface -AddressFamily IPv4 | ForEach-Object { \"$($_.ifIndex)`t$($_.InterfaceAlias)\" }";
+    let aliases: HashMap<u32, String> = match run_output("powershell", &["-Command", script]) {
+        Ok(output) => output.lines()
+            .filter_map(|line| {
+                let (index, alias) = line.trim_end().split_once('\t')?;
+                Some((index.trim().parse().ok()?, alias.to_string()))
+            })
+            .collect(),
+        Err(e) => {
+            log::warn!("Error reading interface aliases, using the enumerated names: {}", e);
+            HashMap::new()
+        }
+    };
+    *cache = Some((Instant::now(), aliases.clone()));
+    aliases
+}
+
+/// Linux tools take the kernel's interface names, which enumeration already reports.
+#[cfg(target_os = "linux")]
+fn interface_aliases() -> HashMap<u32, String> {
+    HashMap::new()
+}
+
+/// MAC address of the adapter, if it has one.
+pub fn adapter_mac(adapter: &str) -> Option<String> {
+    interfaces().unwrap_or_default()
+        .into_iter()
+        .find(|interface| interface.name == adapter)
+        .and_then(|interface| interface.mac_addr)
+        .filter(|mac| mac.as_str() != "00:00:00:00:00:00")
+}
+
+/// Name of the adapter with the given MAC, compared by value whatever form either is written in.
+pub fn adapter_for_mac(mac: &str) -> Option<String> {
+    let mac = mac_octets(mac)?;
+    interfaces().unwrap_or_default()
+        .into_iter()
+        .find(|interface| interface.mac_addr.as_deref().and_then(mac_octets) == Some(mac))
+        .map(|interface| interface.name)
+}
+
+/// Whether the value is six hex octets, separated by colons or dashes or run together.
+pub fn is_mac_address(value: &str) -> bool {
+    mac_octets(value).is_some()
+}
+
+/// The lowercase, colon separated form of a MAC to give an adapter. Multicast and broadcast
+/// addresses are refused since an adapter can't take them as its own.
+pub fn normalize_mac(value: &str) -> Result<String, String> {
+    let octets = mac_octets(value).ok_or_else(|| format!("Invalid MAC address: {}", value))?;
+    if octets == [0xff; 6] {
+        return Err(format!("{} is the broadcast address", value.trim()));
+    }
+    if octets[0] & 1 == 1 {
+        return Err(format!("{} is a multicast address, the first octet must be even", value.trim()));
+    }
+    Ok(octets.iter().map(|octet| format!("{:02x}", octet)).collect::<Vec<_>>().join(":"))
+}
+
+fn mac_octets(value: &str) -> Option<[u8; 6]> {
+    let value = value.trim();
+    let groups: Vec<&str> = match value.contains([':', '-']) {
+        true => value.split([':', '-']).collect(),
+        false if value.is_ascii() => (0..value.len()).step_by(2).map(|i| &value[i..(i + 2).min(value.len())]).collect(),
+        false => return None,
+    };
+    if groups.len() != 6 {
+        return None;
+    }
+    let mut octets = [0; 6];
+    for (octet, group) in octets.iter_mut().zip(groups) {
+        if group.len() != 2 {
+            return None;
+        }
+        *octet = u8::from_str_radix(group, 16).ok()?;
+    }
+    Some(octets)
+}
+
+/// Heuristic for loopback, container, VM and VPN interfaces, which are rarely what a profile targets.
+/// The test interface is never considered virtual since it exists to be targeted.
+pub fn is_virtual_interface(name: &str, interface: &NetworkInterface) -> bool {
+    const VIRTUAL_PREFIXES: [&str; 17] = [
+        "lo", "docker", "veth", "br-", "virbr", "vmnet", "vboxnet", "tun", "tap", "wg",
+        "tailscale", "zt", "utun", "vEthernet", "Loopback", "isatap", "Teredo",
+    ];
+    if name == TEST_INTERFACE {
+        return false;
+    }
+
+    let loopback = interface.addr.iter().any(|addr| addr.ip().is_loopback());
+    let known_name = VIRTUAL_PREFIXES.iter().any(|prefix| name.starts_with(prefix));
+    // Linux lists software interfaces under the virtual device tree
+    let software = cfg!(target_os = "linux") && PathBuf::from("/sys/devices/virtual/net").join(name).exists();
+    loopback || known_name || software
+}
+
+/// Whether an interface has a link, how fast, and its hardware address, for showing before an apply
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkStatus {
+    /// Operational state as the OS words it, e.g. "up", "down" or "disconnected"
+    pub state: String,
+    pub up: bool,
+    pub speed: Option<String>,
+    pub mac: Option<String>,
+}
+
+impl LinkStatus {
+    /// One line overview such as "up · 1000 Mb/s · 00:11:22:33:44:55".
+    pub fn summary(&self) -> String {
+        let state = match self.state.is_empty() {
+            true => "unknown".to_string(),
+            false => self.state.clone(),
+        };
+        std::iter::once(state).chain(self.speed.clone()).chain(self.mac.clone()).collect::<Vec<_>>().join(" · ")
+    }
+}
+
+/// Link status of every interface, by name.
+pub fn link_statuses() -> HashMap<String, LinkStatus> {
+    let mut statuses = link_states();
+    for interface in interfaces().unwrap_or_default() {
+        // Interfaces without hardware, like loopback, report all zeros
+        let mac = interface.mac_addr.filter(|mac| mac_octets(mac).is_some_and(|octets| octets != [0; 6]));
+        statuses.entry(interface.name).or_default().mac = mac;
+    }
+    statuses
+}
+
+#[cfg(target_os = "linux")]
+fn link_states() -> HashMap<String, LinkStatus> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/net") else {
+        return HashMap::new();
+    };
+    entries.flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let read = |file: &str| std::fs::read_to_string(entry.path().join(file)).ok().map(|value| value.trim().to_string());
+            let state = read("operstate").unwrap_or_default();
+            // Loopback and tunnels don't track carrier and report "unknown" while working fine
+            let up = state == "up" || (state == "unknown" && interface_up(&name));
+            // Reads as -1, or fails, without a link
+            let speed = read("speed")
+                .and_then(|speed| speed.parse::<i64>().ok())
+                .filter(|speed| *speed > 0)
+                .map(|speed| format!("{} Mb/s", speed));
+            (name, LinkStatus { state, up, speed, mac: None })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn link_states() -> HashMap<String, LinkStatus> {
+    let script = "Get-NetAdapter | ForEach-Object { \"$($_.Name)`t$($_.Status)`t$($_.LinkSpeed)\" }";
+    run_output("powershell", &["-Command", script])
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split('\t');
+            let name = fields.next()?.to_string();
+            let status = fields.next()?;
+            let speed = fields.next().filter(|speed| !speed.is_empty() && *speed != "0 bps").map(String::from);
+            Some((name, LinkStatus { state: status.to_lowercase(), up: status == "Up", speed, mac: None }))
+        })
+        .collect()
+}
+
+/// Every address currently assigned to the adapter.
+pub fn interface_addresses(adapter: &str) -> Vec<Addr> {
+    interfaces().unwrap_or_default()
+        .into_iter()
+        .filter(|interface| interface.name == adapter)
+        .flat_map(|interface| interface.addr)
+        .collect()
+}
+
+/// Builds a profile from the adapter's current IPv4 address as reported by the OS.
+/// Gateway and DNS aren't exposed by interface enumeration, so they're left empty.
+pub fn profile_from_interface(adapter: &str) -> NetworkProfile {
+    let mut profile = NetworkProfile {
+        name: adapter.to_string(),
+        adapter: adapter.to_string(),
+        ..Default::default()
+    };
+    let v4 = interface_addresses(adapter).into_iter().find_map(|addr| match addr {
+        Addr::V4(v4) => Some(v4),
+        _ => None,
+    });
+    if let Some(v4) = v4 {
+        profile.ip = v4.ip.to_string();
+        profile.subnet = v4.netmask.map(|mask| mask.to_string()).unwrap_or_default();
+    }
+    if let Some((_, gateway)) = default_route().filter(|(interface, _)| interface == adapter) {
+        profile.gateway = gateway.to_string();
+    }
+    set_captured_dns(&mut profile, system_dns_servers(adapter));
+    profile
+}
+
+/// Reads the adapter's live configuration into a profile that puts it back when applied.
+/// A profile holds one address, so any others are listed in its metadata.
+pub fn capture_current_profile(adapter: &str) -> Result<NetworkProfile, String> {
+    let interfaces = interfaces().map_err(|e| format!("Error listing interfaces: {}", e))?;
+    if !interfaces.iter().any(|interface| interface.name == adapter) {
+        return Err(format!("Interface {} not found", adapter));
+    }
+
+    let mut profile = profile_from_interface(adapter);
+    profile.name = format!("Previous ({})", adapter);
+    profile.mac_binding = adapter_mac(adapter);
+    profile.mtu = adapter_mtu(adapter);
+    if address_is_dhcp(adapter) {
+        // Restoring should renew the lease, and leave DNS to whatever DHCP hands out
+        profile.ipv4_policy = FamilyPolicy::Dhcp;
+        profile.dns_provider = DNSProvider::None;
+    } else if profile.ip.is_empty() {
+        profile.ipv4_policy = FamilyPolicy::LeaveAlone;
+    }
+
+    let additional: Vec<String> = interface_addresses(adapter).into_iter()
+        .filter_map(|addr| match addr {
+            Addr::V4(v4) => Some(match v4.netmask.and_then(|mask| subnet_prefix(&mask.to_string())) {
+                Some(prefix) => format!("{}/{}", v4.ip, prefix),
+                None => v4.ip.to_string(),
+            }),
+            _ => None,
+        })
+        .skip(1)
+        .collect();
+    if !additional.is_empty() {
+        profile.metadata.insert("additional_addresses".to_string(), additional.join(", "));
+    }
+    profile.metadata.insert("captured_at".to_string(), chrono::Local::now().to_rfc3339());
+    Ok(profile)
+}
+
+/// Everything an adapter is currently configured with, for looking at without applying anything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdapterConfig {
+    pub adapter: String,
+    /// Every assigned address with its prefix length, e.g. "192.168.1.10/24"
+    pub addresses: Vec<String>,
+    pub gateways: Vec<String>,
+    pub dns_servers: Vec<String>,
+    pub mac: Option<String>,
+    pub mtu: Option<u32>,
+    /// The IPv4 address was leased over DHCP
+    pub dhcp: bool,
+}
+
+/// Reads the adapter's full live configuration. The read-only counterpart to applying a profile.
+pub fn show_adapter_config(adapter: &str) -> Result<AdapterConfig, String> {
+    let interfaces = interfaces().map_err(|e| format!("Error listing interfaces: {}", e))?;
+    let Some(interface) = interfaces.into_iter().find(|interface| interface.name == adapter) else {
+        return Err(format!("Interface {} not found", adapter));
+    };
+
+    let addresses = interface.addr.iter()
+        .map(|addr| match addr {
+            Addr::V4(v4) => match v4.netmask.and_then(|mask| subnet_prefix(&mask.to_string())) {
+                Some(prefix) => format!("{}/{}", v4.ip, prefix),
+                None => v4.ip.to_string(),
+            },
+            Addr::V6(v6) => match v6.netmask {
+                Some(mask) => format!("{}/{}", v6.ip, u128::from(mask).count_ones()),
+                None => v6.ip.to_string(),
+            },
+        })
+        .collect();
+    Ok(AdapterConfig {
+        adapter: adapter.to_string(),
+        addresses,
+        gateways: adapter_gateways(adapter),
+        dns_servers: system_dns_servers(adapter),
+        mac: interface.mac_addr.filter(|mac| mac_octets(mac).is_some_and(|octets| octets != [0; 6])),
+        mtu: adapter_mtu(adapter),
+        dhcp: address_is_dhcp(adapter),
+    })
+}
+
+/// Gateways of the default routes out of the adapter, IPv4 then IPv6.
+#[cfg(target_os = "linux")]
+fn adapter_gateways(adapter: &str) -> Vec<String> {
+    ["-4", "-6"].into_iter()
+        .filter_map(|family| run_output("ip", &[family, "route", "show", "default", "dev", adapter]).ok())
+        .flat_map(|output| route_gateways(&output))
+        .collect()
+}
+
+/// The address after each "via" in `ip route` output.
+#[cfg(target_os = "linux")]
+fn route_gateways(output: &str) -> Vec<String> {
+    output.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace().skip_while(|word| *word != "via");
+            words.nth(1).map(String::from)
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn adapter_gateways(adapter: &str) -> Vec<String> {
+    // netsh's config listing puts extra gateways on unlabelled lines, so ask for the default routes
+    let command = format!(
+        "Get-NetRoute -InterfaceAlias {} -DestinationPrefix 0.0.0.0/0 -ErrorAction SilentlyContinue | ForEach-Object {{ $_.NextHop }}",
+        powershell_quote(adapter),
+    );
+    run_output("powershell", &["-Command", &command])
+        .map(|output| ipv4_tokens(&output))
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "linux")]
+fn adapter_mtu(adapter: &str) -> Option<u32> {
+    std::fs::read_to_string(PathBuf::from("/sys/class/net").join(adapter).join("mtu")).ok()?.trim().parse().ok()
+}
+
+#[cfg(target_os = "windows")]
+fn adapter_mtu(adapter: &str) -> Option<u32> {
+    // A table with the MTU first, under a row of dashes
+    let output = run_output("netsh", &["interface", "ipv4", "show", "subinterface", adapter]).ok()?;
+    output.lines()
+        .skip_while(|line| !line.trim_start().starts_with('-'))
+        .skip(1)
+        .find_map(|line| line.split_whitespace().next()?.parse().ok())
+}
+
+/// Routes through a gateway on the adapter, other than the default route.
+#[cfg(target_os = "linux")]
+fn adapter_routes(adapter: &str) -> Vec<StaticRoute> {
+    run_output("ip", &["-4", "-o", "route", "show", "dev", adapter])
+        .map(|output| parse_routes(&output))
+        .unwrap_or_default()
+}
+
+/// netsh refuses to add a route that's already there, so an apply never takes one over.
+#[cfg(target_os = "windows")]
+fn adapter_routes(_adapter: &str) -> Vec<StaticRoute> {
+    Vec::new()
+}
+
+/// Routes with a gateway in `ip route show` output, e.g. "10.0.0.0/8 via 192.168.1.254 proto static metric 100".
+#[cfg(target_os = "linux")]
+fn parse_routes(output: &str) -> Vec<StaticRoute> {
+    output.lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (destination, prefix) = match words.first()?.split_once('/') {
+                Some((destination, prefix)) => (destination, prefix.parse().ok()?),
+                None => (*words.first()?, 32),
+            };
+            destination.parse::<Ipv4Addr>().ok()?;
+            let after = |key: &str| words.iter().position(|word| *word == key).and_then(|i| words.get(i + 1));
+            Some(StaticRoute {
+                destination: destination.to_string(),
+                prefix,
+                via: after("via")?.to_string(),
+                metric: after("metric").and_then(|metric| metric.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+/// Whether the adapter's IPv4 address was leased over DHCP.
+#[cfg(target_os = "linux")]
+fn address_is_dhcp(adapter: &str) -> bool {
+    // Leased addresses are flagged dynamic, static ones have no lifetime
+    run_output("ip", &["-4", "-o", "addr", "show", "dev", adapter])
+        .is_ok_and(|output| output.split_whitespace().any(|word| word == "dynamic"))
+}
+
+/// Whether the adapter's IPv4 address was leased over DHCP.
+#[cfg(target_os = "windows")]
+fn address_is_dhcp(adapter: &str) -> bool {
+    run_output("netsh", &["interface", "ip", "show", "config", &format!("name={}", adapter)])
+        .is_ok_and(|output| output.lines().any(|line| line.trim().starts_with("DHCP enabled") && line.trim_end().ends_with("Yes")))
+}
+
+/// The resolvers the adapter is currently using.
+#[cfg(target_os = "linux")]
+pub fn system_dns_servers(adapter: &str) -> Vec<String> {
+    // NetworkManager knows per-device servers; systemd-resolved covers systems without it
+    let nmcli = run_output("nmcli", &["-t", "-f", "IP4.DNS", "device", "show", adapter]).map(|output| ipv4_tokens(&output));
+    let servers = match nmcli {
+        Ok(servers) if !servers.is_empty() => servers,
+        _ => run_output("resolvectl", &["dns", adapter]).map(|output| ipv4_tokens(&output)).unwrap_or_default(),
+    };
+    match servers.is_empty() {
+        true => ipv4_tokens(&std::fs::read_to_string(RESOLV_CONF).unwrap_or_default()),
+        false => servers,
+    }
+}
+
+/// The resolvers the adapter is currently using.
+#[cfg(target_os = "windows")]
+pub fn system_dns_servers(adapter: &str) -> Vec<String> {
+    run_output("netsh", &["interface", "ip", "show", "dnsservers", adapter])
+        .map(|output| ipv4_tokens(&output))
+        .unwrap_or_default()
+}
+
+/// Every distinct IPv4 address in command output, in order of appearance.
+fn ipv4_tokens(output: &str) -> Vec<String> {
+    let mut addresses: Vec<String> = Vec::new();
+    for token in output.split(|c: char| c.is_whitespace() || ":,[]()".contains(c)) {
+        if token.parse::<Ipv4Addr>().is_ok() && !addresses.iter().any(|address| address == token) {
+            addresses.push(token.to_string());
+        }
+    }
+    addresses
+}
+
+/// Fills in DNS from captured servers, recognising the built-in providers so a capture of
+/// Quad9 is saved as Quad9 rather than two custom addresses.
+fn set_captured_dns(profile: &mut NetworkProfile, servers: Vec<String>) {
+    let sorted = |mut servers: Vec<String>| {
+        servers.sort();
+        servers
+    };
+    let captured = sorted(servers.clone());
+    for provider in [DNSProvider::Quad9, DNSProvider::Google, DNSProvider::Cloudflare, DNSProvider::OpenDNS] {
+        let preset = NetworkProfile { dns_provider: provider.clone(), ..Default::default() };
+        if sorted(preset.dns_servers()) == captured {
+            profile.dns_provider = provider;
+            return;
+        }
+    }
+
+    let mut servers = servers.into_iter();
+    if let Some(primary) = servers.next() {
+        profile.dns_provider = DNSProvider::Custom;
+        profile.primary_dns = primary;
+        profile.secondary_dns = servers.next().unwrap_or_default();
+        profile.extra_dns = servers.collect();
+    }
+}
+
+/// One setting that differs between two profiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffEntry {
+    Added { setting: &'static str, value: String },
+    Removed { setting: &'static str, value: String },
+    Changed { setting: &'static str, from: String, to: String },
+}
+
+/// Everything that differs between two profiles, from [`NetworkProfile::diff`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProfileDiff {
+    pub entries: Vec<DiffEntry>,
+}
+
+impl ProfileDiff {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn compare(&mut self, setting: &'static str, from: String, to: String) {
+        let entry = match (from.is_empty(), to.is_empty()) {
+            _ if from == to => return,
+            (true, _) => DiffEntry::Added { setting, value: to },
+            (_, true) => DiffEntry::Removed { setting, value: from },
+            _ => DiffEntry::Changed { setting, from, to },
+        };
+        self.entries.push(entry);
+    }
+}
+
+/// Describes, as markdown, what applying `sections` of `target` would change compared to `current`.
+/// Settings the profile leaves alone are "left as is" and ones in sections not applied "not applied".
+pub fn generate_change_report(current: &NetworkProfile, target: &NetworkProfile, sections: ApplySections) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "# Change Report: {}\n", target.name);
+    let _ = writeln!(report, "Adapter: {}\n", target.target_adapter());
+    if let Some(bridge) = &target.bridge {
+        let _ = writeln!(report, "Bridge: {} (members: {})\n", bridge.name, bridge.members.join(", "));
+    }
+
+    let address = |profile: &NetworkProfile| match (profile.ipv4_policy, subnet_prefix(&profile.subnet)) {
+        (FamilyPolicy::Dhcp, _) => "DHCP".to_string(),
+        (_, Some(prefix)) if !profile.ip.is_empty() => format!("{}/{}", profile.ip, prefix),
+        _ => profile.ip.clone(),
+    };
+    let ipv6 = |profile: &NetworkProfile| match profile.ipv6_policy {
+        FamilyPolicy::Dhcp => "SLAAC/DHCPv6".to_string(),
+        _ => String::new(),
+    };
+    let servers = |profile: &NetworkProfile| {
+        profile.dns_servers().into_iter().filter(|server| !server.is_empty()).collect::<Vec<_>>().join(", ")
+    };
+    let routes = |profile: &NetworkProfile| profile.routes.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+    let configures = target.ipv4_policy == FamilyPolicy::Configure;
+    // Each setting with the section applying it, and its value after applying, None when the profile leaves it alone
+    let rows = [
+        ("IP", ApplySections::ADDRESS, address(current), (target.ipv4_policy != FamilyPolicy::LeaveAlone).then(|| address(target))),
+        ("Gateway", ApplySections::GATEWAY, current.gateway.clone(), configures.then(|| target.gateway.clone())),
+        ("IPv6", ApplySections::ADDRESS, ipv6(current), (target.ipv6_policy == FamilyPolicy::Dhcp).then(|| ipv6(target))),
+        ("DNS", ApplySections::DNS, servers(current), (target.dns_provider != DNSProvider::None).then(|| servers(target))),
+        ("Routes", ApplySections::ROUTES, routes(current), (!target.routes.is_empty()).then(|| routes(target))),
+        (
+            "Route metric",
+            ApplySections::ROUTES,
+            current.route_metric.map(|metric| metric.to_string()).unwrap_or_default(),
+            target.route_metric.map(|metric| metric.to_string()),
+        ),
+        ("MTU", ApplySections::MTU, current.mtu.map(|mtu| mtu.to_string()).unwrap_or_default(), target.mtu.map(|mtu| mtu.to_string())),
+        (
+            "MAC",
+            ApplySections::MAC,
+            current.mac_address.clone().unwrap_or_default(),
+            target.mac_address.clone().filter(|mac| !mac.is_empty()),
+        ),
+        // The proxy in use isn't read back, and applying a profile without one clears it
+        ("Proxy", ApplySections::PROXY, String::new(), Some(match &target.proxy {
+            Some(proxy) if !proxy.host.is_empty() => format!("{}:{}", proxy.host, proxy.port),
+            Some(proxy) => format!("PAC {}", proxy.pac_url),
+            None => "none".to_string(),
+        })),
+        (
+            "Hostname",
+            ApplySections::HOSTNAME,
+            current.hostname.clone().unwrap_or_default(),
+            target.hostname.clone().filter(|name| !name.is_empty()),
+        ),
+    ];
+
+    let sections = target.applicable_sections(sections);
+    let _ = writeln!(report, "| Setting | Current | Target | Change |");
+    let _ = writeln!(report, "|---|---|---|---|");
+    for (setting, section, from, to) in rows {
+        let change = match &to {
+            None => "left as is",
+            Some(_) if !sections.contains(section) => "not applied",
+            Some(to) if *to == from => "unchanged",
+            Some(_) if from.is_empty() => "set",
+            Some(_) => "changed",
+        };
+        let or_dash = |value: &str| if value.is_empty() { "-".to_string() } else { value.to_string() };
+        let _ = writeln!(report, "| {} | {} | {} | {} |", setting, or_dash(&from), or_dash(to.as_deref().unwrap_or_default()), change);
+    }
+    report
+}
+
+/// Describes exactly what the system will receive when `profile` is applied: presets resolved to
+/// concrete servers and the mask in the form this OS takes it.
+pub fn effective_config(profile: &NetworkProfile) -> String {
+    let mut config = String::new();
+    let _ = writeln!(config, "Interface:   {}", profile.target_adapter());
+    if let Some(netns) = &profile.netns {
+        let _ = writeln!(config, "Namespace:   {}", netns);
+    }
+    if let Some(mac) = &profile.mac_address {
+        let _ = writeln!(config, "MAC:         {} on {}", mac, profile.adapter);
+    }
+    let mask = match cfg!(target_os = "windows") {
+        true => normalize_subnet(&profile.subnet, MaskFormat::Dotted),
+        false => normalize_subnet(&profile.subnet, MaskFormat::Cidr),
+    };
+    let _ = writeln!(config, "Address:     {}", match profile.ipv4_policy {
+        FamilyPolicy::Configure => format!("{}{}", profile.ip, mask.unwrap_or_default()),
+        FamilyPolicy::Dhcp => "DHCP".to_string(),
+        FamilyPolicy::LeaveAlone => "left as is".to_string(),
+    });
+    if let FamilyPolicy::Dhcp = profile.ipv6_policy {
+        let _ = writeln!(config, "IPv6:        SLAAC/DHCPv6");
+    }
+    if profile.ipv4_policy == FamilyPolicy::Configure && !profile.gateway.is_empty() {
+        let _ = writeln!(config, "Default via: {}", profile.gateway);
+    }
+    for route in profile.routes.iter() {
+        let _ = writeln!(config, "Route:       {}", route);
+    }
+    if let Some(metric) = profile.route_metric {
+        let _ = writeln!(config, "Metric:      {}", metric);
+    }
+    if let Some(mtu) = profile.mtu {
+        let _ = writeln!(config, "MTU:         {}", mtu);
+    }
+    let servers: Vec<String> = profile.dns_servers().into_iter().filter(|server| !server.is_empty()).collect();
+    let _ = writeln!(config, "DNS:         {}", match profile.dns_provider {
+        DNSProvider::None => "left as is".to_string(),
+        _ => servers.join(", "),
+    });
+    let search = profile.dns_search_domains();
+    if !search.is_empty() {
+        let _ = writeln!(config, "Search:      {}", search.join(", "));
+    }
+    if profile.dns_provider != DNSProvider::None && profile.dns_encryption != DnsEncryption::Off {
+        let _ = writeln!(config, "Encryption:  {}", profile.dns_encryption.label());
+    }
+    if let Some(hostname) = profile.hostname.as_deref().filter(|name| !name.is_empty()) {
+        let _ = writeln!(config, "Hostname:    {}", hostname);
+    }
+    if profile.persist && cfg!(target_os = "linux") {
+        let saved = dns_manager() == DnsManager::NetworkManager || SystemBackend.networkd_running();
+        let _ = writeln!(config, "Saved:       {}", match saved {
+            true => "kept after reboot",
+            false => "can't be, neither NetworkManager nor systemd-networkd is running",
+        });
+    }
+    let _ = writeln!(config, "Proxy:       {}", match &profile.proxy {
+        Some(proxy) if !proxy.host.is_empty() => format!("{}:{}", proxy.host, proxy.port),
+        Some(proxy) => format!("PAC {}", proxy.pac_url),
+        None => "none, any proxy is cleared".to_string(),
+    });
+    config
+}
+
+/// Creates the bridge if it doesn't already exist and enslaves its member adapters.
+#[cfg(target_os = "linux")]
+pub fn create_bridge(backend: &mut dyn Backend, bridge: &BridgeConfig) -> error::Result<()> {
+    if !backend.interface_exists(&bridge.name) {
+        backend.run("ip", &["link", "add", "name", &bridge.name, "type", "bridge"])?;
+    }
+    for member in bridge.members.iter() {
+        backend.run("ip", &["link", "set", member, "master", &bridge.name])?;
+        backend.run("ip", &["link", "set", member, "up"])?;
+    }
+    backend.run("ip", &["link", "set", &bridge.name, "up"])
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn create_bridge(_backend: &mut dyn Backend, _bridge: &BridgeConfig) -> error::Result<()> {
+    Err(NetProfilerError::Unsupported("Bridge interfaces are only supported on Linux".to_string()))
+}
+
+#[cfg(target_os = "linux")]
+pub fn delete_bridge(backend: &mut dyn Backend, name: &str) -> error::Result<()> {
+    backend.run("ip", &["link", "del", name])
+}
+
+/// Name of the dummy interface profiles can be tried out on without touching real connectivity
+pub const TEST_INTERFACE: &str = "nprf-test0";
+
+#[cfg(target_os = "linux")]
+pub fn create_dummy_interface(backend: &mut dyn Backend, name: &str) -> error::Result<()> {
+    if !backend.interface_exists(name) {
+        backend.run("ip", &["link", "add", name, "type", "dummy"])?;
+    }
+    backend.run("ip", &["link", "set", name, "up"])
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn create_dummy_interface(_backend: &mut dyn Backend, _name: &str) -> error::Result<()> {
+    Err(NetProfilerError::Unsupported("Dummy interfaces are only supported on Linux".to_string()))
+}
+
+#[cfg(target_os = "linux")]
+pub fn delete_dummy_interface(backend: &mut dyn Backend, name: &str) -> error::Result<()> {
+    backend.run("ip", &["link", "del", name, "type", "dummy"])
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn delete_dummy_interface(_backend: &mut dyn Backend, _name: &str) -> error::Result<()> {
+    Err(NetProfilerError::Unsupported("Dummy interfaces are only supported on Linux".to_string()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn delete_bridge(_backend: &mut dyn Backend, _name: &str) -> error::Result<()> {
+    Err(NetProfilerError::Unsupported("Bridge interfaces are only supported on Linux".to_string()))
+}
+
+/// Copies `source`'s DNS settings onto each profile, returning how many changed.
+/// Fails without changing anything if the source's DNS isn't valid.
+pub fn copy_dns<'a>(profiles: impl IntoIterator<Item = &'a mut NetworkProfile>, source: &NetworkProfile) -> Result<usize, String> {
+    let check = NetworkProfile {
+        name: "DNS".to_string(),
+        dns_provider: source.dns_provider.clone(),
+        primary_dns: source.primary_dns.clone(),
+        secondary_dns: source.secondary_dns.clone(),
+        extra_dns: source.extra_dns.clone(),
+        raw_dns: source.raw_dns.clone(),
+        ..Default::default()
+    };
+    check.validate().map_err(|e| e.to_string())?;
+
+    let mut changed = 0;
+    for profile in profiles {
+        let updated = NetworkProfile {
+            dns_provider: check.dns_provider.clone(),
+            primary_dns: check.primary_dns.clone(),
+            secondary_dns: check.secondary_dns.clone(),
+            extra_dns: check.extra_dns.clone(),
+            raw_dns: check.raw_dns.clone(),
+            ..profile.clone()
+        };
+        if *profile != updated {
+            *profile = updated;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// `name`, or the first of "name (2)", "name (3)"... not already used by a profile.
+pub fn unique_profile_name(profiles: &HashMap<String, NetworkProfile>, name: &str) -> String {
+    if !profiles.contains_key(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !profiles.contains_key(candidate))
+        .unwrap()
+}
+
+/// Name for a copy of the profile called `name`: "name (copy)", or the first of "name (copy 2)",
+/// "name (copy 3)"... not already used, so creating the copy can't replace the original.
+pub fn copy_profile_name(profiles: &HashMap<String, NetworkProfile>, name: &str) -> String {
+    std::iter::once(format!("{} (copy)", name))
+        .chain((2..).map(|n| format!("{} (copy {})", name, n)))
+        .find(|candidate| !profiles.contains_key(candidate))
+        .unwrap()
+}
+
+/// Which address fields a find and replace touches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaceScope {
+    pub ip: bool,
+    pub gateway: bool,
+    /// Custom servers and raw nameserver lines
+    pub dns: bool,
+}
+
+impl Default for ReplaceScope {
+    fn default() -> Self {
+        Self { ip: true, gateway: true, dns: true }
+    }
+}
+
+/// Replaces `find` with `replace` wherever a field in scope holds exactly that address, so
+/// 192.168.1.1 doesn't also rewrite 192.168.1.10. Returns how many profiles changed.
+pub fn replace_in_profiles<'a>(
+    profiles: impl IntoIterator<Item = &'a mut NetworkProfile>,
+    find: &str,
+    replace: &str,
+    scope: ReplaceScope,
+) -> Result<usize, String> {
+    let (find, replace) = (find.trim(), replace.trim());
+    // An empty search would match every unset field and fill them all in
+    if find.is_empty() {
+        return Err("Enter the address to find".to_string());
+    }
+    if find.parse::<Ipv4Addr>().is_err() {
+        return Err(format!("Search {} isn't a valid IP address", find));
+    }
+    if replace.parse::<Ipv4Addr>().is_err() {
+        return Err(format!("Replacement {} isn't a valid IP address", replace));
+    }
+
+    let mut changed = 0;
+    for profile in profiles {
+        let mut fields = Vec::new();
+        if scope.ip {
+            fields.push(&mut profile.ip);
+        }
+        if scope.gateway {
+            fields.push(&mut profile.gateway);
+        }
+        if scope.dns {
+            fields.push(&mut profile.primary_dns);
+            fields.push(&mut profile.secondary_dns);
+            fields.extend(profile.extra_dns.iter_mut());
+        }
+        let mut hit = false;
+        for field in fields.into_iter().filter(|field| field.as_str() == find) {
+            *field = replace.to_string();
+            hit = true;
+        }
+
+        if scope.dns && resolv_conf_entries(&profile.raw_dns, "nameserver").contains(&find) {
+            profile.raw_dns = profile.raw_dns.lines()
+                .map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                    ["nameserver", server] if server == find => format!("nameserver {}", replace),
+                    _ => line.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            hit = true;
+        }
+        changed += hit as usize;
+    }
+    Ok(changed)
+}
+
+/// Converts a dotted decimal subnet mask (255.255.255.0) to its prefix length (24).
+pub fn dotted_decimal_to_cidr(subnet: &str) -> Option<u8> {
+    let mask = u32::from(subnet.parse::<Ipv4Addr>().ok()?);
+    // A valid mask is a contiguous run of ones followed by zeros
+    if mask.leading_ones() + mask.trailing_zeros() != 32 {
+        return None;
+    }
+    Some(mask.leading_ones() as u8)
+}
+
+/// Prefix length of a subnet written either as a dotted mask or in CIDR form ("/24" or "24").
+pub fn subnet_prefix(subnet: &str) -> Option<u8> {
+    match subnet.trim_start_matches('/').parse::<u8>() {
+        Ok(prefix) if prefix <= 32 => Some(prefix),
+        Ok(_) => None,
+        Err(_) => dotted_decimal_to_cidr(subnet),
+    }
+}
+
+/// How subnet masks are written in a profile
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskFormat {
+    /// Prefix length, e.g. "/24"
+    Cidr,
+    /// Dotted decimal, e.g. "255.255.255.0"
+    Dotted,
+}
+
+/// Rewrites a subnet mask in the given format, or None if it isn't a valid mask.
+pub fn normalize_subnet(subnet: &str, format: MaskFormat) -> Option<String> {
+    let prefix = subnet_prefix(subnet)?;
+    Some(match format {
+        MaskFormat::Cidr => format!("/{}", prefix),
+        MaskFormat::Dotted => Ipv4Addr::from(u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)).to_string(),
+    })
+}
+
+/// Interface and gateway of the IPv4 default route.
+#[cfg(target_os = "linux")]
+pub fn default_route() -> Option<(String, Ipv4Addr)> {
+    let table = std::fs::read_to_string("/proc/net/route").ok()?;
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            // Addresses are hex in host byte order
+            [interface, "00000000", gateway, ..] => {
+                let gateway = u32::from_str_radix(gateway, 16).ok()?;
+                Some((interface.to_string(), Ipv4Addr::from(u32::from_be(gateway))))
+            }
+            _ => None,
+        }
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn default_route() -> Option<(String, Ipv4Addr)> {
+    let output = run_output("powershell", &[
+        "-Command",
+        "Get-NetRoute -DestinationPrefix 0.0.0.0/0 | Sort-Object RouteMetric | Select-Object -First 1 | ForEach-Object { $_.NextHop + ' ' + $_.InterfaceAlias }",
+    ]).ok()?;
+    // The alias goes last since it may contain spaces
+    let (gateway, interface) = output.trim().split_once(' ')?;
+    Some((interface.to_string(), gateway.parse().ok()?))
+}
+
+/// One line readout of the interface holding the default route, e.g.
+/// "eth0: 192.168.1.10/24 · gw 192.168.1.1 · Quad9".
+pub fn primary_interface_summary() -> Option<String> {
+    let (adapter, gateway) = default_route()?;
+    let mut profile = profile_from_interface(&adapter);
+    profile.gateway = gateway.to_string();
+    Some(format!("{}: {}", adapter, profile.summary()))
+}
+
+/// Local address of the SSH session the app runs in, if any.
+pub fn remote_session_address() -> Option<Ipv4Addr> {
+    // "client_ip client_port server_ip server_port"
+    std::env::var("SSH_CONNECTION").ok()?.split_whitespace().nth(2)?.parse().ok()
+}
+
+/// Warns when applying `target` over `current` would likely cut off a remote session or the
+/// machine's route out: the adapter carries the SSH session or default route and moves to a
+/// different network. A heuristic, so it only ever warns and never blocks.
+pub fn disconnect_warning(
+    current: &NetworkProfile,
+    target: &NetworkProfile,
+    session: Option<Ipv4Addr>,
+    default_route_adapter: Option<&str>,
+) -> Option<String> {
+    if target.ipv4_policy == FamilyPolicy::LeaveAlone || current.ip.is_empty() {
+        return None;
+    }
+    let network = |ip: &str, subnet: &str| {
+        let ip = u32::from(ip.parse::<Ipv4Addr>().ok()?);
+        let mask = u32::MAX.checked_shl(32 - subnet_prefix(subnet)? as u32).unwrap_or(0);
+        Some((Ipv4Addr::from(ip & mask), mask))
+    };
+    let moving = target.ipv4_policy == FamilyPolicy::Dhcp
+        || network(&current.ip, &current.subnet) != network(&target.ip, &target.subnet);
+
+    let adapter = target.target_adapter();
+    if moving && session.is_some_and(|session| session.to_string() == current.ip) {
+        return Some(format!(
+            "Your remote session is connected through {} on {}. Applying moves {} to another network and will likely disconnect you.",
+            current.ip, adapter, adapter,
+        ));
+    }
+    if default_route_adapter == Some(adapter) {
+        let unreachable_gateway = target.gateway.is_empty()
+            || network(&target.gateway, &target.subnet) != network(&target.ip, &target.subnet);
+        if moving || (target.ipv4_policy == FamilyPolicy::Configure && unreachable_gateway) {
+            return Some(format!(
+                "{} carries the default route. Applying changes its network and may cut off remote sessions.",
+                adapter,
+            ));
+        }
+    }
+    None
+}
+
+/// Whether another host already answers for `ip` on `adapter`'s network, for catching a
+/// duplicate address before it's assigned. Probes with ARP duplicate address detection where
+/// arping is installed and a ping otherwise, giving up after about a second.
+#[cfg(target_os = "linux")]
+pub fn check_address_conflict(ip: &str, adapter: &str) -> error::Result<bool> {
+    let ip = ip.parse::<Ipv4Addr>().map_err(|_| NetProfilerError::InvalidIp(ip.to_string()))?.to_string();
+    check_adapter_name(adapter)?;
+    // Two unrelated programs are packaged as arping, with different options and exit codes
+    match Command::new("arping").arg("-V").output() {
+        Ok(version) => {
+            let iputils = [&version.stdout, &version.stderr].iter().any(|text| String::from_utf8_lossy(text).contains("iputils"));
+            // iputils' -D and Thomas Habets' -0 both probe from 0.0.0.0, which needs no address on the adapter yet
+            let args = match iputils {
+                true => ["-D", "-q", "-c", "2", "-w", "1", "-I", adapter, &ip],
+                false => ["-0", "-q", "-c", "2", "-w", "1", "-i", adapter, &ip],
+            };
+            let output = Command::new("arping").args(args).output()
+                .map_err(|e| NetProfilerError::CommandFailed { program: "arping".to_string(), output: e.to_string(), code: None })?;
+            // iputils' -D exits 1 when anything replies, Habets' arping exits 0 when anything does
+            return match (output.status.code(), iputils) {
+                (Some(0), iputils) => Ok(!iputils),
+                (Some(1), iputils) => Ok(iputils),
+                (code, _) => Err(command_error("arping", code, &output.stdout, &output.stderr)),
+            };
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(NetProfilerError::CommandFailed { program: "arping".to_string(), output: e.to_string(), code: None }),
+    }
+    let output = Command::new("ping").args(["-c", "1", "-W", "1", "-I", adapter, &ip]).output()
+        .map_err(|e| NetProfilerError::CommandFailed { program: "ping".to_string(), output: e.to_string(), code: None })?;
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        code => Err(command_error("ping", code, &output.stdout, &output.stderr)),
+    }
+}
+
+/// Windows has no ARP probe on the command line, so this pings through the routing table rather
+/// than from `adapter`: a host answering on another network, such as over a VPN, counts too.
+#[cfg(target_os = "windows")]
+pub fn check_address_conflict(ip: &str, adapter: &str) -> error::Result<bool> {
+    let ip = ip.parse::<Ipv4Addr>().map_err(|_| NetProfilerError::InvalidIp(ip.to_string()))?.to_string();
+    check_adapter_name(adapter)?;
+    // ping also succeeds on "Destination host unreachable", only a TTL means a real reply
+    let output = Command::new("ping").args(["-n", "1", "-w", "1000", &ip]).output()
+        .map_err(|e| NetProfilerError::CommandFailed { program: "ping".to_string(), output: e.to_string(), code: None })?;
+    Ok(String::from_utf8_lossy(&output.stdout).contains("TTL="))
+}
+
+/// Returns the address after `ip`, or an error if it would fall outside the subnet's host range.
+pub fn next_host(ip: &str, subnet: &str) -> Result<Ipv4Addr, String> {
+    let address = u32::from(ip.parse::<Ipv4Addr>().map_err(|_| format!("Invalid IP address: {}", ip))?);
+    let prefix = subnet_prefix(subnet).ok_or_else(|| format!("Invalid subnet mask: {}", subnet))?;
+    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+
+    // /31 and /32 have no network or broadcast address to reserve
+    let broadcast = (address & mask) | !mask;
+    let last_host = if prefix >= 31 { broadcast } else { broadcast - 1 };
+    match address.checked_add(1) {
+        Some(next) if next <= last_host => Ok(Ipv4Addr::from(next)),
+        _ => Err(format!("{} is the last host in {}/{}", ip, Ipv4Addr::from(address & mask), prefix)),
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> error::Result<()> {
+    run_output(program, args).map(|_| ())
+}
+
+/// Runs a command, returning its stdout.
+fn run_output(program: &str, args: &[&str]) -> error::Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => NetProfilerError::CommandNotFound(program.to_string()),
+            ErrorKind::PermissionDenied => NetProfilerError::Elevation(format!("{} can't be run: {}", program, e)),
+            _ => NetProfilerError::CommandFailed { program: program.to_string(), output: e.to_string(), code: None },
+        })?;
+    if !output.status.success() {
+        return Err(command_error(program, output.status.code(), &output.stdout, &output.stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The error for a command that exited unsuccessfully, explained by whatever it printed.
+fn command_error(program: &str, code: Option<i32>, stdout: &[u8], stderr: &[u8]) -> NetProfilerError {
+    let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+    // netsh prints its errors to stdout and leaves stderr empty
+    let output = match stderr.is_empty() {
+        true => String::from_utf8_lossy(stdout).trim().to_string(),
+        false => stderr,
+    };
+    // Missing rights only show up in the tools' messages, not their exit codes
+    if ["Operation not permitted", "Access is denied", "requires elevation"].iter().any(|message| output.contains(message)) {
+        return NetProfilerError::Elevation(output);
+    }
+    NetProfilerError::CommandFailed { program: program.to_string(), output, code }
+}
+
+impl From<serde_json::Value> for NetworkProfile {
+    fn from(value: serde_json::Value) -> Self {
+        serde_json::from_value(value).unwrap_or_default()
+    }
+}
+
+impl From<NetworkProfile> for serde_json::Value {
+    fn from(profile: NetworkProfile) -> Self {
+        serde_json::to_value(&profile).unwrap_or_default()
+    }
+}
+
+/// How applying a profile treats one address family
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FamilyPolicy {
+    /// Program the profile's static settings
+    #[default]
+    Configure,
+    /// Don't touch the family's current configuration
+    LeaveAlone,
+    /// Hand the family to DHCP, or SLAAC/DHCPv6 for IPv6
+    Dhcp,
+}
+
+impl FamilyPolicy {
+    pub const ALL: [FamilyPolicy; 3] = [FamilyPolicy::Configure, FamilyPolicy::LeaveAlone, FamilyPolicy::Dhcp];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FamilyPolicy::Configure => "Configure",
+            FamilyPolicy::LeaveAlone => "Leave Alone",
+            FamilyPolicy::Dhcp => "DHCP",
+        }
+    }
+}
+
+/// Whether DNS queries to a profile's servers are encrypted
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DnsEncryption {
+    /// Plain DNS over UDP port 53
+    #[default]
+    Off,
+    /// DNS over HTTPS, which Windows 11 supports for servers with a known template
+    Doh,
+    /// DNS over TLS, which NetworkManager and systemd-resolved support
+    Dot,
+}
+
+impl DnsEncryption {
+    pub const ALL: [DnsEncryption; 3] = [DnsEncryption::Off, DnsEncryption::Doh, DnsEncryption::Dot];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DnsEncryption::Off => "Off",
+            DnsEncryption::Doh => "DNS over HTTPS",
+            DnsEncryption::Dot => "DNS over TLS",
+        }
+    }
+}
+
+/// DNS over HTTPS templates of the built-in providers, serving the same filtering as their PROVIDER_DNS addresses
+pub const PROVIDER_DOH: [(DNSProvider, &str); 4] = [
+    (DNSProvider::Quad9, "https://dns.quad9.net/dns-query"),
+    (DNSProvider::Google, "https://dns.google/dns-query"),
+    (DNSProvider::Cloudflare, "https://security.cloudflare-dns.com/dns-query"),
+    (DNSProvider::OpenDNS, "https://doh.opendns.com/dns-query"),
+];
+
+/// The DNS over HTTPS template of a built-in provider, or None for custom and raw servers.
+pub fn doh_template(provider: &DNSProvider) -> Option<&'static str> {
+    PROVIDER_DOH.iter().find(|(preset, _)| preset == provider).map(|(_, template)| *template)
+}
+
+/// Addresses of the built-in DNS providers, unless the user overrides them
+pub const PROVIDER_DNS: [(DNSProvider, [&str; 2]); 4] = [
+    (DNSProvider::Quad9, ["9.9.9.9", "149.112.112.112"]),
+    (DNSProvider::Google, ["8.8.8.8", "8.8.4.4"]),
+    // The malware blocking resolvers, rather than 1.1.1.1
+    (DNSProvider::Cloudflare, ["1.1.1.2", "1.0.0.2"]),
+    (DNSProvider::OpenDNS, ["208.67.222.222", "208.67.220.220"]),
+];
+
+/// Addresses the user has given built-in providers in place of PROVIDER_DNS
+static PROVIDER_OVERRIDES: RwLock<Vec<(DNSProvider, [String; 2])>> = RwLock::new(Vec::new());
+
+/// Replaces the provider address overrides, which every later apply uses.
+pub fn set_provider_overrides(overrides: &HashMap<DNSProvider, [String; 2]>) {
+    *PROVIDER_OVERRIDES.write().unwrap_or_else(PoisonError::into_inner) = overrides.iter()
+        .map(|(provider, servers)| (provider.clone(), servers.clone()))
+        .collect();
+}
+
+/// The addresses a built-in provider stands for, overridden or default, or None for the others.
+pub fn provider_dns(provider: &DNSProvider) -> Option<[String; 2]> {
+    let overrides = PROVIDER_OVERRIDES.read().unwrap_or_else(PoisonError::into_inner);
+    match overrides.iter().find(|(overridden, _)| overridden == provider) {
+        Some((_, servers)) => Some(servers.clone()),
+        None => default_provider_dns(provider),
+    }
+}
+
+pub fn default_provider_dns(provider: &DNSProvider) -> Option<[String; 2]> {
+    PROVIDER_DNS.iter()
+        .find(|(preset, _)| preset == provider)
+        .map(|(_, servers)| servers.map(String::from))
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DNSProvider {
+    #[default]
+    None,
+    Quad9,
+    Google,
+    Cloudflare,
+    OpenDNS,
+    Custom,
+    /// resolv.conf content, for setups the other providers can't express
+    Raw,
+}
+
+/// A route to a specific network through a gateway on the adapter
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct StaticRoute {
+    pub destination: String,
+    pub prefix: u8,
+    pub via: String,
+    pub metric: Option<u32>,
+}
+
+impl std::fmt::Display for StaticRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} via {}", self.destination, self.prefix, self.via)
+    }
+}
+
+/// A web proxy for HTTP and HTTPS, given by address, by PAC file, or both
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub host: String,
+    pub port: u16,
+    /// Hosts reached directly rather than through the proxy, e.g. "localhost" or "*.corp.example"
+    pub bypass: Vec<String>,
+    /// Proxy auto-config script, used instead of the host where the platform supports it
+    pub pac_url: String,
+}
+
+impl ProxyConfig {
+    /// The bypass list without blank entries.
+    pub fn bypass_hosts(&self) -> Vec<&str> {
+        self.bypass.iter().map(|host| host.trim()).filter(|host| !host.is_empty()).collect()
+    }
+
+    pub fn validate(&self) -> error::Result<()> {
+        if self.host.is_empty() && self.pac_url.is_empty() {
+            return Err(NetProfilerError::Profile("The proxy needs a host or a PAC URL".to_string()));
+        }
+        if !self.host.is_empty() && self.port == 0 {
+            return Err(NetProfilerError::Profile("The proxy needs a port".to_string()));
+        }
+        if self.host.is_empty() && cfg!(target_os = "linux") {
+            return Err(NetProfilerError::Unsupported("PAC files aren't supported on Linux, give the proxy's host and port".to_string()));
+        }
+        // These end up quoted in shell scripts and PowerShell commands
+        let unquotable = |value: &str| value.contains(char::is_whitespace) || value.contains(['"', '\'', ';', '$', '`']);
+        if unquotable(&self.host) {
+            return Err(NetProfilerError::Profile(format!("Invalid proxy host: {}", self.host)));
+        }
+        if let Some(host) = self.bypass_hosts().into_iter().find(|host| unquotable(host)) {
+            return Err(NetProfilerError::Profile(format!("Invalid proxy bypass host: {}", host)));
+        }
+        if unquotable(&self.pac_url) {
+            return Err(NetProfilerError::Profile(format!("Invalid PAC URL: {}", self.pac_url)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct BridgeConfig {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the commands it's asked to run instead of executing them
+    #[derive(Default)]
+    struct RecordingBackend {
+        commands: Vec<String>,
+        interfaces: Vec<String>,
+        namespaces: Vec<String>,
+        current: NetworkProfile,
+        /// Commands starting with this fail instead of being recorded as run
+        fail_on: Option<String>,
+        /// Interfaces that are administratively down, the rest count as up
+        down: Vec<String>,
+        dns_manager: DnsManager,
+        hostname: String,
+        networkd: bool,
+    }
+
+    impl Backend for RecordingBackend {
+        fn run(&mut self, program: &str, args: &[&str]) -> error::Result<()> {
+            let command = format!("{} {}", program, args.join(" "));
+            if self.fail_on.as_ref().is_some_and(|prefix| command.starts_with(prefix.as_str())) {
+                return Err(NetProfilerError::CommandFailed { program: program.to_string(), output: "failed".to_string(), code: Some(1) });
+            }
+            self.commands.push(command);
+            Ok(())
+        }
+
+        fn interface_exists(&self, name: &str) -> bool {
+            self.interfaces.iter().any(|interface| interface == name)
+        }
+
+        fn interface_up(&self, name: &str) -> bool {
+            !self.down.iter().any(|interface| interface == name)
+        }
+
+        fn netns_exists(&self, name: &str) -> bool {
+            self.namespaces.iter().any(|netns| netns == name)
+        }
+
+        /// The recorded interfaces stand in for the namespace's as well
+        fn netns_interface_up(&self, _netns: &str, name: &str) -> Option<bool> {
+            self.interface_exists(name).then(|| self.interface_up(name))
+        }
+
+        fn current_profile(&self, _adapter: &str) -> NetworkProfile {
+            self.current.clone()
+        }
+
+        fn write_file(&mut self, path: &str, contents: &str) -> error::Result<()> {
+            self.commands.push(describe_write(path, contents));
+            Ok(())
+        }
+
+        fn dns_manager(&self) -> DnsManager {
+            self.dns_manager
+        }
+
+        fn hostname(&self) -> String {
+            self.hostname.clone()
+        }
+
+        fn networkd_running(&self) -> bool {
+            self.networkd
+        }
+    }
+
+    fn profile() -> NetworkProfile {
+        NetworkProfile {
+            name: "ROV".to_string(),
+            adapter: "eth0".to_string(),
+            ip: "192.168.1.10".to_string(),
+            subnet: "255.255.255.0".to_string(),
+            gateway: "192.168.1.1".to_string(),
+            dns_provider: DNSProvider::Quad9,
+            ..Default::default()
+        }
+    }
+
+    /// Every apply of a profile without a proxy ends by clearing any proxy set before
+    #[cfg(target_os = "linux")]
+    const CLEAR_PROXY: &str = "write /etc/profile.d/net-profiler-proxy.sh: # Written by Net Profiler";
+    #[cfg(target_os = "windows")]
+    const CLEAR_PROXY: &str = concat!(
+        "powershell -Command netsh winhttp reset proxy; ",
+        r"Set-ItemProperty -Path 'HKCU:\Software\Microsoft\Windows\CurrentVersion\Internet Settings' -Name ProxyEnable -Value 0; ",
+        r"Remove-ItemProperty -Path 'HKCU:\Software\Microsoft\Windows\CurrentVersion\Internet Settings' -Name AutoConfigURL -ErrorAction SilentlyContinue",
+    );
+
+    fn load(profile: &NetworkProfile, backend: &mut RecordingBackend, cancel: bool) -> ApplyOutcome {
+        profile.load_with(backend, &AtomicBool::new(cancel))
+    }
+
+    #[test]
+    fn load_without_adapter_runs_nothing() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { adapter: String::new(), ..profile() };
+        assert!(matches!(load(&profile, &mut backend, false), ApplyOutcome::Failed(_)));
+        assert!(backend.commands.is_empty());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_sets_address_gateway_and_dns() {
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile(), &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands, [
+            "ip addr flush dev eth0",
+            "ip addr add 192.168.1.10/24 dev eth0",
+            "ip route replace default via 192.168.1.1 dev eth0",
+            "nmcli device modify eth0 ipv4.dns 9.9.9.9 149.112.112.112",
+            CLEAR_PROXY,
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_sets_route_metric_before_dns() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { route_metric: Some(50), ..profile() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[3..], [
+            "nmcli device modify eth0 ipv4.route-metric 50",
+            "nmcli device modify eth0 ipv4.dns 9.9.9.9 149.112.112.112",
+            CLEAR_PROXY,
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_runs_inside_existing_netns() {
+        let profile = NetworkProfile { netns: Some("lab".to_string()), ..profile() };
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Failed(NetProfilerError::Profile("Network namespace lab doesn't exist".to_string())));
+        assert!(backend.commands.is_empty());
+
+        let mut backend = RecordingBackend {
+            namespaces: vec!["lab".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands, [
+            "ip -n lab addr flush dev eth0",
+            "ip -n lab addr add 192.168.1.10/24 dev eth0",
+            "ip -n lab route replace default via 192.168.1.1 dev eth0",
+            "ip netns exec lab nmcli device modify eth0 ipv4.dns 9.9.9.9 149.112.112.112",
+        ]);
+
+        // The proxy file and computer name belong to the host, not the namespace
+        let profile = NetworkProfile { proxy: Some(ProxyConfig { host: "proxy".to_string(), port: 3128, ..Default::default() }), hostname: Some("rov-lab".to_string()), ..profile };
+        let mut backend = RecordingBackend { namespaces: vec!["lab".to_string()], ..Default::default() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert!(backend.commands.iter().all(|command| command.starts_with("ip -n lab ") || command.starts_with("ip netns exec lab ")), "{:?}", backend.commands);
+        let steps = profile.apply_steps(&backend, ApplySections::all());
+        assert!(!steps.contains(&ApplyStep::SettingProxy) && !steps.contains(&ApplyStep::SettingHostname));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn netns_rollback_doesnt_restore_the_hosts_config() {
+        let profile = NetworkProfile { netns: Some("lab".to_string()), ..profile() };
+        let host = NetworkProfile { ip: "10.0.0.5".to_string(), subnet: "/24".to_string(), gateway: "10.0.0.1".to_string(), ..Default::default() };
+        let mut backend = RecordingBackend {
+            namespaces: vec!["lab".to_string()],
+            current: host,
+            fail_on: Some("ip netns exec lab nmcli".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(profile.load_transactional_with(&mut backend, &AtomicBool::new(false)), ApplyOutcome::RolledBack(_)));
+        assert!(!backend.commands.iter().any(|command| command.contains("10.0.0.")), "{:?}", backend.commands);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_follows_family_policies() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile {
+            ipv4_policy: FamilyPolicy::Dhcp,
+            ipv6_policy: FamilyPolicy::Dhcp,
+            dns_provider: DNSProvider::None,
+            ..profile()
+        };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands, [
+            "nmcli device modify eth0 ipv4.method auto",
+            "nmcli device modify eth0 ipv6.method auto",
+            CLEAR_PROXY,
+        ]);
+
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { ipv4_policy: FamilyPolicy::LeaveAlone, ..profile };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands, ["nmcli device modify eth0 ipv6.method auto", CLEAR_PROXY]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_skips_missing_gateway_and_dns() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile {
+            gateway: String::new(),
+            dns_provider: DNSProvider::None,
+            ..profile()
+        };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands, [
+            "ip addr flush dev eth0",
+            "ip addr add 192.168.1.10/24 dev eth0",
+            CLEAR_PROXY,
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_addresses_bridge_after_creating_it() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile {
+            bridge: Some(BridgeConfig { name: "br0".to_string(), members: vec!["eth0".to_string()] }),
+            dns_provider: DNSProvider::None,
+            ..profile()
+        };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands, [
+            "ip link add name br0 type bridge",
+            "ip link set eth0 master br0",
+            "ip link set eth0 up",
+            "ip link set br0 up",
+            "ip addr flush dev br0",
+            "ip addr add 192.168.1.10/24 dev br0",
+            "ip route replace default via 192.168.1.1 dev br0",
+            CLEAR_PROXY,
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn cancelled_load_removes_created_bridge() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile {
+            bridge: Some(BridgeConfig { name: "br0".to_string(), members: Vec::new() }),
+            ..profile()
+        };
+        assert_eq!(load(&profile, &mut backend, true), ApplyOutcome::Cancelled);
+        assert_eq!(backend.commands, [
+            "ip link add name br0 type bridge",
+            "ip link set br0 up",
+            "ip link del br0",
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn transactional_load_restores_previous_config_on_failure() {
+        let current = NetworkProfile {
+            ip: "10.0.0.5".to_string(),
+            subnet: "255.255.255.0".to_string(),
+            gateway: "10.0.0.1".to_string(),
+            dns_provider: DNSProvider::Custom,
+            primary_dns: "10.0.0.53".to_string(),
+            ..Default::default()
+        };
+        let mut backend = RecordingBackend { current, fail_on: Some("nmcli device modify eth0 ipv4.dns 9.9.9.9".to_string()), ..Default::default() };
+        let outcome = profile().load_transactional_with(&mut backend, &AtomicBool::new(false));
+        assert!(matches!(outcome, ApplyOutcome::RolledBack(NetProfilerError::CommandFailed { .. })));
+        assert_eq!(backend.commands, [
+            "ip addr flush dev eth0",
+            "ip addr add 192.168.1.10/24 dev eth0",
+            "ip route replace default via 192.168.1.1 dev eth0",
+            "nmcli device modify eth0 ipv4.dns 10.0.0.53",
+            "ip addr flush dev eth0",
+            "ip addr add 10.0.0.5/24 dev eth0",
+            "ip route replace default via 10.0.0.1 dev eth0",
+        ]);
+
+        // Without opting in the failure leaves the new address in place
+        let mut backend = RecordingBackend { fail_on: Some("nmcli".to_string()), ..Default::default() };
+        assert!(matches!(load(&profile(), &mut backend, false), ApplyOutcome::Failed(_)));
+        assert_eq!(backend.commands.len(), 3);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn transactional_load_puts_a_leased_adapter_back_on_dhcp() {
+        let current = NetworkProfile { ip: "10.0.0.5".to_string(), subnet: "/24".to_string(), ipv4_policy: FamilyPolicy::Dhcp, ..Default::default() };
+        let mut backend = RecordingBackend { current, fail_on: Some("nmcli device modify eth0 ipv4.dns".to_string()), ..Default::default() };
+        let outcome = profile().load_transactional_with(&mut backend, &AtomicBool::new(false));
+        assert!(matches!(outcome, ApplyOutcome::RolledBack(_)));
+        assert_eq!(backend.commands[3..], ["nmcli device modify eth0 ipv4.method auto"]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn transactional_load_restores_mac_and_mtu() {
+        let current = NetworkProfile { mac_address: Some("02:00:00:00:00:01".to_string()), mtu: Some(1500), ..Default::default() };
+        let mut backend = RecordingBackend { current, fail_on: Some("nmcli device modify eth0 ipv4.dns".to_string()), ..Default::default() };
+        let profile = NetworkProfile { mac_address: Some("02:00:00:aa:bb:cc".to_string()), mtu: Some(9000), ..profile() };
+        assert!(matches!(profile.load_transactional_with(&mut backend, &AtomicBool::new(false)), ApplyOutcome::RolledBack(_)));
+        let rollback = backend.commands.iter().position(|command| command == "ip link set dev eth0 mtu 1500").unwrap();
+        assert_eq!(backend.commands[rollback..rollback + 4], [
+            "ip link set dev eth0 mtu 1500",
+            "ip link set dev eth0 down",
+            "ip link set dev eth0 address 02:00:00:00:00:01",
+            "ip link set dev eth0 up",
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn mac_change_cycles_the_link_only_when_it_was_up() {
+        let profile = NetworkProfile {
+            mac_address: Some("02-00-00-aa-bb-cc".to_string()),
+            dns_provider: DNSProvider::None,
+            ..profile()
+        };
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[..3], [
+            "ip link set dev eth0 down",
+            "ip link set dev eth0 address 02:00:00:aa:bb:cc",
+            "ip link set dev eth0 up",
+        ]);
+        assert_eq!(backend.commands[3], "ip addr flush dev eth0");
+
+        let mut backend = RecordingBackend { down: vec!["eth0".to_string()], ..Default::default() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[0], "ip link set dev eth0 address 02:00:00:aa:bb:cc");
+        assert!(!backend.commands.iter().any(|command| command.ends_with(" up")));
+
+        // A rejected address still brings the link back up
+        let mut backend = RecordingBackend { fail_on: Some("ip link set dev eth0 address".to_string()), ..Default::default() };
+        assert!(matches!(load(&profile, &mut backend, false), ApplyOutcome::Failed(_)));
+        assert_eq!(backend.commands, ["ip link set dev eth0 down", "ip link set dev eth0 up"]);
+    }
+
+    #[test]
+    fn validation_reports_every_problem_and_blocks_apply() {
+        let invalid = NetworkProfile {
+            ip: "192.168.1.300".to_string(),
+            gateway: "10.0.0.1".to_string(),
+            dns_provider: DNSProvider::Custom,
+            primary_dns: "1.1.1".to_string(),
+            ..profile()
+        };
+        assert_eq!(invalid.validation_errors(), [
+            NetProfilerError::InvalidIp("192.168.1.300".to_string()),
+            NetProfilerError::InvalidDns("1.1.1".to_string()),
+        ]);
+        let mut backend = RecordingBackend::default();
+        assert!(matches!(load(&invalid, &mut backend, false), ApplyOutcome::Failed(NetProfilerError::InvalidIp(_))));
+        assert!(backend.commands.is_empty());
+
+        assert!(profile().validation_errors().is_empty());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_sets_mtu_before_addressing() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { mtu: Some(9000), ..profile() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[..2], ["ip link set dev eth0 mtu 9000", "ip addr flush dev eth0"]);
+
+        let profile = NetworkProfile { mtu: Some(20), ..profile };
+        assert!(matches!(load(&profile, &mut backend, false), ApplyOutcome::Failed(NetProfilerError::Profile(_))));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_adds_static_routes_and_removes_them_on_rollback() {
+        let route = |destination: &str, metric| StaticRoute { destination: destination.to_string(), prefix: 16, via: "192.168.1.254".to_string(), metric };
+        let profile = NetworkProfile {
+            routes: vec![route("10.50.0.0", None), route("10.60.0.0", Some(50))],
+            ..profile()
+        };
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[3..5], [
+            "ip route replace 10.50.0.0/16 via 192.168.1.254 dev eth0",
+            "ip route replace 10.60.0.0/16 via 192.168.1.254 dev eth0 metric 50",
+        ]);
+
+        let leave_address = NetworkProfile { ipv4_policy: FamilyPolicy::LeaveAlone, ..profile.clone() };
+        let mut backend = RecordingBackend { fail_on: Some("ip route replace 10.60".to_string()), ..Default::default() };
+        let outcome = leave_address.load_transactional_with(&mut backend, &AtomicBool::new(false));
+        assert!(matches!(outcome, ApplyOutcome::RolledBack(_)));
+        assert_eq!(backend.commands, [
+            "ip route replace 10.50.0.0/16 via 192.168.1.254 dev eth0",
+            "ip route del 10.50.0.0/16 via 192.168.1.254 dev eth0",
+        ]);
+
+        // A route the adapter already had isn't removed along with the ones the apply added
+        let mut backend = RecordingBackend { fail_on: Some("ip route replace 10.60".to_string()), ..Default::default() };
+        backend.current.routes = parse_routes("10.50.0.0/16 via 192.168.1.1 proto static metric 100\n192.168.1.0/24 proto kernel scope link src 192.168.1.10");
+        assert_eq!(backend.current.routes, [StaticRoute { via: "192.168.1.1".to_string(), ..route("10.50.0.0", Some(100)) }]);
+        let outcome = leave_address.load_transactional_with(&mut backend, &AtomicBool::new(false));
+        assert!(matches!(outcome, ApplyOutcome::RolledBack(_)));
+        assert_eq!(backend.commands, ["ip route replace 10.50.0.0/16 via 192.168.1.254 dev eth0"]);
+
+        let invalid = NetworkProfile { routes: vec![StaticRoute { prefix: 33, ..route("10.50.0", None) }], ..profile };
+        assert_eq!(invalid.validation_errors().len(), 2);
+        let unaligned = NetworkProfile { routes: vec![StaticRoute { prefix: 8, ..route("10.50.0.0", None) }], ..invalid };
+        assert_eq!(unaligned.validation_errors(), [NetProfilerError::Profile(
+            "Route destination 10.50.0.0/8 isn't a network address, did you mean 10.0.0.0/8?".to_string(),
+        )]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn reset_to_dhcp_clears_static_config() {
+        let mut backend = RecordingBackend::default();
+        reset_to_dhcp(&mut backend, "eth0").unwrap();
+        assert_eq!(backend.commands, [
+            concat!(
+                "sh -c nmcli connection modify \"$(nmcli -g GENERAL.CONNECTION device show 'eth0')\" ",
+                "ipv4.method 'auto' ipv4.addresses '' ipv4.gateway '' ipv4.dns '' ipv4.dns-search ''",
+            ),
+            "ip addr flush dev eth0",
+            "nmcli device reapply eth0",
+        ]);
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::Resolved, networkd: true, ..Default::default() };
+        reset_to_dhcp(&mut backend, "eth0").unwrap();
+        assert_eq!(backend.commands[1..], ["networkctl reload", "ip addr flush dev eth0", "networkctl reconfigure eth0"]);
+        assert!(backend.commands[0].starts_with("write /etc/systemd/network/10-net-profiler-eth0.network:"));
+        assert!(backend.commands[0].contains("DHCP=ipv4"));
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::ResolvConf, ..Default::default() };
+        reset_to_dhcp(&mut backend, "eth0").unwrap();
+        assert_eq!(backend.commands, ["dhclient -r eth0", "ip addr flush dev eth0", "dhclient eth0"]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dhcp_goes_through_whatever_manages_the_adapter() {
+        let dhcp = NetworkProfile { ipv4_policy: FamilyPolicy::Dhcp, dns_provider: DNSProvider::None, ..profile() };
+        let mut backend = RecordingBackend { dns_manager: DnsManager::ResolvConf, ..Default::default() };
+        assert_eq!(load(&dhcp, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[0], "dhclient eth0");
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::Resolved, networkd: true, ..Default::default() };
+        assert_eq!(load(&dhcp, &mut backend, false), ApplyOutcome::Applied);
+        assert!(backend.commands[0].starts_with("write /etc/systemd/network/10-net-profiler-eth0.network:"));
+        assert!(backend.commands[0].contains("DHCP=ipv4"));
+        assert_eq!(backend.commands[1..3], ["networkctl reload", "networkctl reconfigure eth0"]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn failed_ipv6_dhcp_isnt_rolled_back_unless_asked() {
+        let profile = NetworkProfile { ipv6_policy: FamilyPolicy::Dhcp, ..profile() };
+        let mut backend = RecordingBackend { fail_on: Some("nmcli device modify eth0 ipv6.method".to_string()), ..Default::default() };
+        backend.current.ip = "192.168.1.99".to_string();
+        assert!(matches!(load(&profile, &mut backend, false), ApplyOutcome::Failed(_)));
+        // Nothing puts 192.168.1.99 back
+        assert_eq!(backend.commands, [
+            "ip addr flush dev eth0",
+            "ip addr add 192.168.1.10/24 dev eth0",
+            "ip route replace default via 192.168.1.1 dev eth0",
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn reset_to_dhcp_keeps_the_address_when_nothing_can_lease_one() {
+        let mut backend = RecordingBackend { dns_manager: DnsManager::ResolvConf, fail_on: Some("dhclient".to_string()), ..Default::default() };
+        assert!(reset_to_dhcp(&mut backend, "eth0").is_err());
+        assert!(backend.commands.is_empty());
+    }
+
+    #[test]
+    fn unique_profile_name_numbers_collisions() {
+        let mut profiles = HashMap::new();
+        assert_eq!(unique_profile_name(&profiles, "ROV"), "ROV");
+        profiles.insert("ROV".to_string(), profile());
+        profiles.insert("ROV (2)".to_string(), profile());
+        assert_eq!(unique_profile_name(&profiles, "ROV"), "ROV (3)");
+    }
+
+    #[test]
+    fn copy_profile_name_skips_existing_copies() {
+        let mut profiles = HashMap::new();
+        profiles.insert("ROV".to_string(), profile());
+        assert_eq!(copy_profile_name(&profiles, "ROV"), "ROV (copy)");
+        profiles.insert("ROV (copy)".to_string(), profile());
+        assert_eq!(copy_profile_name(&profiles, "ROV"), "ROV (copy 2)");
+        profiles.insert("ROV (copy 2)".to_string(), profile());
+        assert_eq!(copy_profile_name(&profiles, "ROV"), "ROV (copy 3)");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_multi_addresses_only_the_first_adapter() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { mac_address: Some("02:00:00:00:00:01".to_string()), ..profile() };
+        let adapters = ["eth0".to_string(), "eth1".to_string()];
+        let outcomes = profile.load_multi_with(&mut backend, &adapters, &AtomicBool::new(false), ApplySections::all());
+        assert_eq!(outcomes, [("eth0".to_string(), ApplyOutcome::Applied), ("eth1".to_string(), ApplyOutcome::Applied)]);
+
+        let on_eth1: Vec<&String> = backend.commands.iter().filter(|command| command.contains("eth1")).collect();
+        assert_eq!(on_eth1, ["nmcli device modify eth1 ipv4.dns 9.9.9.9 149.112.112.112"]);
+        assert!(backend.commands.contains(&"ip addr add 192.168.1.10/24 dev eth0".to_string()));
+        assert!(backend.commands.iter().any(|command| command.contains("address 02:00:00:00:00:01") && command.contains("eth0")));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_sections_skips_the_rest() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { mtu: Some(9000), route_metric: Some(50), ..profile() };
+        let outcome = profile.load_sections_with(&mut backend, &AtomicBool::new(false), ApplySections::DNS);
+        assert_eq!(outcome, ApplyOutcome::Applied);
+        assert_eq!(backend.commands, ["nmcli device modify eth0 ipv4.dns 9.9.9.9 149.112.112.112"]);
+
+        // The gateway the adapter already had survives the address change
+        let mut backend = RecordingBackend::default();
+        backend.current.gateway = "192.168.1.254".to_string();
+        let outcome = profile.load_sections_with(&mut backend, &AtomicBool::new(false), ApplySections::ADDRESS | ApplySections::MTU);
+        assert_eq!(outcome, ApplyOutcome::Applied);
+        assert_eq!(backend.commands, [
+            "ip link set dev eth0 mtu 9000",
+            "ip addr flush dev eth0",
+            "ip addr add 192.168.1.10/24 dev eth0",
+            "ip route replace default via 192.168.1.254 dev eth0",
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn gateway_section_alone_keeps_the_address() {
+        let mut backend = RecordingBackend::default();
+        let outcome = profile().load_sections_with(&mut backend, &AtomicBool::new(false), ApplySections::GATEWAY);
+        assert_eq!(outcome, ApplyOutcome::Applied);
+        assert_eq!(backend.commands, ["ip route replace default via 192.168.1.1 dev eth0"]);
+    }
+
+    #[test]
+    fn additional_adapters_keep_dhcp() {
+        let dhcp = NetworkProfile { ipv4_policy: FamilyPolicy::Dhcp, ..profile() };
+        assert_eq!(dhcp.for_additional_adapter("eth1").ipv4_policy, FamilyPolicy::Dhcp);
+        assert_eq!(profile().for_additional_adapter("eth1").ipv4_policy, FamilyPolicy::LeaveAlone);
+    }
+
+    #[test]
+    fn additional_adapters_leave_out_routes() {
+        let route = StaticRoute { destination: "10.0.0.0".to_string(), prefix: 8, via: "192.168.1.254".to_string(), metric: None };
+        let profile = NetworkProfile { routes: vec![route], ..profile() };
+        assert!(profile.for_additional_adapter("eth1").routes.is_empty());
+    }
+
+    #[test]
+    fn set_dns_refuses_empty_and_malformed_servers() {
+        let mut backend = RecordingBackend::default();
+        assert_eq!(set_dns(&mut backend, "eth0", &[], &[]), Err(NetProfilerError::InvalidDns(String::new())));
+        assert_eq!(set_dns(&mut backend, "eth0", &["", ""], &[]), Err(NetProfilerError::InvalidDns(String::new())));
+        assert_eq!(set_dns(&mut backend, "eth0", &["9.9.9.9", "dns"], &[]), Err(NetProfilerError::InvalidDns("dns".to_string())));
+        assert!(backend.commands.is_empty());
+
+        let blank = NetworkProfile { dns_provider: DNSProvider::Custom, ..profile() };
+        assert!(matches!(load(&blank, &mut backend, false), ApplyOutcome::Failed(NetProfilerError::InvalidDns(_))));
+    }
+
+    #[test]
+    fn description_is_optional_and_round_trips() {
+        let old: NetworkProfile = serde_json::from_str(r#"{"name": "ROV", "ip": "192.168.1.10"}"#).unwrap();
+        assert_eq!(old.description, "");
+
+        let described = NetworkProfile { description: "DR site, VLAN 40".to_string(), ..profile() };
+        let json = serde_json::to_string(&described).unwrap();
+        assert_eq!(serde_json::from_str::<NetworkProfile>(&json).unwrap(), described);
+        assert!(described.matches("vlan 40"));
+    }
+
+    #[test]
+    fn command_errors_fall_back_to_stdout_and_keep_the_exit_code() {
+        let error = command_error("netsh", Some(1), b"The filename, directory name, or volume label syntax is incorrect.\r\n", b"");
+        assert_eq!(error.to_string(), "netsh failed with exit code 1: The filename, directory name, or volume label syntax is incorrect.");
+
+        let error = command_error("ip", Some(2), b"", b"Error: inet prefix is expected rather than \"x\".\n");
+        assert_eq!(error.to_string(), "ip failed with exit code 2: Error: inet prefix is expected rather than \"x\".");
+
+        assert_eq!(command_error("nmcli", Some(8), b"", b"").to_string(), "nmcli exited with code 8");
+        assert!(matches!(command_error("netsh", Some(1), b"The requested operation requires elevation (Run as administrator).", b""), NetProfilerError::Elevation(_)));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dns_goes_through_resolved_or_resolv_conf_without_network_manager() {
+        let profile = NetworkProfile {
+            dns_provider: DNSProvider::Raw,
+            raw_dns: "nameserver 9.9.9.9\nsearch example.com".to_string(),
+            ..profile()
+        };
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::Resolved, ..Default::default() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[3..], ["resolvectl dns eth0 9.9.9.9", "resolvectl domain eth0 example.com", CLEAR_PROXY]);
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::ResolvConf, ..Default::default() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[3..], ["write /etc/resolv.conf: # Written by Net Profiler; nameserver 9.9.9.9; search example.com", CLEAR_PROXY]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dns_over_tls_follows_the_dns_manager() {
+        let profile = NetworkProfile { dns_encryption: DnsEncryption::Dot, ..profile() };
+
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[4..], ["nmcli device modify eth0 connection.dns-over-tls yes", CLEAR_PROXY]);
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::Resolved, ..Default::default() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[4..], ["resolvectl dnsovertls eth0 yes", CLEAR_PROXY]);
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::ResolvConf, ..Default::default() };
+        assert!(matches!(load(&profile, &mut backend, false), ApplyOutcome::Failed(NetProfilerError::Unsupported(_))));
+
+        let doh = NetworkProfile { dns_encryption: DnsEncryption::Doh, ..profile };
+        assert!(matches!(doh.validate(), Err(NetProfilerError::Unsupported(_))));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn hostname_is_set_last_and_only_when_it_changes() {
+        let profile = NetworkProfile { hostname: Some("site-a".to_string()), ..profile() };
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands.last().unwrap(), "hostnamectl set-hostname site-a");
+
+        let mut backend = RecordingBackend { hostname: "SITE-A".to_string(), ..Default::default() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert!(!backend.commands.iter().any(|command| command.starts_with("hostnamectl")));
+    }
+
+    #[test]
+    fn hostnames_follow_rfc_1123() {
+        assert!(check_hostname("site-a").is_ok());
+        assert!(check_hostname("-site").is_err());
+        assert!(check_hostname("site_a").is_err());
+        assert!(check_hostname(&"a".repeat(64)).is_err());
+        assert!(check_hostname("").is_err());
+        assert_eq!(check_hostname("site.example.com").is_ok(), cfg!(target_os = "linux"));
+        let profile = NetworkProfile { hostname: Some("bad name".to_string()), ..profile() };
+        assert!(matches!(profile.validate(), Err(NetProfilerError::Profile(_))));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn proxy_is_exported_for_login_shells() {
+        let proxy = ProxyConfig {
+            host: "proxy.corp.example".to_string(),
+            port: 3128,
+            bypass: vec!["localhost".to_string(), ".corp.example".to_string()],
+            ..Default::default()
+        };
+        let profile = NetworkProfile { proxy: Some(proxy), ..profile() };
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands.last().unwrap(), &[
+            "write /etc/profile.d/net-profiler-proxy.sh: # Written by Net Profiler",
+            "export http_proxy=\"http://proxy.corp.example:3128\"",
+            "export https_proxy=\"http://proxy.corp.example:3128\"",
+            "export HTTP_PROXY=\"http://proxy.corp.example:3128\"",
+            "export HTTPS_PROXY=\"http://proxy.corp.example:3128\"",
+            "export no_proxy=\"localhost,.corp.example\"",
+            "export NO_PROXY=\"localhost,.corp.example\"",
+        ].join("; "));
+
+        // Only the first of several adapters touches the system wide proxy
+        let mut backend = RecordingBackend::default();
+        let adapters = ["eth0".to_string(), "eth1".to_string()];
+        profile.load_multi_with(&mut backend, &adapters, &AtomicBool::new(false), ApplySections::all());
+        assert_eq!(backend.commands.iter().filter(|command| command.contains("net-profiler-proxy")).count(), 1);
+
+        let pac_only = ProxyConfig { pac_url: "http://wpad/wpad.dat".to_string(), ..Default::default() };
+        assert!(matches!(pac_only.validate(), Err(NetProfilerError::Unsupported(_))));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn persist_saves_through_network_manager_or_networkd() {
+        let route = StaticRoute { destination: "10.0.0.0".to_string(), prefix: 8, via: "192.168.1.254".to_string(), metric: None };
+        let profile = NetworkProfile { persist: true, routes: vec![route], ..profile() };
+
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[backend.commands.len() - 2], concat!(
+            "sh -c nmcli connection modify \"$(nmcli -g GENERAL.CONNECTION device show 'eth0')\" ",
+            "ipv4.method 'manual' ipv4.addresses '192.168.1.10/24' ipv4.gateway '192.168.1.1' ",
+            "ipv4.routes '10.0.0.0/8 192.168.1.254' ipv4.dns '9.9.9.9,149.112.112.112'",
+        ));
+
+        let mut backend = RecordingBackend { dns_manager: DnsManager::Resolved, networkd: true, ..Default::default() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[backend.commands.len() - 2], concat!(
+            "write /etc/systemd/network/10-net-profiler-eth0.network: # Written by Net Profiler; [Match]; Name=eth0; ; ",
+            "[Network]; Address=192.168.1.10/24; Gateway=192.168.1.1; DNS=9.9.9.9; DNS=149.112.112.112; ; ",
+            "[Route]; Destination=10.0.0.0/8; Gateway=192.168.1.254",
+        ));
+
+        // The route metric is only saved on the connection along with the rest
+        let metric = NetworkProfile { route_metric: Some(50), ..profile.clone() };
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&metric, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands.iter().filter(|command| command.contains("ipv4.route-metric '50'")).count(), 1);
+        assert!(backend.commands[backend.commands.len() - 2].contains("ipv4.route-metric '50'"));
+
+        // A .network file does nothing without networkd to read it
+        let mut backend = RecordingBackend { dns_manager: DnsManager::ResolvConf, ..Default::default() };
+        assert!(matches!(load(&profile, &mut backend, false), ApplyOutcome::Failed(NetProfilerError::Unsupported(_))));
+
+        // Saving part of a profile would lose the adapter's other settings at boot
+        let mut backend = RecordingBackend::default();
+        profile.load_sections_with(&mut backend, &AtomicBool::new(false), ApplySections::DNS);
+        assert!(!backend.commands.iter().any(|command| command.starts_with("sh -c")));
+
+        // The proxy and hostname aren't the adapter's, so leaving them out still saves it
+        let mut backend = RecordingBackend::default();
+        profile.load_sections_with(&mut backend, &AtomicBool::new(false), ApplySections::ADAPTER);
+        assert!(backend.commands.iter().any(|command| command.starts_with("sh -c nmcli connection modify")));
+    }
+
+    #[test]
+    fn network_manager_settings_keep_mac_and_mtu() {
+        let profile = NetworkProfile { mac_address: Some("02-00-00-aa-bb-cc".to_string()), mtu: Some(9000), ..profile() };
+        let settings = network_manager_settings(&profile);
+        assert_eq!(settings[..2], [
+            ("802-3-ethernet.cloned-mac-address", "02:00:00:aa:bb:cc".to_string()),
+            ("802-3-ethernet.mtu", "9000".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn applied_within_covers_running_and_recent_applies() {
+        // An adapter name of its own, as the apply locks are shared with tests running alongside
+        assert!(!applied_within("watch0", Duration::from_secs(30)));
+        let lock = ApplyLock::acquire("watch0");
+        assert!(applied_within("watch0", Duration::ZERO));
+        drop(lock);
+        assert!(applied_within("watch0", Duration::from_secs(30)));
+        assert!(!applied_within("watch0", Duration::ZERO));
+    }
+
+    #[test]
+    fn provider_addresses_can_be_overridden() {
+        // Only this test uses OpenDNS, so the override can't leak into others running alongside it
+        let opendns = NetworkProfile { dns_provider: DNSProvider::OpenDNS, ..profile() };
+        assert_eq!(opendns.dns_servers(), ["208.67.222.222", "208.67.220.220"]);
+
+        let overrides = HashMap::from([(DNSProvider::OpenDNS, ["208.67.222.123".to_string(), "208.67.220.123".to_string()])]);
+        set_provider_overrides(&overrides);
+        assert_eq!(opendns.dns_servers(), ["208.67.222.123", "208.67.220.123"]);
+        assert_eq!(default_provider_dns(&DNSProvider::OpenDNS).unwrap(), ["208.67.222.222", "208.67.220.220"]);
+
+        set_provider_overrides(&HashMap::new());
+        assert_eq!(opendns.dns_servers(), ["208.67.222.222", "208.67.220.220"]);
+    }
+
+    #[test]
+    fn matches_name_and_addresses_ignoring_case() {
+        let profile = profile();
+        assert!(profile.matches(""));
+        assert!(profile.matches("rov"));
+        assert!(profile.matches("168.1.1"));
+        assert!(profile.matches("9.9.9.9"));
+        assert!(!profile.matches("10.0.0"));
+    }
+
+    #[test]
+    fn tags_are_added_once_and_filtered_by() {
+        let mut profile = profile();
+        profile.add_tags("Site A, lab,, ");
+        profile.add_tags("LAB");
+        assert_eq!(profile.tags, ["Site A", "lab"]);
+        assert!(profile.matches("site"));
+        assert!(profile.matches("tag:site a"));
+        assert!(!profile.matches("tag:site"));
+    }
+
+    #[test]
+    fn apply_lock_is_held_once_per_adapter() {
+        let lock = ApplyLock::acquire("lock-test0").unwrap();
+        assert!(is_applying("lock-test0"));
+        assert!(ApplyLock::acquire("lock-test0").is_none());
+        assert!(ApplyLock::acquire("lock-test1").is_some());
+        drop(lock);
+        assert!(!is_applying("lock-test0"));
+    }
+
+    #[test]
+    fn gateway_in_subnet_follows_the_prefix() {
+        assert!(gateway_in_subnet("192.168.1.50", "255.255.255.0", "192.168.1.1"));
+        assert!(!gateway_in_subnet("192.168.1.50", "/24", "10.0.0.1"));
+        // Point to point links have exactly two addresses, both usable
+        assert!(gateway_in_subnet("10.0.0.0", "/31", "10.0.0.1"));
+        assert!(!gateway_in_subnet("10.0.0.1", "/31", "10.0.0.2"));
+        // A host route has no room for a gateway
+        assert!(!gateway_in_subnet("10.0.0.5", "/32", "10.0.0.5"));
+        assert!(!gateway_in_subnet("10.0.0.5", "255.255.255.255", "10.0.0.1"));
+        // Classful boundaries
+        assert!(gateway_in_subnet("10.200.3.4", "255.0.0.0", "10.0.0.1"));
+        assert!(!gateway_in_subnet("172.16.5.4", "/16", "172.17.0.1"));
+        assert!(gateway_in_subnet("192.168.1.50", "/0", "8.8.8.8"));
+        assert!(!gateway_in_subnet("192.168.1.50", "", "192.168.1.1"));
+    }
+
+    #[test]
+    fn mac_addresses_normalize_from_any_notation() {
+        for mac in ["02:1A:2b:3c:4d:5e", "02-1a-2B-3C-4D-5E", "021a2b3c4d5e"] {
+            assert!(is_mac_address(mac));
+            assert_eq!(normalize_mac(mac).unwrap(), "02:1a:2b:3c:4d:5e");
+        }
+        for mac in ["02:1a:2b:3c:4d", "02:1a:2b:3c:4d:5g", "021a2b3c4d5", "02:1a2b:3c:4d:5e:6f"] {
+            assert!(!is_mac_address(mac));
+        }
+        assert_eq!(normalize_mac("ff:ff:ff:ff:ff:ff"), Err("ff:ff:ff:ff:ff:ff is the broadcast address".to_string()));
+        assert!(normalize_mac("01:00:5e:00:00:01").unwrap_err().contains("multicast"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_maps_raw_dns_to_nmcli() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile {
+            gateway: String::new(),
+            dns_provider: DNSProvider::Raw,
+            raw_dns: "# office\nnameserver 10.0.0.53\nnameserver 10.0.1.53\nsearch corp.example lab.example\noptions rotate".to_string(),
+            ..profile()
+        };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[2..], [
+            "nmcli device modify eth0 ipv4.dns 10.0.0.53 10.0.1.53",
+            "nmcli device modify eth0 ipv4.dns-search corp.example lab.example",
+            CLEAR_PROXY,
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dummy_interface_is_created_once_and_removed() {
+        let mut backend = RecordingBackend {
+            interfaces: vec![TEST_INTERFACE.to_string()],
+            ..Default::default()
+        };
+        create_dummy_interface(&mut backend, TEST_INTERFACE).unwrap();
+        delete_dummy_interface(&mut backend, TEST_INTERFACE).unwrap();
+        assert_eq!(backend.commands, [
+            "ip link set nprf-test0 up",
+            "ip link del nprf-test0 type dummy",
+        ]);
+    }
+
+    #[test]
+    fn virtual_interfaces_are_detected_by_name_and_loopback() {
+        let interface = |name: &str, ip: &str| NetworkInterface {
+            name: name.to_string(),
+            addr: vec![Addr::V4(network_interface::V4IfAddr { ip: ip.parse().unwrap(), broadcast: None, netmask: None })],
+            mac_addr: None,
+            index: 0,
+        };
+        assert!(is_virtual_interface("docker0", &interface("docker0", "172.17.0.1")));
+        assert!(is_virtual_interface("loopback", &interface("loopback", "127.0.0.1")));
+        assert!(!is_virtual_interface(TEST_INTERFACE, &interface(TEST_INTERFACE, "10.0.0.1")));
+    }
+
+    #[test]
+    fn replace_only_touches_exact_addresses_in_scope() {
+        let mut profiles = [
+            profile(),
+            NetworkProfile { gateway: "192.168.1.10".to_string(), ..profile() },
+            NetworkProfile {
+                dns_provider: DNSProvider::Raw,
+                raw_dns: "nameserver 192.168.1.1\nsearch lab.example".to_string(),
+                ..profile()
+            },
+        ];
+        let scope = ReplaceScope { ip: false, ..Default::default() };
+        assert_eq!(replace_in_profiles(profiles.iter_mut(), "192.168.1.1", "10.0.0.1", scope), Ok(2));
+        assert_eq!(profiles[0].gateway, "10.0.0.1");
+        assert_eq!(profiles[1].gateway, "192.168.1.10");
+        assert_eq!(profiles[2].raw_dns, "nameserver 10.0.0.1\nsearch lab.example");
+        assert!(replace_in_profiles(profiles.iter_mut(), "10.0.0.1", "10.0.0", scope).is_err());
+    }
+
+    #[test]
+    fn replace_needs_an_address_to_find() {
+        let mut profiles = [NetworkProfile { secondary_dns: String::new(), ..profile() }];
+        let scope = ReplaceScope::default();
+        assert!(replace_in_profiles(profiles.iter_mut(), "", "10.0.0.1", scope).is_err());
+        assert!(replace_in_profiles(profiles.iter_mut(), "192.168", "10.0.0.1", scope).is_err());
+        assert_eq!(profiles[0].secondary_dns, "");
+    }
+
+    #[test]
+    fn captured_dns_recognises_presets() {
+        let servers = ipv4_tokens("IP4.DNS[1]:149.112.112.112\nIP4.DNS[2]:9.9.9.9\n");
+        assert_eq!(servers, ["149.112.112.112", "9.9.9.9"]);
+        let mut captured = NetworkProfile::default();
+        set_captured_dns(&mut captured, servers);
+        assert_eq!(captured.dns_provider, DNSProvider::Quad9);
+
+        let servers = ipv4_tokens("Link 2 (eth0): 10.0.0.53 10.0.0.54");
+        let mut captured = NetworkProfile::default();
+        set_captured_dns(&mut captured, servers);
+        assert_eq!(captured.dns_provider, DNSProvider::Custom);
+        assert_eq!((captured.primary_dns.as_str(), captured.secondary_dns.as_str()), ("10.0.0.53", "10.0.0.54"));
+
+        let servers = ipv4_tokens("Link 2 (eth0): 10.0.0.53 10.0.0.54 10.0.0.55 10.0.0.56");
+        let mut captured = NetworkProfile::default();
+        set_captured_dns(&mut captured, servers);
+        assert_eq!(captured.dns_provider, DNSProvider::Custom);
+        assert_eq!(captured.dns_servers(), ["10.0.0.53", "10.0.0.54", "10.0.0.55", "10.0.0.56"]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn load_sets_every_custom_dns_server() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile {
+            dns_provider: DNSProvider::Custom,
+            primary_dns: "10.0.0.53".to_string(),
+            extra_dns: vec!["10.0.0.55".to_string(), "10.0.0.56".to_string()],
+            ..profile()
+        };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[backend.commands.len() - 2..], ["nmcli device modify eth0 ipv4.dns 10.0.0.53 10.0.0.55 10.0.0.56", CLEAR_PROXY]);
+    }
+
+    #[test]
+    fn disconnect_warning_flags_moving_the_session_or_default_route() {
+        let current = NetworkProfile { ip: "10.0.0.5".to_string(), subnet: "255.255.255.0".to_string(), ..profile() };
+        let session = Some(Ipv4Addr::new(10, 0, 0, 5));
+        assert!(disconnect_warning(&current, &profile(), session, None).is_some());
+        assert!(disconnect_warning(&current, &profile(), None, Some("eth0")).is_some());
+        assert!(disconnect_warning(&current, &profile(), None, Some("eth1")).is_none());
+
+        let same_network = NetworkProfile { ip: "10.0.0.9".to_string(), gateway: "10.0.0.1".to_string(), ..current.clone() };
+        assert!(disconnect_warning(&current, &same_network, session, Some("eth0")).is_none());
+    }
+
+    #[test]
+    fn subnets_normalize_between_cidr_and_dotted() {
+        assert_eq!(normalize_subnet("255.255.255.0", MaskFormat::Cidr).as_deref(), Some("/24"));
+        assert_eq!(normalize_subnet("/20", MaskFormat::Dotted).as_deref(), Some("255.255.240.0"));
+        assert_eq!(normalize_subnet("0", MaskFormat::Dotted).as_deref(), Some("0.0.0.0"));
+        assert_eq!(normalize_subnet("255.0.255.0", MaskFormat::Cidr), None);
+        assert_eq!(normalize_subnet("/33", MaskFormat::Dotted), None);
+    }
+
+    #[test]
+    fn every_prefix_round_trips_through_dotted() {
+        for prefix in 0..=32u8 {
+            let dotted = normalize_subnet(&format!("/{}", prefix), MaskFormat::Dotted).unwrap();
+            assert_eq!(dotted_decimal_to_cidr(&dotted), Some(prefix), "{}", dotted);
+            assert_eq!(subnet_prefix(&prefix.to_string()), Some(prefix));
+            assert_eq!(normalize_subnet(&dotted, MaskFormat::Cidr), Some(format!("/{}", prefix)));
+        }
+        assert_eq!(dotted_decimal_to_cidr("0.0.0.0"), Some(0));
+        assert_eq!(dotted_decimal_to_cidr("255.255.255.255"), Some(32));
+        assert_eq!(dotted_decimal_to_cidr("255.255.255.128"), Some(25));
+    }
+
+    #[test]
+    fn malformed_masks_are_rejected() {
+        for mask in ["255.0.255.0", "255.255.255.1", "0.255.255.255", "255.255.256.0", "255.255.255", "mask", ""] {
+            assert_eq!(dotted_decimal_to_cidr(mask), None, "{}", mask);
+            assert_eq!(subnet_prefix(mask), None, "{}", mask);
+        }
+        for prefix in ["/33", "33", "/255", "/-1"] {
+            assert_eq!(subnet_prefix(prefix), None, "{}", prefix);
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn linux_addresses_take_the_prefix_of_either_mask_form() {
+        for subnet in ["255.255.240.0", "/20", "20"] {
+            let mut backend = RecordingBackend::default();
+            let profile = NetworkProfile { subnet: subnet.to_string(), ..profile() };
+            assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+            assert!(backend.commands.contains(&"ip addr add 192.168.1.10/20 dev eth0".to_string()), "{}", subnet);
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn windows_addresses_take_a_dotted_mask_of_either_form() {
+        for subnet in ["255.255.240.0", "/20", "20"] {
+            let mut backend = RecordingBackend::default();
+            let profile = NetworkProfile { subnet: subnet.to_string(), ..profile() };
+            assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+            assert!(backend.commands[0].contains("static 192.168.1.10 255.255.240.0 192.168.1.1"), "{}", subnet);
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn load_sets_address_gateway_and_dns() {
+        let mut backend = RecordingBackend::default();
+        assert_eq!(load(&profile(), &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands, [
+            "netsh interface ip set address eth0 static 192.168.1.10 255.255.255.0 192.168.1.1",
+            "netsh interface ip set dns eth0 static 9.9.9.9 primary validate=no",
+            "netsh interface ip add dns eth0 149.112.112.112 index=2 validate=no",
+            CLEAR_PROXY,
+        ]);
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn windows_addresses_without_a_gateway_leave_it_off() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { gateway: String::new(), ..profile() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert_eq!(backend.commands[0], "netsh interface ip set address eth0 static 192.168.1.10 255.255.255.0");
+    }
+
+    #[test]
+    fn progress_reports_each_step_as_planned() {
+        let route = |destination: &str| StaticRoute { destination: destination.to_string(), prefix: 16, via: "192.168.1.254".to_string(), metric: None };
+        let profile = NetworkProfile { routes: vec![route("10.1.0.0"), route("10.2.0.0")], mtu: Some(9000), ..profile() };
+        let mut backend = RecordingBackend::default();
+        let mut steps = Vec::new();
+        assert_eq!(profile.load_with_progress(&mut backend, &AtomicBool::new(false), &mut |step| steps.push(step)), ApplyOutcome::Applied);
+        assert_eq!(steps, [
+            ApplyStep::SettingMtu,
+            ApplyStep::SettingAddress,
+            ApplyStep::AddingRoute(1),
+            ApplyStep::AddingRoute(2),
+            ApplyStep::SettingDns,
+            ApplyStep::SettingProxy,
+            ApplyStep::Done,
+        ]);
+        assert_eq!(steps, profile.apply_steps(&backend, ApplySections::all()));
+    }
+
+    #[test]
+    fn progress_stops_at_the_failing_step() {
+        let dns = if cfg!(target_os = "windows") { "netsh interface ip set dns" } else { "nmcli" };
+        let mut backend = RecordingBackend { fail_on: Some(dns.to_string()), ..Default::default() };
+        let mut steps = Vec::new();
+        let outcome = profile().load_with_progress(&mut backend, &AtomicBool::new(false), &mut |step| steps.push(step));
+        assert!(matches!(outcome, ApplyOutcome::Failed(_)));
+        assert_eq!(steps.last(), Some(&ApplyStep::SettingDns));
+    }
+
+    #[test]
+    fn adapter_names_that_would_break_out_of_their_argument_are_refused() {
+        assert!(check_adapter_name("eth0\nreboot").is_err());
+        assert!(check_adapter_name("Wi-Fi\t2").is_err());
+        assert!(check_adapter_name("eth0.100").is_ok());
+        assert!(check_adapter_name("enp0s31f6").is_ok());
+        if cfg!(target_os = "windows") {
+            assert!(check_adapter_name("Ethernet 2").is_ok());
+            assert!(check_adapter_name("Wi-Fi; Restart-Computer").is_ok());
+            assert!(check_adapter_name("Ethernet \"2\"").is_err());
+            assert!(check_adapter_name("Ethernet `2").is_err());
+        } else {
+            assert!(check_adapter_name("Ethernet 2").is_err());
+            assert!(check_adapter_name("eth0;reboot").is_ok());
+            assert!(check_adapter_name("../eth0").is_err());
+            assert!(check_adapter_name("eth0:1").is_err());
+            assert!(check_adapter_name("a-very-long-name0").is_err());
+        }
+    }
+
+    #[test]
+    fn invalid_adapter_names_are_refused_before_anything_runs() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { adapter: "eth0\n".to_string(), ..profile() };
+        assert!(matches!(load(&profile, &mut backend, false), ApplyOutcome::Failed(NetProfilerError::Profile(_))));
+        assert!(backend.commands.is_empty());
+    }
+
+    #[test]
+    fn powershell_quoting_keeps_names_literal() {
+        assert_eq!(powershell_quote("Ethernet 2"), "'Ethernet 2'");
+        assert_eq!(powershell_quote("Wi-Fi; $x"), "'Wi-Fi; $x'");
+        assert_eq!(powershell_quote("Bob's NIC"), "'Bob''s NIC'");
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn windows_adapter_names_with_spaces_go_to_netsh_as_they_are() {
+        let mut backend = RecordingBackend::default();
+        let profile = NetworkProfile { adapter: "Ethernet 2".to_string(), mac_address: Some("02:00:00:00:00:01".to_string()), ..profile() };
+        assert_eq!(load(&profile, &mut backend, false), ApplyOutcome::Applied);
+        assert!(backend.commands.iter().any(|command| command.starts_with("netsh interface ip set address Ethernet 2 static")));
+        assert!(backend.commands.iter().any(|command| command.contains("Set-NetAdapterAdvancedProperty -Name 'Ethernet 2' ")));
+    }
+
+    #[test]
+    fn diff_lists_what_an_apply_would_change() {
+        let current = NetworkProfile {
+            gateway: String::new(),
+            dns_provider: DNSProvider::Custom,
+            primary_dns: "9.9.9.9".to_string(),
+            secondary_dns: "8.8.8.8".to_string(),
+            ..profile()
+        };
+        let target = NetworkProfile { ip: "192.168.1.20".to_string(), ..profile() };
+        assert_eq!(current.diff(&target).entries, vec![
+            DiffEntry::Changed { setting: "IP", from: "192.168.1.10/24".to_string(), to: "192.168.1.20/24".to_string() },
+            DiffEntry::Added { setting: "Gateway", value: "192.168.1.1".to_string() },
+            DiffEntry::Removed { setting: "DNS", value: "8.8.8.8".to_string() },
+            DiffEntry::Added { setting: "DNS", value: "149.112.112.112".to_string() },
+        ]);
+        assert!(target.diff(&target).is_empty());
+
+        // Settings the target leaves alone aren't changes
+        let untouched = NetworkProfile { ipv4_policy: FamilyPolicy::LeaveAlone, dns_provider: DNSProvider::None, ..Default::default() };
+        assert!(current.diff(&untouched).is_empty());
+
+        // Nor are the sections left out of the apply
+        assert_eq!(current.diff_sections(&target, ApplySections::GATEWAY).entries, vec![
+            DiffEntry::Added { setting: "Gateway", value: "192.168.1.1".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn change_report_tabulates_each_setting() {
+        let current = NetworkProfile {
+            gateway: String::new(),
+            dns_provider: DNSProvider::Custom,
+            primary_dns: "8.8.8.8".to_string(),
+            mtu: Some(1500),
+            ..profile()
+        };
+        let target = NetworkProfile {
+            ip: "192.168.1.20".to_string(),
+            mtu: Some(1500),
+            routes: vec![StaticRoute { destination: "10.50.0.0".to_string(), prefix: 16, via: "192.168.1.254".to_string(), metric: None }],
+            hostname: Some("rov-01".to_string()),
+            ..profile()
+        };
+        assert_eq!(generate_change_report(&current, &target, ApplySections::all()), concat!(
+            "# Change Report: ROV\n\n",
+            "Adapter: eth0\n\n",
+            "| Setting | Current | Target | Change |\n",
+            "|---|---|---|---|\n",
+            "| IP | 192.168.1.10/24 | 192.168.1.20/24 | changed |\n",
+            "| Gateway | - | 192.168.1.1 | set |\n",
+            "| IPv6 | - | - | left as is |\n",
+            "| DNS | 8.8.8.8 | 9.9.9.9, 149.112.112.112 | changed |\n",
+            "| Routes | - | 10.50.0.0/16 via 192.168.1.254 | set |\n",
+            "| Route metric | - | - | left as is |\n",
+            "| MTU | 1500 | 1500 | unchanged |\n",
+            "| MAC | - | - | left as is |\n",
+            "| Proxy | - | none | set |\n",
+            "| Hostname | - | rov-01 | set |\n",
+        ));
+
+        // A target without DNS doesn't touch the servers already there
+        let no_dns = NetworkProfile { dns_provider: DNSProvider::None, ..target.clone() };
+        assert!(generate_change_report(&current, &no_dns, ApplySections::all()).contains("| DNS | 8.8.8.8 | - | left as is |"));
+
+        // Sections left unchecked are listed without being changed
+        let report = generate_change_report(&current, &target, ApplySections::DNS);
+        assert!(report.contains("| IP | 192.168.1.10/24 | 192.168.1.20/24 | not applied |"));
+        assert!(report.contains("| Routes | - | 10.50.0.0/16 via 192.168.1.254 | not applied |"));
+        assert!(report.contains("| Hostname | - | rov-01 | not applied |"));
+        assert!(report.contains("| DNS | 8.8.8.8 | 9.9.9.9, 149.112.112.112 | changed |"));
+    }
+
+    #[test]
+    fn change_report_follows_the_address_policy() {
+        // A DHCP profile's leftover static address isn't what it applies
+        let dhcp = NetworkProfile { ipv4_policy: FamilyPolicy::Dhcp, ipv6_policy: FamilyPolicy::Dhcp, ..profile() };
+        let report = generate_change_report(&profile(), &dhcp, ApplySections::all());
+        assert!(report.contains("| IP | 192.168.1.10/24 | DHCP | changed |"));
+        assert!(report.contains("| Gateway | 192.168.1.1 | - | left as is |"));
+        assert!(report.contains("| IPv6 | - | SLAAC/DHCPv6 | set |"));
+
+        let leave_alone = NetworkProfile { ipv4_policy: FamilyPolicy::LeaveAlone, ip: "10.0.0.1".to_string(), ..profile() };
+        let report = generate_change_report(&profile(), &leave_alone, ApplySections::all());
+        assert!(report.contains("| IP | 192.168.1.10/24 | - | left as is |"));
+    }
+
+    #[test]
+    fn conflict_checks_refuse_bad_input_before_probing() {
+        assert_eq!(check_address_conflict("192.168.1", "eth0"), Err(NetProfilerError::InvalidIp("192.168.1".to_string())));
+        assert!(matches!(check_address_conflict("192.168.1.10", "eth0\n"), Err(NetProfilerError::Profile(_))));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn route_gateways_reads_each_via() {
+        let output = "default via 192.168.1.1 proto dhcp src 192.168.1.10 metric 100\ndefault via 10.0.0.1 metric 600\ndefault dev wg0 scope link\n";
+        assert_eq!(route_gateways(output), vec!["192.168.1.1", "10.0.0.1"]);
+    }
+}
@@ -0,0 +1,34 @@
+use notify_rust::Notification;
+
+pub enum NotificationKind {
+    Success,
+    Failure,
+}
+
+/// Shows an OS-native notification so results are seen even when the window isn't focused.
+/// Sent from a separate thread since the notification daemon can be slow to respond.
+pub fn notify(title: &str, body: &str, kind: NotificationKind) {
+    let mut notification = Notification::new();
+    notification
+        .appname("Net Profiler")
+        .summary(title)
+        .body(body);
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    match kind {
+        NotificationKind::Success => notification.icon("dialog-information"),
+        NotificationKind::Failure => notification.icon("dialog-error").urgency(notify_rust::Urgency::Critical),
+    };
+
+    // Windows toasts have no stock icons to pick from, so failures are marked in the title
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    if let NotificationKind::Failure = kind {
+        notification.summary(&format!("⚠ {}", title));
+    }
+
+    std::thread::spawn(move || {
+        if let Err(e) = notification.show() {
+            log::error!("Error showing notification: {}", e);
+        }
+    });
+}
@@ -0,0 +1,102 @@
+use eframe::egui;
+use egui::{Key, Modifiers};
+
+/// Most matches listed at once, the rest are reached by refining the query
+const MAX_RESULTS: usize = 12;
+
+/// Ctrl+P overlay for finding and running any action by name.
+#[derive(Default)]
+pub struct CommandPalette {
+    query: String,
+    selected: usize,
+}
+
+pub enum PaletteAction<T> {
+    None,
+    Run(T),
+    Close,
+}
+
+impl CommandPalette {
+    /// Shows the palette over the given commands, labelled by the strings they're searched by.
+    pub fn show<T: Clone>(&mut self, ctx: &egui::Context, commands: &[(String, T)]) -> PaletteAction<T> {
+        let mut matches: Vec<(i32, &(String, T))> = commands.iter()
+            .filter_map(|command| fuzzy_score(&self.query, &command.0).map(|score| (score, command)))
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.0.cmp(&b.1.0)));
+        matches.truncate(MAX_RESULTS);
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        // Take the navigation keys before the text field sees them
+        let (up, down, enter, escape) = ctx.input_mut(|i| (
+            i.consume_key(Modifiers::NONE, Key::ArrowUp),
+            i.consume_key(Modifiers::NONE, Key::ArrowDown),
+            i.consume_key(Modifiers::NONE, Key::Enter),
+            i.consume_key(Modifiers::NONE, Key::Escape),
+        ));
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if down {
+            self.selected = (self.selected + 1).min(matches.len().saturating_sub(1));
+        }
+        if escape {
+            return PaletteAction::Close;
+        }
+        if enter {
+            return match matches.get(self.selected) {
+                Some((_, (_, command))) => PaletteAction::Run(command.clone()),
+                None => PaletteAction::None,
+            };
+        }
+
+        let mut action = PaletteAction::None;
+        egui::Window::new("Command Palette")
+            .title_bar(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+            .show(ctx, |ui| {
+                let search = ui.add(egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Type a command...")
+                    .desired_width(360.0));
+                search.request_focus();
+                if search.changed() {
+                    self.selected = 0;
+                }
+
+                ui.separator();
+                if matches.is_empty() {
+                    ui.label("No matching commands");
+                }
+                for (index, (_, (label, command))) in matches.iter().enumerate() {
+                    if ui.selectable_label(index == self.selected, label).clicked() {
+                        action = PaletteAction::Run(command.clone());
+                    }
+                }
+            });
+        action
+    }
+}
+
+/// Scores how well `query` matches `candidate` as a case-insensitive subsequence, favouring
+/// consecutive characters and word starts. None if the query's characters don't all appear in order.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + candidate[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if previous_match.is_some_and(|previous| previous + 1 == found) {
+            score += 4;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        score -= (found - position) as i32 / 4;
+        previous_match = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
@@ -0,0 +1,229 @@
+use std::{collections::HashMap, net::Ipv4Addr, path::PathBuf};
+
+use eframe::egui;
+use egui::{Color32, RichText};
+
+use crate::file_operations;
+use crate::network::{self, DNSProvider};
+
+/// App wide preferences, saved with the rest of the app's state
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Subnet mask new profiles start with
+    pub default_subnet: String,
+    /// DNS provider new profiles start with
+    pub default_dns: DNSProvider,
+    /// Ask before applying a profile, other than the safe profile
+    pub confirm_apply: bool,
+    /// Where the profile library is autosaved, or None for the app's data directory
+    pub library_path: Option<PathBuf>,
+    pub theme: Theme,
+    /// Show OS notifications for apply results, for when the window isn't focused
+    pub os_notifications: bool,
+    /// Export without pretty printing, for when file size matters more than readability
+    pub compact_export: bool,
+    /// Addresses used for built-in DNS providers in place of their defaults
+    pub provider_dns: HashMap<DNSProvider, [String; 2]>,
+    /// Provider addresses being typed that aren't valid yet, kept out of `provider_dns` until they are
+    #[serde(skip)]
+    provider_drafts: HashMap<DNSProvider, [String; 2]>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_subnet: "255.255.255.0".to_string(),
+            default_dns: DNSProvider::None,
+            confirm_apply: true,
+            library_path: None,
+            theme: Theme::System,
+            os_notifications: false,
+            compact_export: false,
+            provider_dns: HashMap::new(),
+            provider_drafts: HashMap::new(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    /// Follow the OS, where eframe can tell, and dark otherwise
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Dark, Theme::Light];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::System => "System",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+}
+
+pub enum SettingsAction {
+    None,
+    /// Pick a new file for the profile library
+    BrowseLibrary,
+    /// The library moved, so the profiles need writing to the new file
+    LibraryMoved,
+}
+
+impl Settings {
+    /// The file the profile library is kept in.
+    pub fn library_path(&self) -> PathBuf {
+        self.library_path.clone().unwrap_or_else(file_operations::library_path)
+    }
+
+    /// The subnet mask new profiles start with, the built-in default while the setting isn't a valid mask.
+    pub fn new_profile_subnet(&self) -> String {
+        match network::subnet_prefix(&self.default_subnet) {
+            Some(_) => self.default_subnet.clone(),
+            None => Settings::default().default_subnet,
+        }
+    }
+
+    /// Switches the app to the chosen theme, when it isn't showing it already.
+    pub fn apply_theme(&self, ctx: &egui::Context, frame: &eframe::Frame) {
+        let theme = match self.theme {
+            Theme::System => frame.info().system_theme.unwrap_or(eframe::Theme::Dark),
+            Theme::Dark => eframe::Theme::Dark,
+            Theme::Light => eframe::Theme::Light,
+        };
+        if ctx.style().visuals.dark_mode != (theme == eframe::Theme::Dark) {
+            ctx.set_visuals(theme.egui_visuals());
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool) -> SettingsAction {
+        let mut action = SettingsAction::None;
+        let overrides = self.provider_dns.clone();
+        egui::Window::new("Settings").open(open).show(ctx, |ui| {
+            egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
+                let label = ui.label("Default subnet");
+                let valid = network::subnet_prefix(&self.default_subnet).is_some();
+                ui.add(egui::TextEdit::singleline(&mut self.default_subnet)
+                    .desired_width(120.0)
+                    .text_color_opt((!valid).then_some(Color32::RED)))
+                    .on_hover_text("Subnet mask new profiles start with, dotted or as a prefix length; 255.255.255.0 while it isn't valid")
+                    .labelled_by(label.id);
+                ui.end_row();
+
+                let label = ui.label("Default DNS");
+                egui::ComboBox::from_id_source("default_dns")
+                    .selected_text(format!("{:?}", self.default_dns))
+                    .show_ui(ui, |ui| {
+                        for provider in [DNSProvider::None, DNSProvider::Quad9, DNSProvider::Google, DNSProvider::Cloudflare, DNSProvider::OpenDNS] {
+                            let text = format!("{:?}", provider);
+                            ui.selectable_value(&mut self.default_dns, provider, text);
+                        }
+                    })
+                    .response
+                    .labelled_by(label.id);
+                ui.end_row();
+
+                ui.label("Apply");
+                ui.checkbox(&mut self.confirm_apply, "Confirm before applying")
+                    .on_hover_text("Ask before a profile reconfigures an interface, except the safe profile");
+                ui.end_row();
+
+                ui.label("Notifications");
+                ui.checkbox(&mut self.os_notifications, "OS notifications")
+                    .on_hover_text("Notify when a profile finishes applying, even while minimized");
+                ui.end_row();
+
+                ui.label("Export");
+                ui.checkbox(&mut self.compact_export, "Compact")
+                    .on_hover_text("Write exported profiles on a single line instead of pretty printed");
+                ui.end_row();
+
+                ui.label("Profile library");
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(self.library_path().display().to_string()).monospace().small());
+                    if ui.button("Browse").clicked() {
+                        action = SettingsAction::BrowseLibrary;
+                    }
+                    if ui.add_enabled(self.library_path.is_some(), egui::Button::new("Default")).clicked() {
+                        self.library_path = None;
+                        action = SettingsAction::LibraryMoved;
+                    }
+                });
+                ui.end_row();
+
+                let label = ui.label("Theme");
+                egui::ComboBox::from_id_source("theme")
+                    .selected_text(self.theme.label())
+                    .show_ui(ui, |ui| {
+                        for theme in Theme::ALL {
+                            ui.selectable_value(&mut self.theme, theme, theme.label());
+                        }
+                    })
+                    .response
+                    .labelled_by(label.id);
+                ui.end_row();
+            });
+
+            ui.separator();
+            ui.label(RichText::new("DNS providers").strong());
+            egui::Grid::new("provider_dns").show(ui, |ui| {
+                for (provider, defaults) in network::PROVIDER_DNS {
+                    let mut servers = self.provider_drafts.get(&provider)
+                        .or(self.provider_dns.get(&provider))
+                        .cloned()
+                        .unwrap_or(defaults.map(String::from));
+                    let mut changed = false;
+                    ui.label(format!("{:?}", provider));
+                    for server in servers.iter_mut() {
+                        let valid = server.parse::<Ipv4Addr>().is_ok();
+                        changed |= ui.add(egui::TextEdit::singleline(server).desired_width(120.0).text_color_opt((!valid).then_some(Color32::RED))).changed();
+                    }
+                    if ui.add_enabled(servers != defaults, egui::Button::new("Reset"))
+                        .on_hover_text(defaults.join(", "))
+                        .clicked()
+                    {
+                        servers = defaults.map(String::from);
+                        changed = true;
+                    }
+                    if changed {
+                        // Only addresses that parse reach the overrides applies use
+                        match servers.iter().all(|server| server.parse::<Ipv4Addr>().is_ok()) {
+                            true => {
+                                self.provider_drafts.remove(&provider);
+                                match servers == defaults {
+                                    true => self.provider_dns.remove(&provider),
+                                    false => self.provider_dns.insert(provider, servers),
+                                };
+                            }
+                            false => {
+                                self.provider_drafts.insert(provider, servers);
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.label(RichText::new("Profiles using a provider get these addresses the next time they're applied").small().weak());
+
+            ui.separator();
+            if ui.button("Restore Defaults").clicked() {
+                let moved = self.library_path.is_some();
+                *self = Settings::default();
+                if moved {
+                    action = SettingsAction::LibraryMoved;
+                }
+            }
+        });
+        if self.provider_dns != overrides {
+            network::set_provider_overrides(&self.provider_dns);
+        }
+        action
+    }
+}